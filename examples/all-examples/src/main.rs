@@ -0,0 +1,337 @@
+//! Serves the documented examples from a single server.
+//!
+//! Every example is mounted under `/<example>/<module>`, e.g.
+//! `/errors/override/` or `/responses/brotli/`, and the routes of an
+//! example's `lib.rs` live directly under `/<example>/`. Settings that a
+//! `ServiceConfig` cannot carry, like middleware and default services, are
+//! applied to the example's scope here, mirroring the example's own `main`.
+//! `Logger` and `Compress` change the response body type, which only an
+//! `App` allows, so they wrap the whole server instead; the examples that
+//! use `Compress::new(ContentEncoding::Br)` fall back to the default
+//! encoding.
+//!
+//! The event log of `powerful-extractors` is opened once, in the temporary
+//! directory, and shared by all workers.
+//!
+//! Examples that configure the server itself (`server`, `http2`), need a
+//! database or other external setup (`databases`, `websockets`), or share
+//! state between workers (`request-handlers/handlers_arc.rs`) are not
+//! mounted.
+
+use actix_web::{
+    guard, http::Method, middleware, web, App, HttpResponse, HttpServer,
+};
+use my_errors as errors;
+use once_cell::sync::Lazy;
+
+/// Scopes are matched in registration order, so an example's modules are
+/// registered before the catch-all routes of its `lib.rs`.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/getting-started").configure(getting_started::configure),
+    )
+    .service(web::scope("/main-example").configure(main_example::configure))
+    .service(
+        web::scope("/request-routing").configure(request_routing::configure),
+    )
+    .service(
+        web::scope("/flexible-responders")
+            .configure(flexible_responders::configure),
+    )
+    .service(
+        web::scope("/easy-form-handling")
+            .configure(easy_form_handling::configure),
+    )
+    .service(
+        web::scope("/powerful-extractors")
+            .app_data(event_store())
+            .configure(powerful_extractors::configure),
+    )
+    .service(url_dispatch_scope())
+    .service(extractors_scope())
+    .service(errors_scope())
+    .service(responses_scope())
+    .service(requests_scope())
+    .service(
+        web::scope("/async-handlers")
+            .configure(async_handlers::stream::configure),
+    )
+    .service(web::scope("/either").configure(either::configure))
+    .service(
+        web::scope("/responder-trait").configure(responder_trait::configure),
+    )
+    .service(
+        web::scope("/request-handlers").configure(request_handlers::configure),
+    );
+}
+
+fn event_store() -> web::Data<powerful_extractors::store::EventStore> {
+    use powerful_extractors::store::EventStore;
+
+    static STORE: Lazy<web::Data<EventStore>> = Lazy::new(|| {
+        let path = std::env::temp_dir().join("actix-events.jsonl");
+        web::Data::new(EventStore::open(path).expect("cannot open event log"))
+    });
+    STORE.clone()
+}
+
+fn url_dispatch_scope() -> actix_web::Scope {
+    web::scope("/url-dispatch")
+        .service(web::scope("/cfg").configure(url_dispatch::cfg::configure))
+        .service(
+            web::scope("/default")
+                .configure(url_dispatch::dhandler::configure)
+                .default_service(
+                    web::route()
+                        .guard(guard::Not(guard::Get()))
+                        .to(HttpResponse::MethodNotAllowed),
+                ),
+        )
+        .service(
+            web::scope("/guard").configure(url_dispatch::guard::configure),
+        )
+        .service(
+            web::scope("/guard2").configure(url_dispatch::guard2::configure),
+        )
+        .service(
+            web::scope("/minfo").configure(url_dispatch::minfo::configure),
+        )
+        .service(
+            web::scope("/norm")
+                .wrap(middleware::NormalizePath::default())
+                .configure(url_dispatch::norm::configure),
+        )
+        .service(
+            web::scope("/norm2")
+                .wrap(middleware::NormalizePath::default())
+                .configure(url_dispatch::norm2::configure)
+                .default_service(web::route().method(Method::GET)),
+        )
+        .service(web::scope("/path").configure(url_dispatch::path::configure))
+        .service(
+            web::scope("/path2").configure(url_dispatch::path2::configure),
+        )
+        .service(
+            web::scope("/resource")
+                .configure(url_dispatch::resource::configure),
+        )
+        .service(
+            web::scope("/scope").configure(url_dispatch::scope::configure),
+        )
+        .service(
+            web::scope("/url-ext").configure(url_dispatch::url_ext::configure),
+        )
+        .service(web::scope("/urls").configure(url_dispatch::urls::configure))
+        .configure(url_dispatch::configure)
+}
+
+fn extractors_scope() -> actix_web::Scope {
+    web::scope("/extractors")
+        .service(web::scope("/form").configure(extractors::form::configure))
+        .service(
+            web::scope("/json-one").configure(extractors::json_one::configure),
+        )
+        .service(
+            web::scope("/json-two").configure(extractors::json_two::configure),
+        )
+        .service(
+            web::scope("/multiple").configure(extractors::multiple::configure),
+        )
+        .service(
+            web::scope("/path-one").configure(extractors::path_one::configure),
+        )
+        .service(
+            web::scope("/path-two").configure(extractors::path_two::configure),
+        )
+        .service(
+            web::scope("/path-three")
+                .configure(extractors::path_three::configure),
+        )
+        .service(web::scope("/query").configure(extractors::query::configure))
+        .service(
+            web::scope("/validated")
+                .configure(extractors::validated::configure),
+        )
+        .configure(extractors::configure)
+}
+
+fn errors_scope() -> actix_web::Scope {
+    web::scope("/errors")
+        .service(web::scope("/helpers").configure(errors::helpers::configure))
+        .service(
+            web::scope("/logging")
+                .wrap(request_id::RequestLog::new())
+                .configure(errors::logging::configure),
+        )
+        .service(
+            web::scope("/override")
+                .configure(errors::override_error::configure),
+        )
+        .service(
+            web::scope("/problem")
+                .wrap(errors::problem::NegotiateProblems)
+                .configure(errors::problem::configure),
+        )
+        .service(
+            web::scope("/recommend-one")
+                .configure(errors::recommend_one::configure),
+        )
+        .service(
+            web::scope("/recommend-two")
+                .configure(errors::recommend_two::configure),
+        )
+        .configure(errors::configure)
+}
+
+fn responses_scope() -> actix_web::Scope {
+    web::scope("/responses")
+        .service(web::scope("/auto").configure(responses::auto::configure))
+        .service(web::scope("/brotli").configure(responses::brotli::configure))
+        .service(
+            web::scope("/brotli-two")
+                .configure(responses::brotli_two::configure),
+        )
+        .service(
+            web::scope("/chunked").configure(responses::chunked::configure),
+        )
+        .service(
+            web::scope("/compress").configure(responses::compress::configure),
+        )
+        .service(
+            web::scope("/identity").configure(responses::identity::configure),
+        )
+        .service(
+            web::scope("/identity-two")
+                .configure(responses::identity_two::configure),
+        )
+        .service(
+            web::scope("/json").configure(responses::json_resp::configure),
+        )
+        .configure(responses::configure)
+}
+
+fn requests_scope() -> actix_web::Scope {
+    let uploads = requests::multipart::UploadConfig {
+        dir: std::env::temp_dir().join("actix-uploads"),
+        max_field_size: 1024 * 1024,
+        max_total_size: 4 * 1024 * 1024,
+        allowed_types: vec![
+            mime::IMAGE_PNG,
+            mime::IMAGE_JPEG,
+            mime::TEXT_PLAIN,
+        ],
+    };
+
+    web::scope("/requests")
+        .service(web::scope("/manual").configure(requests::manual::configure))
+        .service(
+            web::scope("/multipart")
+                .app_data(web::Data::new(uploads))
+                .configure(requests::multipart::configure),
+        )
+        .service(
+            web::scope("/streaming").configure(requests::streaming::configure),
+        )
+        .service(
+            web::scope("/urlencoded")
+                .configure(requests::urlencoded::configure),
+        )
+        .configure(requests::configure)
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    env_logger::init();
+    std::fs::create_dir_all(std::env::temp_dir().join("actix-uploads"))?;
+
+    HttpServer::new(|| {
+        App::new()
+            .wrap(middleware::Logger::default())
+            .wrap(middleware::Compress::default())
+            .configure(configure)
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await
+}
+
+#[cfg(test)]
+mod mount_tests {
+    use super::*;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::test;
+
+    #[actix_rt::test]
+    async fn test_examples_are_mounted() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(middleware::Compress::default())
+                .configure(configure),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/getting-started/hey")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Hey there!");
+
+        let req = test::TestRequest::get()
+            .uri("/extractors/path-one/users/42/ferris")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Welcome ferris, user_id 42!");
+
+        let req = test::TestRequest::get()
+            .uri("/errors/override/")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/errors/problem/orders/2")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/responses/brotli/")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "br"
+        );
+
+        // `url_for` includes the scope an example is mounted under
+        let req = test::TestRequest::get()
+            .uri("/url-dispatch/urls/test/")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(
+            resp.headers().get(header::LOCATION).unwrap(),
+            "http://localhost:8080/url-dispatch/urls/test/1/2/3"
+        );
+
+        // module scopes are registered before the routes of each lib.rs
+        let req = test::TestRequest::get().uri("/url-dispatch/").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Hello");
+
+        let req = test::TestRequest::get()
+            .uri("/url-dispatch/norm/resource")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Hello");
+    }
+}
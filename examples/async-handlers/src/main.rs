@@ -1,3 +1 @@
-pub mod stream;
-
 fn main() {}
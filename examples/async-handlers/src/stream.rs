@@ -19,3 +19,27 @@ async fn main() -> std::io::Result<()> {
         .await
 }
 // </stream>
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(stream);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+
+    #[actix_rt::test]
+    async fn test_stream() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/stream").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(
+            resp.headers().get("content-type").unwrap(),
+            "application/json"
+        );
+        assert_eq!(test::read_body(resp).await, "test");
+    }
+}
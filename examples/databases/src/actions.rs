@@ -0,0 +1,76 @@
+use diesel::prelude::*;
+
+use crate::models;
+
+type DbError = diesel::result::Error;
+
+// <handler>
+pub fn insert_new_user(
+    conn: &SqliteConnection,
+    user: models::CreateUser,
+) -> Result<models::User, DbError> {
+    use crate::schema::users::dsl::*;
+
+    // Create insertion model
+    let uuid = uuid::Uuid::new_v4().to_string();
+    let new_user = models::NewUser {
+        id: &uuid,
+        name: &user.name,
+    };
+
+    // normal diesel operations
+    diesel::insert_into(users).values(&new_user).execute(conn)?;
+
+    users.filter(id.eq(&uuid)).first::<models::User>(conn)
+}
+// </handler>
+
+pub fn find_user_by_id(
+    conn: &SqliteConnection,
+    user_id: &str,
+) -> Result<Option<models::User>, DbError> {
+    use crate::schema::users::dsl::*;
+
+    users
+        .filter(id.eq(user_id))
+        .first::<models::User>(conn)
+        .optional()
+}
+
+pub fn update_user_name(
+    conn: &SqliteConnection,
+    user_id: &str,
+    user: models::CreateUser,
+) -> Result<Option<models::User>, DbError> {
+    use crate::schema::users::dsl::*;
+
+    let updated = diesel::update(users.filter(id.eq(user_id)))
+        .set(name.eq(&user.name))
+        .execute(conn)?;
+
+    if updated == 0 {
+        return Ok(None);
+    }
+
+    find_user_by_id(conn, user_id)
+}
+
+pub fn delete_user(
+    conn: &SqliteConnection,
+    user_id: &str,
+) -> Result<bool, DbError> {
+    use crate::schema::users::dsl::*;
+
+    let deleted =
+        diesel::delete(users.filter(id.eq(user_id))).execute(conn)?;
+
+    Ok(deleted > 0)
+}
+
+pub fn list_users(
+    conn: &SqliteConnection,
+) -> Result<Vec<models::User>, DbError> {
+    use crate::schema::users::dsl::*;
+
+    users.order(name.asc()).load::<models::User>(conn)
+}
@@ -1,63 +1,257 @@
-// <handler>
-fn insert_new_user(db: &SqliteConnection, user: CreateUser) -> Result<User, Error> {
-    use self::schema::users::dsl::*;
+#[macro_use]
+extern crate diesel;
+#[macro_use]
+extern crate diesel_migrations;
 
-    // Create insertion model
-    let uuid = format!("{}", uuid::Uuid::new_v4());
-    let new_user = models::NewUser {
-        id: &uuid,
-        name: &user.name,
-    };
+use actix_web::{web, App, Error, HttpResponse, HttpServer};
+use diesel::prelude::*;
+use diesel::r2d2::{self, ConnectionManager};
 
-    // normal diesel operations
-    diesel::insert_into(users)
-        .values(&new_user)
-        .execute(&self.0)
-        .expect("Error inserting person");
+mod actions;
+mod models;
+mod schema;
 
-    let mut items = users
-        .filter(id.eq(&uuid))
-        .load::<models::User>(&self.0)
-        .expect("Error loading person");
-
-    Ok(items.pop().unwrap())
-}
-// </handler>
+embed_migrations!();
 
 // <main>
 type DbPool = r2d2::Pool<ConnectionManager<SqliteConnection>>;
 
+fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/users", web::get().to(list_users))
+        .route("/users", web::post().to(add_user))
+        .route("/users/{user_id}", web::get().to(get_user))
+        .route("/users/{user_id}", web::put().to(update_user))
+        .route("/users/{user_id}", web::delete().to(delete_user));
+}
+
 #[actix_web::main]
-async fn main() -> io::Result<()> {
+async fn main() -> std::io::Result<()> {
+    let database_url = std::env::var("DATABASE_URL")
+        .unwrap_or_else(|_| String::from("users.db"));
+    let manager = ConnectionManager::<SqliteConnection>::new(database_url);
+
     // Create connection pool
     let pool = r2d2::Pool::builder()
         .build(manager)
         .expect("Failed to create pool.");
 
+    // Create the `users` table if this is a fresh database
+    let conn = pool.get().expect("couldn't get db connection from pool");
+    embedded_migrations::run(&conn).expect("Failed to run migrations.");
+
     // Start HTTP server
-    HttpServer::new(move || {
-        App::new::data(pool.clone())
-            .resource("/{name}", web::get().to(index))
-    })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+    HttpServer::new(move || App::new().data(pool.clone()).configure(config))
+        .bind("127.0.0.1:8080")?
+        .run()
+        .await
 }
 // </main>
 
 // <index>
-async fn index(pool: web::Data<DbPool>, name: web::Path<(String)>) -> impl Responder {
-    let name = name.into_inner();
+async fn add_user(
+    pool: web::Data<DbPool>,
+    form: web::Json<models::CreateUser>,
+) -> Result<HttpResponse, Error> {
+    let conn = pool.get().expect("couldn't get db connection from pool");
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let user =
+        web::block(move || actions::insert_new_user(&conn, form.into_inner()))
+            .await
+            .map_err(|e| {
+                eprintln!("{}", e);
+                HttpResponse::InternalServerError().finish()
+            })?;
+
+    Ok(HttpResponse::Created().json(user))
+}
+// </index>
 
+async fn get_user(
+    pool: web::Data<DbPool>,
+    user_id: web::Path<String>,
+) -> Result<HttpResponse, Error> {
     let conn = pool.get().expect("couldn't get db connection from pool");
 
-    let user = web::block(move || actions::insert_new_user(&conn, &user))
+    let user = web::block(move || actions::find_user_by_id(&conn, &user_id))
         .await
         .map_err(|e| {
             eprintln!("{}", e);
             HttpResponse::InternalServerError().finish()
         })?;
-    
-    Ok(HttpResponse::Ok().json(user))
+
+    Ok(match user {
+        Some(user) => HttpResponse::Ok().json(user),
+        None => HttpResponse::NotFound().finish(),
+    })
+}
+
+async fn update_user(
+    pool: web::Data<DbPool>,
+    user_id: web::Path<String>,
+    form: web::Json<models::CreateUser>,
+) -> Result<HttpResponse, Error> {
+    let conn = pool.get().expect("couldn't get db connection from pool");
+
+    let user = web::block(move || {
+        actions::update_user_name(&conn, &user_id, form.into_inner())
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("{}", e);
+        HttpResponse::InternalServerError().finish()
+    })?;
+
+    Ok(match user {
+        Some(user) => HttpResponse::Ok().json(user),
+        None => HttpResponse::NotFound().finish(),
+    })
+}
+
+async fn delete_user(
+    pool: web::Data<DbPool>,
+    user_id: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let conn = pool.get().expect("couldn't get db connection from pool");
+
+    let deleted = web::block(move || actions::delete_user(&conn, &user_id))
+        .await
+        .map_err(|e| {
+            eprintln!("{}", e);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(if deleted {
+        HttpResponse::NoContent().finish()
+    } else {
+        HttpResponse::NotFound().finish()
+    })
+}
+
+async fn list_users(pool: web::Data<DbPool>) -> Result<HttpResponse, Error> {
+    let conn = pool.get().expect("couldn't get db connection from pool");
+
+    let users = web::block(move || actions::list_users(&conn))
+        .await
+        .map_err(|e| {
+            eprintln!("{}", e);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(HttpResponse::Ok().json(users))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test};
+
+    fn test_pool() -> DbPool {
+        // every `:memory:` connection is its own database, so the pool
+        // must hand out the same single connection to every handler
+        let manager = ConnectionManager::<SqliteConnection>::new(":memory:");
+        let pool = r2d2::Pool::builder().max_size(1).build(manager).unwrap();
+
+        embedded_migrations::run(&pool.get().unwrap()).unwrap();
+
+        pool
+    }
+
+    fn create_req(name: &str) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri("/users")
+            .set_json(&models::CreateUser {
+                name: name.to_owned(),
+            })
+    }
+
+    #[actix_rt::test]
+    async fn test_user_crud() {
+        let mut app =
+            test::init_service(App::new().data(test_pool()).configure(config))
+                .await;
+
+        // create
+        let resp =
+            test::call_service(&mut app, create_req("Ferris").to_request())
+                .await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let user: models::User = test::read_body_json(resp).await;
+        assert_eq!(user.name, "Ferris");
+
+        // read
+        let req = test::TestRequest::get()
+            .uri(&format!("/users/{}", user.id))
+            .to_request();
+        let found: models::User =
+            test::read_response_json(&mut app, req).await;
+        assert_eq!(found, user);
+
+        // update
+        let req = test::TestRequest::put()
+            .uri(&format!("/users/{}", user.id))
+            .set_json(&models::CreateUser {
+                name: String::from("Corro"),
+            })
+            .to_request();
+        let updated: models::User =
+            test::read_response_json(&mut app, req).await;
+        assert_eq!(updated.id, user.id);
+        assert_eq!(updated.name, "Corro");
+
+        // delete
+        let req = test::TestRequest::delete()
+            .uri(&format!("/users/{}", user.id))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/users/{}", user.id))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_list_users() {
+        let mut app =
+            test::init_service(App::new().data(test_pool()).configure(config))
+                .await;
+
+        for name in &["Zoe", "Ana"] {
+            let resp =
+                test::call_service(&mut app, create_req(name).to_request())
+                    .await;
+            assert_eq!(resp.status(), StatusCode::CREATED);
+        }
+
+        let req = test::TestRequest::get().uri("/users").to_request();
+        let users: Vec<models::User> =
+            test::read_response_json(&mut app, req).await;
+        let names: Vec<_> = users.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(names, ["Ana", "Zoe"]);
+    }
+
+    #[actix_rt::test]
+    async fn test_missing_user() {
+        let mut app =
+            test::init_service(App::new().data(test_pool()).configure(config))
+                .await;
+
+        let req = test::TestRequest::put()
+            .uri("/users/does-not-exist")
+            .set_json(&models::CreateUser {
+                name: String::from("Nobody"),
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::delete()
+            .uri("/users/does-not-exist")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
 }
-// </index>
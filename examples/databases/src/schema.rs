@@ -0,0 +1,9 @@
+// the impls that diesel 1.x `table!` generates are nested in other items
+#![allow(non_local_definitions)]
+
+table! {
+    users (id) {
+        id -> Text,
+        name -> Text,
+    }
+}
@@ -0,0 +1,105 @@
+//! Double-submit CSRF protection. The token lives in a cookie and every form
+//! repeats it in a hidden field. Another site can make the browser send the
+//! cookie, but it cannot read it to fill in the field.
+use std::fmt;
+
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::dev::{HttpResponseBuilder, Payload};
+use actix_web::http::StatusCode;
+use actix_web::{
+    Error, FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError,
+};
+use futures::future::{ok, Ready};
+use rand::Rng;
+
+pub const COOKIE: &str = "csrf";
+
+/// The client's token, freshly created if the request has no cookie yet.
+pub struct CsrfToken {
+    value: String,
+    is_new: bool,
+}
+
+impl CsrfToken {
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Stores a freshly created token in the client's cookie
+    pub fn set_cookie(&self, res: &mut HttpResponseBuilder) {
+        if self.is_new {
+            res.cookie(
+                Cookie::build(COOKIE, self.value.clone())
+                    .path("/")
+                    .http_only(true)
+                    .same_site(SameSite::Strict)
+                    .finish(),
+            );
+        }
+    }
+
+    /// Checks a token submitted with a form against the cookie
+    pub fn verify(&self, submitted: &str) -> Result<(), CsrfError> {
+        // a new token was never sent to the client, so it cannot match
+        if !self.is_new && constant_time_eq(&self.value, submitted) {
+            Ok(())
+        } else {
+            Err(CsrfError)
+        }
+    }
+}
+
+/// Compares every byte, so the time taken reveals nothing about the token
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+impl FromRequest for CsrfToken {
+    type Error = Error;
+    type Future = Ready<Result<CsrfToken, Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = match req.cookie(COOKIE) {
+            Some(cookie) => CsrfToken {
+                value: cookie.value().to_owned(),
+                is_new: false,
+            },
+            None => {
+                let bytes: [u8; 32] = rand::thread_rng().gen();
+                CsrfToken {
+                    value: bytes
+                        .iter()
+                        .map(|b| format!("{:02x}", b))
+                        .collect(),
+                    is_new: true,
+                }
+            }
+        };
+
+        ok(token)
+    }
+}
+
+#[derive(Debug)]
+pub struct CsrfError;
+
+impl fmt::Display for CsrfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the form has expired, please reload the page")
+    }
+}
+
+impl ResponseError for CsrfError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::FORBIDDEN
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::Forbidden().body(self.to_string())
+    }
+}
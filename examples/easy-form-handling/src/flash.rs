@@ -0,0 +1,55 @@
+//! One-off messages that survive a redirect. The message is stored in a
+//! cookie by the response that redirects and removed again by the page that
+//! shows it.
+use actix_web::cookie::Cookie;
+use actix_web::dev::{HttpResponseBuilder, Payload};
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest};
+use futures::future::{ok, Ready};
+
+pub const COOKIE: &str = "flash";
+
+/// Sets the message shown by the next page the client requests
+pub fn set(res: &mut HttpResponseBuilder, message: &str) {
+    // cookie values cannot contain spaces, commas or semicolons
+    let value = base64::encode_config(message, base64::URL_SAFE_NO_PAD);
+    res.cookie(
+        Cookie::build(COOKIE, value)
+            .path("/")
+            .http_only(true)
+            .finish(),
+    );
+}
+
+/// The message set by the previous response, if any
+pub struct Flash(Option<String>);
+
+impl Flash {
+    pub fn message(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+
+    /// Removes the message from the client, once it has been shown
+    pub fn clear(&self, res: &mut HttpResponseBuilder) {
+        if self.0.is_some() {
+            res.del_cookie(&Cookie::build(COOKIE, "").path("/").finish());
+        }
+    }
+}
+
+impl FromRequest for Flash {
+    type Error = Error;
+    type Future = Ready<Result<Flash, Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        // a cookie that does not decode is ignored, it only ever held text
+        let message = req.cookie(COOKIE).and_then(|cookie| {
+            let bytes =
+                base64::decode_config(cookie.value(), base64::URL_SAFE_NO_PAD)
+                    .ok()?;
+            String::from_utf8(bytes).ok()
+        });
+
+        ok(Flash(message))
+    }
+}
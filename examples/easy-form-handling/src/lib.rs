@@ -0,0 +1,276 @@
+// <easy-form-handling>
+use std::collections::HashMap;
+
+use actix_web::{http::header, web, Error, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use templating::{Template, Templates};
+use validation::ValidationErrors;
+
+pub mod csrf;
+pub mod flash;
+
+use csrf::CsrfToken;
+use flash::Flash;
+
+#[derive(Default, Deserialize, Serialize)]
+struct Register {
+    username: String,
+    country: String,
+}
+
+#[derive(Deserialize)]
+struct RegisterForm {
+    csrf_token: String,
+    #[serde(flatten)]
+    register: Register,
+}
+
+/// The form with the values and errors of the last submission
+fn form_page(
+    req: &HttpRequest,
+    csrf: &CsrfToken,
+    form: &Register,
+    errors: Option<&ValidationErrors>,
+    flash: Option<&str>,
+) -> Result<Template, Error> {
+    let errors: HashMap<_, _> = errors
+        .iter()
+        .flat_map(|errors| errors.errors.iter())
+        .map(|err| (err.field.as_str(), err.message.as_str()))
+        .collect();
+
+    Ok(Template::new("register.html")
+        .with("action", req.url_for_static("register")?.path())
+        .with("csrf_token", csrf.value())
+        .with("form", form)
+        .with("errors", &errors)
+        .with("flash", &flash))
+}
+
+async fn index(
+    req: HttpRequest,
+    csrf: CsrfToken,
+    flash: Flash,
+) -> Result<HttpResponse, Error> {
+    let form = Register::default();
+    let page = form_page(&req, &csrf, &form, None, flash.message())?;
+
+    let mut res = HttpResponse::Ok();
+    csrf.set_cookie(&mut res);
+    flash.clear(&mut res);
+    Ok(res
+        .content_type("text/html; charset=utf-8")
+        .body(page.render(&req)?))
+}
+
+async fn register(
+    req: HttpRequest,
+    csrf: CsrfToken,
+    form: web::Form<RegisterForm>,
+) -> Result<HttpResponse, Error> {
+    csrf.verify(&form.csrf_token)?;
+    let form = form.into_inner().register;
+
+    // show the form again, keeping what was typed in
+    if let Err(errors) = validation::validate(&form) {
+        let page = form_page(&req, &csrf, &form, Some(&errors), None)?;
+        return Ok(HttpResponse::UnprocessableEntity()
+            .content_type("text/html; charset=utf-8")
+            .body(page.render(&req)?));
+    }
+
+    // redirect, so reloading the page does not submit the form again
+    let mut res = HttpResponse::SeeOther();
+    flash::set(
+        &mut res,
+        &format!("Hello {} from {}!", form.username, form.country),
+    );
+    let location = req.url_for_static("register_form")?;
+    Ok(res.header(header::LOCATION, location.as_str()).finish())
+}
+
+/// Edits to `templates/` show up on the next request in debug builds
+fn templates() -> Templates {
+    let sources =
+        [("register.html", include_str!("../templates/register.html"))];
+    Templates::new(&sources)
+        .expect("invalid template")
+        .reload_from(concat!(env!("CARGO_MANIFEST_DIR"), "/templates"))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.data(templates())
+        .service(
+            web::resource("/")
+                .name("register_form")
+                .route(web::get().to(index)),
+        )
+        .service(
+            web::resource("/register")
+                .name("register")
+                .route(web::post().to(register)),
+        );
+}
+// </easy-form-handling>
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use validation::{custom, length, pattern, Validate, Validator};
+
+static USERNAME: Lazy<Regex> =
+    Lazy::new(|| Regex::new("^[A-Za-z0-9_]+$").unwrap());
+
+impl Validate for Register {
+    fn validate(&self, v: &mut Validator) {
+        v.field(
+            "username",
+            &self.username,
+            &[
+                &length(3, 32),
+                &pattern(
+                    &USERNAME,
+                    "may only contain letters, digits and underscores",
+                ),
+            ],
+        )
+        .field(
+            "country",
+            &self.country,
+            &[
+                &length(2, 56),
+                &custom("letters", |country: &String| {
+                    if country.chars().all(|c| c.is_alphabetic() || c == ' ') {
+                        Ok(())
+                    } else {
+                        Err(String::from("may only contain letters"))
+                    }
+                }),
+            ],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::cookie::Cookie;
+    use actix_web::dev::ServiceResponse;
+    use actix_web::{http::StatusCode, test, App};
+
+    fn cookie(resp: &ServiceResponse, name: &str) -> Option<Cookie<'static>> {
+        resp.response()
+            .cookies()
+            .find(|cookie| cookie.name() == name)
+            .map(Cookie::into_owned)
+    }
+
+    async fn body(resp: ServiceResponse) -> String {
+        let body = test::read_body(resp).await;
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn test_register() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+        let csrf = cookie(&resp, csrf::COOKIE).unwrap();
+        let field = format!(r#"name="csrf_token" value="{}""#, csrf.value());
+        assert!(body(resp).await.contains(&field));
+
+        let req = test::TestRequest::post()
+            .uri("/register")
+            .cookie(csrf.clone())
+            .set_form(&[
+                ("csrf_token", csrf.value()),
+                ("username", "ferris"),
+                ("country", "Norway"),
+            ])
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            resp.headers().get(header::LOCATION).unwrap(),
+            "http://localhost:8080/"
+        );
+        let flash = cookie(&resp, flash::COOKIE).unwrap();
+
+        // the message is shown once and removed again
+        let req = test::TestRequest::get()
+            .uri("/")
+            .cookie(csrf.clone())
+            .cookie(flash)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(cookie(&resp, csrf::COOKIE).is_none());
+        assert_eq!(cookie(&resp, flash::COOKIE).unwrap().value(), "");
+        assert!(body(resp).await.contains("Hello ferris from Norway!"));
+
+        let req = test::TestRequest::post()
+            .uri("/register")
+            .cookie(csrf.clone())
+            .set_form(&[("csrf_token", csrf.value()), ("username", "ferris")])
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn test_register_invalid() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+        let csrf = Cookie::new(csrf::COOKIE, "token");
+
+        let req = test::TestRequest::post()
+            .uri("/register")
+            .cookie(csrf.clone())
+            .set_form(&[
+                ("csrf_token", "token"),
+                ("username", "<b>"),
+                ("country", "N0rway"),
+            ])
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // the submitted values are kept, and escaped
+        let body = body(resp).await;
+        assert!(body.contains(r#"name="username" value="&lt;b&gt;""#));
+        assert!(body.contains(r#"name="country" value="N0rway""#));
+        assert!(body.contains("may only contain letters, digits and"));
+        assert!(body.contains("may only contain letters</span>"));
+    }
+
+    #[actix_rt::test]
+    async fn test_register_csrf() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+        let form = [
+            ("csrf_token", "token"),
+            ("username", "ferris"),
+            ("country", "Norway"),
+        ];
+
+        // without the cookie any token is rejected
+        let req = test::TestRequest::post()
+            .uri("/register")
+            .set_form(&form)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::post()
+            .uri("/register")
+            .cookie(Cookie::new(csrf::COOKIE, "other"))
+            .set_form(&form)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,42 @@
+// <either>
+use actix_web::{Either, Error, HttpResponse};
+
+type RegisterResult = Either<HttpResponse, Result<&'static str, Error>>;
+
+async fn index() -> RegisterResult {
+    if is_a_variant() {
+        // <- choose variant A
+        Either::A(HttpResponse::BadRequest().body("Bad data"))
+    } else {
+        // <- variant B
+        Either::B(Ok("Hello!"))
+    }
+}
+// </either>
+
+fn is_a_variant() -> bool {
+    true
+}
+
+pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
+    use actix_web::web;
+
+    cfg.route("/", web::get().to(index));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    #[actix_rt::test]
+    async fn test_either() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(test::read_body(resp).await, "Bad data");
+    }
+}
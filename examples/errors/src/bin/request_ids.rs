@@ -0,0 +1,21 @@
+// <request-id-main>
+use actix_web::{middleware::Logger, App, HttpServer};
+use my_errors::logging::configure;
+use request_id::RequestLog;
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    std::env::set_var("RUST_LOG", "info");
+    env_logger::init();
+
+    HttpServer::new(|| {
+        App::new()
+            .wrap(Logger::default())
+            .wrap(RequestLog::new())
+            .configure(configure)
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await
+}
+// </request-id-main>
@@ -16,8 +16,12 @@ async fn index() -> Result<&'static str> {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| App::new().service(index))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
+
+pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
+    cfg.service(index);
+}
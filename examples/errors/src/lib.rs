@@ -0,0 +1,30 @@
+pub mod helpers;
+pub mod logging;
+pub mod override_error;
+pub mod problem;
+pub mod recommend_one;
+pub mod recommend_two;
+
+// <response-error>
+use actix_web::{error, Result};
+use derive_more::{Display, Error};
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "my error: {}", name)]
+struct MyError {
+    name: &'static str,
+}
+
+// Use default implementation for `error_response()` method
+impl error::ResponseError for MyError {}
+
+async fn index() -> Result<&'static str, MyError> {
+    Err(MyError { name: "test" })
+}
+// </response-error>
+
+pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
+    use actix_web::web;
+
+    cfg.route("/", web::get().to(index));
+}
@@ -38,3 +38,76 @@ async fn main() -> std::io::Result<()> {
     .await
 }
 // </logging>
+
+// <request-id>
+use actix_web::{http::StatusCode, HttpResponse};
+use log::warn;
+use request_id::RequestId;
+use serde_json::json;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "card declined: {}", reason)]
+pub struct PaymentError {
+    reason: &'static str,
+}
+
+impl error::ResponseError for PaymentError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::PAYMENT_REQUIRED
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        // runs while the request is handled, so its id is known
+        let id = RequestId::current();
+        let id = id.as_ref().map(RequestId::as_str);
+        warn!("request {}: {}", id.unwrap_or("-"), self);
+
+        // the id lets support find the log lines of a failed request
+        HttpResponse::build(self.status_code()).json(json!({
+            "error": "payment declined",
+            "request_id": id,
+        }))
+    }
+}
+
+#[get("/pay")]
+async fn pay(id: RequestId) -> Result<&'static str, PaymentError> {
+    info!("request {}: charging card", id);
+    Err(PaymentError {
+        reason: "insufficient funds",
+    })
+}
+
+/// The handlers above, which the `main` below serves with `RequestLog`
+pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
+    cfg.service(index).service(pay);
+}
+// </request-id>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+    use request_id::RequestLog;
+
+    #[actix_rt::test]
+    async fn test_request_id_in_error() {
+        let mut app = test::init_service(
+            App::new().wrap(RequestLog::new()).configure(configure),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/pay")
+            .header(request_id::HEADER, "order-17")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::PAYMENT_REQUIRED);
+        assert_eq!(
+            resp.headers().get(request_id::HEADER).unwrap(),
+            "order-17"
+        );
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["request_id"], "order-17");
+    }
+}
@@ -1,32 +1,9 @@
-pub mod helpers;
-pub mod logging;
-pub mod override_error;
-pub mod recommend_one;
-pub mod recommend_two;
-
-// <response-error>
-use actix_web::{error, Result};
-use derive_more::{Display, Error};
-
-#[derive(Debug, Display, Error)]
-#[display(fmt = "my error: {}", name)]
-struct MyError {
-    name: &'static str,
-}
-
-// Use default implementation for `error_response()` method
-impl error::ResponseError for MyError {}
-
-async fn index() -> Result<&'static str, MyError> {
-    Err(MyError { name: "test" })
-}
-// </response-error>
+use actix_web::{App, HttpServer};
+use my_errors::configure;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    use actix_web::{web, App, HttpServer};
-
-    HttpServer::new(|| App::new().route("/", web::get().to(index)))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
@@ -1,9 +1,9 @@
 // <override>
-use actix_web::{
-    dev::HttpResponseBuilder, error, get, http::header, http::StatusCode, App, HttpResponse,
-};
+use actix_web::{error, get, http::StatusCode, App, HttpResponse};
 use derive_more::{Display, Error};
 
+use super::problem::Problem;
+
 #[derive(Debug, Display, Error)]
 enum MyError {
     #[display(fmt = "internal error")]
@@ -18,9 +18,9 @@ enum MyError {
 
 impl error::ResponseError for MyError {
     fn error_response(&self) -> HttpResponse {
-        HttpResponseBuilder::new(self.status_code())
-            .set_header(header::CONTENT_TYPE, "text/html; charset=utf-8")
-            .body(self.to_string())
+        Problem::new(self.status_code())
+            .with_detail(self.to_string())
+            .error_response()
     }
 
     fn status_code(&self) -> StatusCode {
@@ -52,8 +52,12 @@ async fn error3() -> Result<&'static str, MyError> {
 async fn main() -> std::io::Result<()> {
     use actix_web::HttpServer;
 
-    HttpServer::new(|| App::new().service(index).service(error2).service(error3))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
+
+pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
+    cfg.service(index).service(error2).service(error3);
+}
@@ -0,0 +1,563 @@
+#![allow(dead_code)]
+
+// <problem>
+use std::fmt;
+
+use actix_web::{
+    dev::HttpResponseBuilder, error, http::header, http::StatusCode, web,
+    HttpRequest, HttpResponse,
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// An error rendered as an RFC 7807 "problem details" document.
+#[derive(Debug, Clone, Serialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    type_uri: String,
+    title: String,
+    #[serde(serialize_with = "serialize_status")]
+    status: StatusCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance: Option<String>,
+    #[serde(flatten)]
+    extensions: Map<String, Value>,
+}
+
+fn serialize_status<S: serde::Serializer>(
+    status: &StatusCode,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u16(status.as_u16())
+}
+
+impl Problem {
+    /// A problem without a more specific type, titled after the status code.
+    pub fn new(status: StatusCode) -> Self {
+        Problem {
+            type_uri: String::from("about:blank"),
+            title: status.canonical_reason().unwrap_or("Error").to_owned(),
+            status,
+            detail: None,
+            instance: None,
+            extensions: Map::new(),
+        }
+    }
+
+    pub fn with_type(mut self, type_uri: &str, title: &str) -> Self {
+        self.type_uri = type_uri.to_owned();
+        self.title = title.to_owned();
+        self
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn with_instance(mut self, instance: impl Into<String>) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Adds an extension member. `type`, `title`, `status`, `detail` and
+    /// `instance` are reserved, extensions with those keys are left out.
+    pub fn with_extension(
+        mut self,
+        key: &str,
+        value: impl Into<Value>,
+    ) -> Self {
+        const RESERVED: &[&str] =
+            &["type", "title", "status", "detail", "instance"];
+        if RESERVED.contains(&key) {
+            log::warn!("problem extension `{}` is a reserved member", key);
+            return self;
+        }
+
+        self.extensions.insert(key.to_owned(), value.into());
+        self
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    fn to_html(&self) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head><title>{status} {title}</title></head>\n\
+             <body>\n<h1>{status} {title}</h1>\n",
+            status = self.status.as_u16(),
+            title = escape(&self.title),
+        );
+        if let Some(ref detail) = self.detail {
+            html.push_str(&format!("<p>{}</p>\n", escape(detail)));
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    /// The content type and body for the request, as JSON or HTML,
+    /// whichever the client prefers.
+    fn render(mut self, req: &HttpRequest) -> (&'static str, String) {
+        if self.instance.is_none() {
+            self.instance = Some(req.path().to_owned());
+        }
+
+        if prefers_html(req) {
+            ("text/html; charset=utf-8", self.to_html())
+        } else {
+            ("application/problem+json", self.to_json())
+        }
+    }
+
+    /// Renders the problem as JSON or HTML, whichever the client prefers.
+    pub fn respond_to(self, req: &HttpRequest) -> HttpResponse {
+        let status = self.status;
+        let (content_type, body) = self.render(req);
+
+        HttpResponseBuilder::new(status)
+            .set_header(header::CONTENT_TYPE, content_type)
+            .body(body)
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.detail {
+            Some(ref detail) => write!(f, "{}: {}", self.title, detail),
+            None => f.write_str(&self.title),
+        }
+    }
+}
+
+// Without access to the request only JSON can be rendered here; the
+// `NegotiateProblems` middleware re-renders it once the request is known.
+impl error::ResponseError for Problem {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponseBuilder::new(self.status)
+            .set_header(header::CONTENT_TYPE, "application/problem+json")
+            .body(self.to_json())
+    }
+}
+
+/// Errors that can be described as a problem.
+pub trait ToProblem {
+    fn to_problem(&self) -> Problem;
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+// </problem>
+
+// <negotiate>
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use actix_service::{Service, Transform};
+use actix_web::body::{Body, ResponseBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{Accept, Header, HeaderValue};
+use actix_web::Error;
+use futures::future::{ok, Ready};
+use futures::Future;
+
+/// Returns true if the `Accept` header ranks HTML above JSON.
+fn prefers_html(req: &HttpRequest) -> bool {
+    let accept = match Accept::parse(req) {
+        Ok(accept) => accept,
+        Err(_) => return false,
+    };
+
+    let html = quality_for(&accept, &mime::TEXT_HTML);
+    let json = ["application/problem+json", "application/json"]
+        .iter()
+        .filter_map(|ct| quality_for(&accept, &ct.parse().unwrap()))
+        .max();
+
+    match (html, json) {
+        (Some(html), Some(json)) => html > json,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+/// Quality the client assigned to `offered`, taken from the most specific
+/// matching media range. `None` if it is not acceptable at all.
+fn quality_for(
+    accept: &Accept,
+    offered: &mime::Mime,
+) -> Option<header::Quality> {
+    accept
+        .iter()
+        .filter_map(|range| {
+            let item = &range.item;
+            let specificity = if item.type_() == mime::STAR {
+                0
+            } else if item.type_() != offered.type_() {
+                return None;
+            } else if item.subtype() == mime::STAR {
+                1
+            } else if item.subtype() != offered.subtype() {
+                return None;
+            } else {
+                2
+            };
+            Some((specificity, range.quality))
+        })
+        .max_by_key(|(specificity, _)| *specificity)
+        .map(|(_, quality)| quality)
+        .filter(|quality| *quality > header::q(0))
+}
+
+/// Renders every `Problem` returned by a handler, extractor or middleware
+/// in the format the client asked for.
+pub struct NegotiateProblems;
+
+impl<S, B> Transform<S> for NegotiateProblems
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = NegotiateProblemsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(NegotiateProblemsMiddleware { service })
+    }
+}
+
+pub struct NegotiateProblemsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for NegotiateProblemsMiddleware<S>
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+
+            let problem = res
+                .response()
+                .error()
+                .and_then(|err| err.as_error::<Problem>())
+                .cloned();
+
+            Ok(match problem {
+                Some(problem) => {
+                    let (content_type, body) = problem.render(res.request());
+                    let mut res = res;
+                    res.headers_mut().insert(
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static(content_type),
+                    );
+                    // only the body changes, `Logger` and others still see
+                    // the error
+                    res.map_body(|_, _| ResponseBody::Other(Body::from(body)))
+                }
+                None => res,
+            })
+        })
+    }
+}
+// </negotiate>
+
+// <extractor-errors>
+/// Extractor configs that report deserialization failures as problems.
+pub fn extractor_errors(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(web::JsonConfig::default().error_handler(|err, req| {
+        extractor_problem(err.to_string(), "body", req, &err)
+    }))
+    .app_data(web::FormConfig::default().error_handler(|err, req| {
+        extractor_problem(err.to_string(), "body", req, &err)
+    }))
+    .app_data(web::QueryConfig::default().error_handler(|err, req| {
+        extractor_problem(err.to_string(), "query", req, &err)
+    }))
+    .app_data(web::PathConfig::default().error_handler(|err, req| {
+        extractor_problem(err.to_string(), "path", req, &err)
+    }));
+}
+
+fn extractor_problem(
+    detail: String,
+    location: &str,
+    req: &HttpRequest,
+    err: &dyn error::ResponseError,
+) -> Error {
+    // some extractor errors only override `error_response`, so ask the
+    // rendered response for the status instead of `status_code()`
+    Problem::new(err.error_response().status())
+        .with_type("/problems/invalid-request", "Invalid request")
+        .with_detail(detail)
+        .with_instance(req.path())
+        .with_extension("location", location)
+        .into()
+}
+// </extractor-errors>
+
+// <to-problem>
+use derive_more::Display;
+
+#[derive(Debug, Display)]
+enum OrderError {
+    #[display(fmt = "order {} does not exist", id)]
+    NotFound { id: u32 },
+
+    #[display(fmt = "insufficient balance")]
+    InsufficientBalance { balance: u32, cost: u32 },
+}
+
+impl ToProblem for OrderError {
+    fn to_problem(&self) -> Problem {
+        match *self {
+            OrderError::NotFound { .. } => Problem::new(StatusCode::NOT_FOUND)
+                .with_detail(self.to_string()),
+            OrderError::InsufficientBalance { balance, cost } => {
+                Problem::new(StatusCode::FORBIDDEN)
+                    .with_type(
+                        "/problems/out-of-credit",
+                        "You do not have enough credit.",
+                    )
+                    .with_detail(format!(
+                        "Your current balance is {}, but that costs {}.",
+                        balance, cost
+                    ))
+                    .with_extension("balance", balance)
+            }
+        }
+    }
+}
+
+impl From<OrderError> for Problem {
+    fn from(err: OrderError) -> Problem {
+        err.to_problem()
+    }
+}
+
+async fn order(id: web::Path<u32>) -> Result<HttpResponse, Problem> {
+    match id.into_inner() {
+        1 => Ok(HttpResponse::Ok().body("order 1")),
+        2 => Err(OrderError::InsufficientBalance {
+            balance: 30,
+            cost: 50,
+        }
+        .into()),
+        id => Err(OrderError::NotFound { id }.into()),
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    extractor_errors(cfg);
+    cfg.route("/orders/{id}", web::get().to(order));
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    use actix_web::{App, HttpServer};
+
+    HttpServer::new(|| App::new().wrap(NegotiateProblems).configure(configure))
+        .bind("127.0.0.1:8080")?
+        .run()
+        .await
+}
+// </to-problem>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Query {
+        page: u32,
+    }
+
+    async fn list(
+        _: web::Query<Query>,
+        _: web::Json<Vec<u32>>,
+    ) -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    async fn call(req: test::TestRequest) -> (StatusCode, String, web::Bytes) {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(NegotiateProblems)
+                .configure(extractor_errors)
+                .route("/orders/{id}", web::get().to(order))
+                .route("/list", web::post().to(list)),
+        )
+        .await;
+
+        let res = test::call_service(&mut app, req.to_request()).await;
+        let status = res.status();
+        let content_type = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|ct| ct.to_str().unwrap().to_owned())
+            .unwrap_or_default();
+        let body = test::read_body(res).await;
+
+        (status, content_type, body)
+    }
+
+    fn json(body: &[u8]) -> Value {
+        serde_json::from_slice(body).unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn test_problem_json() {
+        let req = test::TestRequest::get().uri("/orders/2");
+        let (status, content_type, body) = call(req).await;
+
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(content_type, "application/problem+json");
+        assert_eq!(
+            json(&body),
+            serde_json::json!({
+                "type": "/problems/out-of-credit",
+                "title": "You do not have enough credit.",
+                "status": 403,
+                "detail": "Your current balance is 30, but that costs 50.",
+                "instance": "/orders/2",
+                "balance": 30,
+            })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_problem_html() {
+        let req = test::TestRequest::get().uri("/orders/7").header(
+            header::ACCEPT,
+            "text/html,application/xhtml+xml,*/*;q=0.8",
+        );
+        let (status, content_type, body) = call(req).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type, "text/html; charset=utf-8");
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("<h1>404 Not Found</h1>"));
+        assert!(body.contains("<p>order 7 does not exist</p>"));
+    }
+
+    #[actix_rt::test]
+    async fn test_accept_q_values() {
+        let req = test::TestRequest::get()
+            .uri("/orders/7")
+            .header(header::ACCEPT, "text/html;q=0.5, application/json");
+        let (_, content_type, _) = call(req).await;
+        assert_eq!(content_type, "application/problem+json");
+
+        let req = test::TestRequest::get()
+            .uri("/orders/7")
+            .header(header::ACCEPT, "application/*;q=0.2, text/*");
+        let (_, content_type, _) = call(req).await;
+        assert_eq!(content_type, "text/html; charset=utf-8");
+    }
+
+    #[actix_rt::test]
+    async fn test_error_is_kept() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(NegotiateProblems)
+                .route("/orders/{id}", web::get().to(order)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/orders/7")
+            .header(header::ACCEPT, "text/html")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        let err = res.response().error().unwrap();
+        assert_eq!(err.to_string(), "Not Found: order 7 does not exist");
+    }
+
+    #[test]
+    fn test_reserved_extensions() {
+        let problem = Problem::new(StatusCode::NOT_FOUND)
+            .with_extension("status", 200)
+            .with_extension("id", 7);
+        let json: Value = serde_json::from_str(&problem.to_json()).unwrap();
+        assert_eq!(json["status"], 404);
+        assert_eq!(json["id"], 7);
+    }
+
+    #[actix_rt::test]
+    async fn test_success_untouched() {
+        let req = test::TestRequest::get().uri("/orders/1");
+        let (status, _, body) = call(req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, web::Bytes::from_static(b"order 1"));
+    }
+
+    #[actix_rt::test]
+    async fn test_extractor_errors() {
+        let req = test::TestRequest::get().uri("/orders/abc");
+        let (status, content_type, body) = call(req).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(content_type, "application/problem+json");
+        assert_eq!(json(&body)["location"], "path");
+        assert_eq!(json(&body)["instance"], "/orders/abc");
+
+        let req = test::TestRequest::post()
+            .uri("/list?page=x")
+            .set_json(&[1, 2]);
+        let (status, _, body) = call(req).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json(&body)["type"], "/problems/invalid-request");
+        assert_eq!(json(&body)["location"], "query");
+
+        let req = test::TestRequest::post()
+            .uri("/list?page=1")
+            .header(header::CONTENT_TYPE, "application/json")
+            .set_payload("{not json");
+        let (status, _, body) = call(req).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json(&body)["location"], "body");
+        assert_eq!(json(&body)["status"], 400);
+    }
+}
@@ -1,21 +1,31 @@
 // <recommend-one>
-use actix_web::{
-    dev::HttpResponseBuilder, error, get, http::header, http::StatusCode, App, HttpResponse,
-    HttpServer,
-};
+use actix_web::{error, get, http::StatusCode, App, HttpResponse, HttpServer};
 use derive_more::{Display, Error};
 
+use super::problem::{Problem, ToProblem};
+
 #[derive(Debug, Display, Error)]
 enum UserError {
     #[display(fmt = "Validation error on field: {}", field)]
     ValidationError { field: String },
 }
 
+impl ToProblem for UserError {
+    fn to_problem(&self) -> Problem {
+        match *self {
+            UserError::ValidationError { ref field } => {
+                Problem::new(StatusCode::BAD_REQUEST)
+                    .with_type("/problems/validation", "Validation error")
+                    .with_detail(self.to_string())
+                    .with_extension("field", field.as_str())
+            }
+        }
+    }
+}
+
 impl error::ResponseError for UserError {
     fn error_response(&self) -> HttpResponse {
-        HttpResponseBuilder::new(self.status_code())
-            .set_header(header::CONTENT_TYPE, "text/html; charset=utf-8")
-            .body(self.to_string())
+        self.to_problem().error_response()
     }
     fn status_code(&self) -> StatusCode {
         match *self {
@@ -34,8 +44,12 @@ async fn index() -> Result<&'static str, UserError> {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| App::new().service(index))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
+
+pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
+    cfg.service(index);
+}
@@ -1,10 +1,9 @@
 // <recommend-two>
-use actix_web::{
-    dev::HttpResponseBuilder, error, get, http::header, http::StatusCode, App, HttpResponse,
-    HttpServer,
-};
+use actix_web::{error, get, http::StatusCode, App, HttpResponse, HttpServer};
 use derive_more::{Display, Error};
 
+use super::problem::Problem;
+
 #[derive(Debug, Display, Error)]
 enum UserError {
     #[display(fmt = "An internal error occurred. Please try again later.")]
@@ -13,9 +12,10 @@ enum UserError {
 
 impl error::ResponseError for UserError {
     fn error_response(&self) -> HttpResponse {
-        HttpResponseBuilder::new(self.status_code())
-            .set_header(header::CONTENT_TYPE, "text/html; charset=utf-8")
-            .body(self.to_string())
+        // only the generic message, never the underlying error
+        Problem::new(self.status_code())
+            .with_detail(self.to_string())
+            .error_response()
     }
     fn status_code(&self) -> StatusCode {
         match *self {
@@ -37,8 +37,12 @@ fn do_thing_that_fails() -> Result<(), std::io::Error> {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| App::new().service(index))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
+
+pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
+    cfg.service(index);
+}
@@ -1,25 +1,70 @@
 // <form>
 use actix_web::{post, web, App, HttpServer, Result};
 use serde::Deserialize;
+use validation::{length, Validate, Validated, Validator};
 
 #[derive(Deserialize)]
 struct FormData {
     username: String,
 }
 
+impl Validate for FormData {
+    fn validate(&self, v: &mut Validator) {
+        v.field("username", &self.username, &[&length(3, 32)]);
+    }
+}
+
 /// extract form data using serde
-/// this handler gets called only if the content type is *x-www-form-urlencoded*
-/// and the content of the request could be deserialized to a `FormData` struct
+/// this handler gets called only if the content type is *x-www-form-urlencoded*,
+/// the content of the request could be deserialized to a `FormData` struct
+/// and that struct passes its validation rules
 #[post("/")]
-async fn index(form: web::Form<FormData>) -> Result<String> {
+async fn index(form: Validated<web::Form<FormData>>) -> Result<String> {
     Ok(format!("Welcome {}!", form.username))
 }
 // </form>
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| App::new().service(index))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test};
+
+    #[actix_rt::test]
+    async fn test_form() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/")
+            .set_form(&[("username", "ferris")])
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Welcome ferris!");
+
+        let req = test::TestRequest::post()
+            .uri("/")
+            .set_payload("username=ferris")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/")
+            .set_form(&[("username", "fe")])
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
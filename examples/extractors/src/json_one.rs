@@ -1,23 +1,68 @@
 // <json-one>
 use actix_web::{get, web, App, HttpServer, Result};
 use serde::Deserialize;
+use validation::{length, Validate, Validated, Validator};
 
 #[derive(Deserialize)]
 struct Info {
     username: String,
 }
 
-/// deserialize `Info` from request's body
+impl Validate for Info {
+    fn validate(&self, v: &mut Validator) {
+        v.field("username", &self.username, &[&length(3, 32)]);
+    }
+}
+
+/// deserialize `Info` from request's body and check it against its rules
 #[get("/")]
-async fn index(info: web::Json<Info>) -> Result<String> {
+async fn index(info: Validated<web::Json<Info>>) -> Result<String> {
     Ok(format!("Welcome {}!", info.username))
 }
 // </json-one>
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| App::new().service(index))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test};
+    use serde_json::json;
+
+    #[actix_rt::test]
+    async fn test_json() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .set_json(&json!({ "username": "ferris" }))
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Welcome ferris!");
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .set_json(&json!({ "name": "ferris" }))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .set_json(&json!({ "username": "fe" }))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
@@ -1,5 +1,3 @@
-#![allow(dead_code)]
-
 // <json-two>
 use actix_web::{error, web, App, HttpResponse, HttpServer, Responder};
 use serde::Deserialize;
@@ -14,25 +12,27 @@ async fn index(info: web::Json<Info>) -> impl Responder {
     format!("Welcome {}!", info.username)
 }
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let json_config = web::JsonConfig::default()
+        .limit(4096)
+        .error_handler(|err, _req| {
+            // create custom error response
+            error::InternalError::from_response(err, HttpResponse::Conflict().finish()).into()
+        });
+
+    cfg.service(
+        web::resource("/")
+            // change json extractor configuration
+            .app_data(json_config)
+            .route(web::post().to(index)),
+    );
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| {
-        let json_config = web::JsonConfig::default()
-            .limit(4096)
-            .error_handler(|err, _req| {
-                // create custom error response
-                error::InternalError::from_response(err, HttpResponse::Conflict().finish()).into()
-            });
-
-        App::new().service(
-            web::resource("/")
-                // change json extractor configuration
-                .app_data(json_config)
-                .route(web::post().to(index)),
-        )
-    })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+    HttpServer::new(|| App::new().configure(configure))
+        .bind("127.0.0.1:8080")?
+        .run()
+        .await
 }
 // </json-two>
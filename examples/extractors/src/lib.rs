@@ -0,0 +1,78 @@
+use actix_web::{web, FromRequest, HttpRequest, Responder, Result};
+use serde::Deserialize;
+
+// pub mod custom_handler;
+pub mod form;
+pub mod json_one;
+pub mod json_two;
+pub mod multiple;
+pub mod path_one;
+pub mod path_three;
+pub mod path_two;
+pub mod query;
+pub mod validated;
+
+#[derive(Deserialize, Debug)]
+struct MyInfo {
+    username: String,
+    id: u32,
+}
+
+// <option-one>
+async fn index(path: web::Path<(String, String)>, json: web::Json<MyInfo>) -> impl Responder {
+    let path = path.into_inner();
+    format!("{} {} {} {}", path.0, path.1, json.id, json.username)
+}
+// </option-one>
+
+// <option-two>
+async fn extract(
+    req: HttpRequest,
+    mut payload: web::Payload,
+) -> Result<String> {
+    let params = web::Path::<(String, String)>::extract(&req)
+        .await?
+        .into_inner();
+
+    // `extract` only sees the request head, the body has to be passed in
+    let info = web::Json::<MyInfo>::from_request(&req, &mut payload.0).await?;
+
+    Ok(format!(
+        "{} {} {} {}",
+        params.0, params.1, info.username, info.id
+    ))
+}
+// </option-two>
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/{name}/{id}", web::post().to(index))
+        .route("/{name}/{id}/extract", web::post().to(extract));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use serde_json::json;
+
+    #[actix_rt::test]
+    async fn test_multiple_extractors() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+        let info = json!({ "username": "ferris", "id": 7 });
+
+        let req = test::TestRequest::post()
+            .uri("/crab/42")
+            .set_json(&info)
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "crab 42 7 ferris");
+
+        let req = test::TestRequest::post()
+            .uri("/crab/42/extract")
+            .set_json(&info)
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "crab 42 ferris 7");
+    }
+}
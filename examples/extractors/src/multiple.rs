@@ -18,13 +18,35 @@ async fn index(
     )
 }
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     use actix_web::{App, HttpServer};
 
-    HttpServer::new(|| App::new().service(index))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
 // </multi>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn test_path_and_query() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/users/42/ferris?username=crab")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Welcome crab, friend ferris, user_id 42!");
+    }
+}
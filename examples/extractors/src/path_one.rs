@@ -13,9 +13,46 @@ async fn index(web::Path((user_id, friend)): web::Path<(u32, String)>) -> Result
 async fn main() -> std::io::Result<()> {
     use actix_web::{App, HttpServer};
 
-    HttpServer::new(|| App::new().service(index))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
 // </path-one>
+
+// <path-config>
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(
+        // `PathError` renders as 400 Bad Request; without this handler a
+        // segment that fails to deserialize ends with 404 Not Found
+        web::PathConfig::default().error_handler(|err, _req| err.into()),
+    )
+    .service(index);
+}
+// </path-config>
+
+// <path-test>
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    #[actix_rt::test]
+    async fn test_path_tuple() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/users/42/ferris")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Welcome ferris, user_id 42!");
+
+        let req = test::TestRequest::get()
+            .uri("/users/abc/ferris")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
+// </path-test>
@@ -1,4 +1,4 @@
-use actix_web::{get, HttpRequest, Result};
+use actix_web::{get, web, HttpRequest, Result};
 
 // <path-three>
 #[get("/users/{userid}/{friend}")] // <- define path parameters
@@ -13,9 +13,31 @@ async fn index(req: HttpRequest) -> Result<String> {
 async fn main() -> std::io::Result<()> {
     use actix_web::{App, HttpServer};
 
-    HttpServer::new(|| App::new().service(index))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
 // </path-three>
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn test_match_info() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/users/42/ferris")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Welcome ferris, userid 42!");
+    }
+}
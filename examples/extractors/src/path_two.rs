@@ -1,6 +1,7 @@
 // <path-two>
 use actix_web::{get, web, Result};
 use serde::Deserialize;
+use validation::{length, range, Validate, Validated, Validator};
 
 #[derive(Deserialize)]
 struct Info {
@@ -8,9 +9,16 @@ struct Info {
     friend: String,
 }
 
-/// extract path info using serde
+impl Validate for Info {
+    fn validate(&self, v: &mut Validator) {
+        v.field("user_id", &self.user_id, &[&range(1, 1_000_000)])
+            .field("friend", &self.friend, &[&length(3, 32)]);
+    }
+}
+
+/// extract path info using serde and check it against its rules
 #[get("/users/{user_id}/{friend}")] // <- define path parameters
-async fn index(info: web::Path<Info>) -> Result<String> {
+async fn index(info: Validated<web::Path<Info>>) -> Result<String> {
     Ok(format!(
         "Welcome {}, user_id {}!",
         info.friend, info.user_id
@@ -21,9 +29,42 @@ async fn index(info: web::Path<Info>) -> Result<String> {
 async fn main() -> std::io::Result<()> {
     use actix_web::{App, HttpServer};
 
-    HttpServer::new(|| App::new().service(index))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
 // </path-two>
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    #[actix_rt::test]
+    async fn test_path_struct() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/users/42/ferris")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Welcome ferris, user_id 42!");
+
+        // the default `PathConfig` reports deserialization errors as 404
+        let req = test::TestRequest::get()
+            .uri("/users/abc/ferris")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::get().uri("/users/0/fe").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
@@ -1,24 +1,62 @@
 // <query>
 use actix_web::{get, web, App, HttpServer};
 use serde::Deserialize;
+use validation::{length, Validate, Validated, Validator};
 
 #[derive(Deserialize)]
 struct Info {
     username: String,
 }
 
+impl Validate for Info {
+    fn validate(&self, v: &mut Validator) {
+        v.field("username", &self.username, &[&length(3, 32)]);
+    }
+}
+
 // this handler gets called if the query deserializes into `Info` successfully
-// otherwise a 400 Bad Request error response is returned
+// and passes its rules, otherwise a 400 Bad Request or a 422 Unprocessable
+// Entity error response is returned
 #[get("/")]
-async fn index(info: web::Query<Info>) -> String {
+async fn index(info: Validated<web::Query<Info>>) -> String {
     format!("Welcome {}!", info.username)
 }
 // </query>
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| App::new().service(index))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test};
+
+    #[actix_rt::test]
+    async fn test_query() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/?username=ferris")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Welcome ferris!");
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::get().uri("/?username=fe").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
@@ -0,0 +1,182 @@
+// <validated-rules>
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+use validation::{custom, email, length, pattern, range, Validate, Validator};
+
+static USERNAME: Lazy<Regex> =
+    Lazy::new(|| Regex::new("^[a-z0-9_]+$").unwrap());
+
+#[derive(Deserialize)]
+struct Address {
+    city: String,
+    postcode: String,
+}
+
+impl Validate for Address {
+    fn validate(&self, v: &mut Validator) {
+        v.field("city", &self.city, &[&length(1, 64)]).field(
+            "postcode",
+            &self.postcode,
+            &[&length(4, 10)],
+        );
+    }
+}
+
+#[derive(Deserialize)]
+struct Signup {
+    username: String,
+    email: String,
+    age: u8,
+    website: Option<String>,
+    address: Address,
+}
+
+impl Validate for Signup {
+    fn validate(&self, v: &mut Validator) {
+        v.field(
+            "username",
+            &self.username,
+            &[
+                &length(3, 32),
+                &pattern(&USERNAME, "may only contain a-z, 0-9 and _"),
+            ],
+        )
+        .field("email", &self.email, &[&email()])
+        .field("age", &self.age, &[&range(13, 130)])
+        .optional(
+            "website",
+            &self.website,
+            &[&custom("https", |url: &String| {
+                if url.starts_with("https://") {
+                    Ok(())
+                } else {
+                    Err(String::from("must start with https://"))
+                }
+            })],
+        )
+        .nested("address", &self.address);
+    }
+}
+// </validated-rules>
+
+// <validated>
+use actix_web::{get, post, web, App, HttpServer};
+use validation::Validated;
+
+/// the handler only runs if the body deserializes *and* passes the rules,
+/// otherwise a 422 with the failed fields is returned
+#[post("/signup")]
+async fn signup(info: Validated<web::Json<Signup>>) -> String {
+    format!("Welcome {} from {}!", info.username, info.address.city)
+}
+
+#[derive(Deserialize)]
+struct Search {
+    q: String,
+    page: u32,
+}
+
+impl Validate for Search {
+    fn validate(&self, v: &mut Validator) {
+        v.field("q", &self.q, &[&length(1, 100)]).field(
+            "page",
+            &self.page,
+            &[&range(1, 1000)],
+        );
+    }
+}
+
+#[get("/search")]
+async fn search(query: Validated<web::Query<Search>>) -> String {
+    format!("Results for {}, page {}", query.q, query.page)
+}
+// </validated>
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(signup).service(search);
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    HttpServer::new(|| App::new().configure(configure))
+        .bind("127.0.0.1:8080")?
+        .run()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{dev::ServiceResponse, http::StatusCode, test};
+    use serde_json::{json, Value};
+
+    async fn fields(resp: ServiceResponse) -> Vec<String> {
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value = test::read_body_json(resp).await;
+        body["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|err| err["field"].as_str().unwrap().to_owned())
+            .collect()
+    }
+
+    #[actix_rt::test]
+    async fn test_json() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let body = json!({
+            "username": "ferris",
+            "email": "ferris@example.com",
+            "age": 30,
+            "address": { "city": "Oslo", "postcode": "0150" }
+        });
+        let req = test::TestRequest::post()
+            .uri("/signup")
+            .set_json(&body)
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Welcome ferris from Oslo!");
+
+        let req = test::TestRequest::post()
+            .uri("/signup")
+            .set_json(&json!({
+                "username": "Ferris",
+                "email": "ferris",
+                "age": 7,
+                "website": "http://example.com",
+                "address": { "city": "", "postcode": "0150" }
+            }))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(
+            fields(resp).await,
+            ["username", "email", "age", "website", "address.city"]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_query() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/search?q=actix&page=2")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Results for actix, page 2");
+
+        let req = test::TestRequest::get()
+            .uri("/search?q=&page=0")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(fields(resp).await, ["q", "page"]);
+
+        // a query that does not deserialize is still a 400
+        let req = test::TestRequest::get().uri("/search?q=actix").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,47 @@
+use actix_web::{web, Responder};
+use serde::Serialize;
+
+// <flexible-responders>
+#[derive(Serialize)]
+struct Measurement {
+    temperature: f32,
+}
+
+async fn hello_world() -> impl Responder {
+    "Hello World!"
+}
+
+async fn current_temperature() -> impl Responder {
+    web::Json(Measurement { temperature: 42.3 })
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/").to(hello_world))
+        .service(web::resource("/temp").to(current_temperature));
+}
+// </flexible-responders>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::header, test, App};
+
+    #[actix_rt::test]
+    async fn test_responders() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Hello World!");
+
+        let req = test::TestRequest::get().uri("/temp").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+        let body = test::read_body(resp).await;
+        assert_eq!(body, r#"{"temperature":42.3}"#);
+    }
+}
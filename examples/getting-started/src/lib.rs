@@ -0,0 +1,56 @@
+// <handlers>
+use actix_web::{get, post, web, HttpResponse, Responder};
+
+#[get("/")]
+async fn hello() -> impl Responder {
+    HttpResponse::Ok().body("Hello world!")
+}
+
+#[post("/echo")]
+async fn echo(req_body: String) -> impl Responder {
+    HttpResponse::Ok().body(req_body)
+}
+
+async fn manual_hello() -> impl Responder {
+    HttpResponse::Ok().body("Hey there!")
+}
+// </handlers>
+
+// <configure>
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(hello)
+        .service(echo)
+        .route("/hey", web::get().to(manual_hello));
+}
+// </configure>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    #[actix_rt::test]
+    async fn test_routes() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Hello world!");
+
+        let req = test::TestRequest::post()
+            .uri("/echo")
+            .set_payload("ping")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "ping");
+
+        let req = test::TestRequest::get().uri("/hey").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Hey there!");
+
+        let req = test::TestRequest::get().uri("/echo").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
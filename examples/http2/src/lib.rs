@@ -0,0 +1,9 @@
+#[cfg(not(any(feature = "openssl", feature = "rustls")))]
+compile_error!("enable the `openssl` or the `rustls` feature");
+
+#[cfg(any(feature = "openssl", test))]
+extern crate openssl_crate as openssl;
+#[cfg(feature = "rustls")]
+extern crate rustls_crate as rustls;
+
+pub mod tls;
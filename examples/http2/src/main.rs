@@ -1,6 +1,13 @@
 // <main>
+use std::time::Duration;
+
 use actix_web::{web, App, HttpRequest, HttpServer, Responder};
-use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+use http2::tls;
+
+#[cfg(all(feature = "openssl", not(feature = "rustls")))]
+use tls::openssl::Reloader;
+#[cfg(feature = "rustls")]
+use tls::rustls::Reloader;
 
 async fn index(_req: HttpRequest) -> impl Responder {
     "Hello."
@@ -11,15 +18,21 @@ async fn main() -> std::io::Result<()> {
     // load ssl keys
     // to create a self-signed temporary cert for testing:
     // `openssl req -x509 -newkey rsa:4096 -nodes -keyout key.pem -out cert.pem -days 365 -subj '/CN=localhost'`
-    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
-    builder
-        .set_private_key_file("key.pem", SslFiletype::PEM)
-        .unwrap();
-    builder.set_certificate_chain_file("cert.pem").unwrap();
-
-    HttpServer::new(|| App::new().route("/", web::get().to(index)))
-        .bind_openssl("127.0.0.1:8080", builder)?
-        .run()
-        .await
+    let reloader = Reloader::new(tls::CertPaths::new("cert.pem", "key.pem"))?;
+
+    // pick up renewed certificates without a restart
+    reloader.watch(Duration::from_secs(10));
+
+    let server =
+        HttpServer::new(|| App::new().route("/", web::get().to(index)));
+
+    // build with `--no-default-features --features rustls` to use rustls
+    #[cfg(feature = "rustls")]
+    let server = server.bind_rustls("127.0.0.1:8080", reloader.config())?;
+    #[cfg(all(feature = "openssl", not(feature = "rustls")))]
+    let server =
+        server.bind_openssl("127.0.0.1:8080", reloader.acceptor()?)?;
+
+    server.run().await
 }
 // </main>
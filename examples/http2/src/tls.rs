@@ -0,0 +1,545 @@
+// <tls-reload>
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Certificate chain and private key, both PEM encoded
+#[derive(Clone, Debug)]
+pub struct CertPaths {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl CertPaths {
+    pub fn new(cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        CertPaths {
+            cert: cert.into(),
+            key: key.into(),
+        }
+    }
+
+    fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+        let modified = |path: &Path| fs::metadata(path)?.modified();
+        Some((modified(&self.cert).ok()?, modified(&self.key).ok()?))
+    }
+}
+
+#[derive(Debug)]
+pub enum CertError {
+    /// File is missing or unreadable
+    Read { path: PathBuf, source: io::Error },
+    /// File does not contain a usable certificate or key
+    Invalid { path: PathBuf, reason: String },
+    /// Private key does not belong to the certificate
+    KeyMismatch { cert: PathBuf, key: PathBuf },
+}
+
+impl fmt::Display for CertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CertError::Read { path, source } => {
+                write!(f, "cannot read {}: {}", path.display(), source)
+            }
+            CertError::Invalid { path, reason } => {
+                write!(f, "invalid {}: {}", path.display(), reason)
+            }
+            CertError::KeyMismatch { cert, key } => write!(
+                f,
+                "private key {} does not match certificate {}",
+                key.display(),
+                cert.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CertError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CertError::Read { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<CertError> for io::Error {
+    fn from(err: CertError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, CertError> {
+    fs::read(path).map_err(|source| CertError::Read {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+fn invalid(path: &Path, reason: impl fmt::Display) -> CertError {
+    CertError::Invalid {
+        path: path.to_owned(),
+        reason: reason.to_string(),
+    }
+}
+
+/// Polls the modification times of both files and calls `reload` when
+/// either changes. A failed reload keeps the previous certificate, so a
+/// half-written key or certificate never takes the server down.
+fn watch<F>(paths: CertPaths, interval: Duration, reload: F)
+where
+    F: Fn() -> Result<(), CertError> + 'static,
+{
+    let mut last = paths.modified();
+
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(interval);
+
+        loop {
+            interval.tick().await;
+
+            let modified = paths.modified();
+            if modified.is_none() || modified == last {
+                continue;
+            }
+
+            // a file still being written is retried once it changes again
+            match reload() {
+                Ok(()) => log::info!("reloaded {}", paths.cert.display()),
+                Err(err) => log::error!("certificate reload failed: {}", err),
+            }
+            last = modified;
+        }
+    });
+}
+
+#[cfg(feature = "openssl")]
+pub mod openssl {
+    use std::sync::{Arc, RwLock};
+    use std::time::Duration;
+
+    use openssl::pkey::PKey;
+    use openssl::ssl::{
+        select_next_proto, AlpnError, SniError, SslAcceptor,
+        SslAcceptorBuilder, SslContext, SslMethod,
+    };
+    use openssl::x509::X509;
+
+    use super::{invalid, read, CertError, CertPaths};
+
+    /// Serves the most recently loaded certificate. Every handshake
+    /// switches to the current `SslContext`, so connections that are
+    /// already established keep the certificate they started with.
+    #[derive(Clone)]
+    pub struct Reloader {
+        paths: CertPaths,
+        current: Arc<RwLock<SslContext>>,
+    }
+
+    impl Reloader {
+        pub fn new(paths: CertPaths) -> Result<Self, CertError> {
+            let context = load(&paths)?.build().into_context();
+
+            Ok(Reloader {
+                paths,
+                current: Arc::new(RwLock::new(context)),
+            })
+        }
+
+        pub fn reload(&self) -> Result<(), CertError> {
+            let context = load(&self.paths)?.build().into_context();
+            *self.current.write().unwrap() = context;
+            Ok(())
+        }
+
+        /// Reloads the certificate whenever the files change.
+        pub fn watch(&self, interval: Duration) {
+            let reloader = self.clone();
+            super::watch(self.paths.clone(), interval, move || {
+                reloader.reload()
+            });
+        }
+
+        /// Acceptor for `HttpServer::bind_openssl`. It has no certificate
+        /// of its own; OpenSSL runs the server name callback even when the
+        /// client sends no name, so every handshake gets the current one.
+        pub fn acceptor(&self) -> Result<SslAcceptorBuilder, CertError> {
+            let mut builder =
+                SslAcceptor::mozilla_intermediate(SslMethod::tls())
+                    .map_err(|err| invalid(&self.paths.cert, err))?;
+
+            let current = Arc::clone(&self.current);
+            builder.set_servername_callback(move |ssl, _| {
+                ssl.set_ssl_context(&current.read().unwrap())
+                    .map_err(|_| SniError::ALERT_FATAL)
+            });
+
+            Ok(builder)
+        }
+    }
+
+    fn load(paths: &CertPaths) -> Result<SslAcceptorBuilder, CertError> {
+        let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())
+            .map_err(|err| invalid(&paths.cert, err))?;
+
+        let chain = X509::stack_from_pem(&read(&paths.cert)?)
+            .map_err(|err| invalid(&paths.cert, err))?;
+        let mut chain = chain.into_iter();
+        let leaf = chain
+            .next()
+            .ok_or_else(|| invalid(&paths.cert, "no certificate found"))?;
+        builder
+            .set_certificate(&leaf)
+            .map_err(|err| invalid(&paths.cert, err))?;
+        for cert in chain {
+            builder
+                .add_extra_chain_cert(cert)
+                .map_err(|err| invalid(&paths.cert, err))?;
+        }
+
+        let key = PKey::private_key_from_pem(&read(&paths.key)?)
+            .map_err(|err| invalid(&paths.key, err))?;
+        let public_key =
+            leaf.public_key().map_err(|err| invalid(&paths.cert, err))?;
+        if !public_key.public_eq(&key) {
+            return Err(CertError::KeyMismatch {
+                cert: paths.cert.clone(),
+                key: paths.key.clone(),
+            });
+        }
+        builder
+            .set_private_key(&key)
+            .map_err(|err| invalid(&paths.key, err))?;
+
+        // ALPN is negotiated with whichever context the handshake
+        // switched to, so each one needs the callback
+        builder.set_alpn_select_callback(|_, protocols| {
+            select_next_proto(b"\x02h2\x08http/1.1", protocols)
+                .ok_or(AlpnError::NOACK)
+        });
+
+        Ok(builder)
+    }
+}
+
+#[cfg(feature = "rustls")]
+pub mod rustls {
+    use std::io::BufReader;
+    use std::sync::{Arc, RwLock};
+    use std::time::Duration;
+
+    use rustls::internal::pemfile;
+    use rustls::sign::{self, CertifiedKey};
+    use rustls::{
+        ClientHello, NoClientAuth, ResolvesServerCert, ServerConfig,
+        SignatureScheme,
+    };
+
+    use super::{invalid, read, CertError, CertPaths};
+
+    /// Serves the most recently loaded certificate to new handshakes.
+    #[derive(Clone)]
+    pub struct Reloader {
+        paths: CertPaths,
+        current: Arc<RwLock<CertifiedKey>>,
+    }
+
+    impl Reloader {
+        pub fn new(paths: CertPaths) -> Result<Self, CertError> {
+            let key = load(&paths)?;
+
+            Ok(Reloader {
+                paths,
+                current: Arc::new(RwLock::new(key)),
+            })
+        }
+
+        pub fn reload(&self) -> Result<(), CertError> {
+            let key = load(&self.paths)?;
+            *self.current.write().unwrap() = key;
+            Ok(())
+        }
+
+        /// Reloads the certificate whenever the files change.
+        pub fn watch(&self, interval: Duration) {
+            let reloader = self.clone();
+            super::watch(self.paths.clone(), interval, move || {
+                reloader.reload()
+            });
+        }
+
+        /// Config for `HttpServer::bind_rustls`
+        pub fn config(&self) -> ServerConfig {
+            let mut config = ServerConfig::new(NoClientAuth::new());
+            config.cert_resolver =
+                Arc::new(Resolver(Arc::clone(&self.current)));
+            config
+        }
+    }
+
+    struct Resolver(Arc<RwLock<CertifiedKey>>);
+
+    impl ResolvesServerCert for Resolver {
+        fn resolve(&self, _: ClientHello) -> Option<CertifiedKey> {
+            Some(self.0.read().unwrap().clone())
+        }
+    }
+
+    fn load(paths: &CertPaths) -> Result<CertifiedKey, CertError> {
+        let pem = read(&paths.cert)?;
+        let chain = pemfile::certs(&mut BufReader::new(&pem[..]))
+            .map_err(|_| invalid(&paths.cert, "malformed PEM"))?;
+        if chain.is_empty() {
+            return Err(invalid(&paths.cert, "no certificate found"));
+        }
+
+        let pem = read(&paths.key)?;
+        let mut keys =
+            pemfile::pkcs8_private_keys(&mut BufReader::new(&pem[..]))
+                .map_err(|_| invalid(&paths.key, "malformed PEM"))?;
+        if keys.is_empty() {
+            keys = pemfile::rsa_private_keys(&mut BufReader::new(&pem[..]))
+                .map_err(|_| invalid(&paths.key, "malformed PEM"))?;
+        }
+        let key = keys
+            .first()
+            .ok_or_else(|| invalid(&paths.key, "no private key found"))?;
+        let key = sign::any_supported_type(key)
+            .map_err(|_| invalid(&paths.key, "unsupported key type"))?;
+
+        if !key_matches(key.as_ref(), &chain[0].0) {
+            return Err(CertError::KeyMismatch {
+                cert: paths.cert.clone(),
+                key: paths.key.clone(),
+            });
+        }
+
+        Ok(CertifiedKey::new(chain, Arc::new(key)))
+    }
+
+    /// rustls cannot compare a key with a certificate directly, so sign a
+    /// message and verify it with the certificate's public key instead.
+    fn key_matches(key: &dyn sign::SigningKey, cert: &[u8]) -> bool {
+        let schemes = [
+            (
+                SignatureScheme::ECDSA_NISTP256_SHA256,
+                &webpki::ECDSA_P256_SHA256,
+            ),
+            (
+                SignatureScheme::ECDSA_NISTP384_SHA384,
+                &webpki::ECDSA_P384_SHA384,
+            ),
+            (SignatureScheme::ED25519, &webpki::ED25519),
+            (
+                SignatureScheme::RSA_PKCS1_SHA256,
+                &webpki::RSA_PKCS1_2048_8192_SHA256,
+            ),
+        ];
+        let offered: Vec<_> =
+            schemes.iter().map(|(scheme, _)| *scheme).collect();
+
+        let signer = match key.choose_scheme(&offered) {
+            Some(signer) => signer,
+            None => return false,
+        };
+        let alg = match schemes
+            .iter()
+            .find(|(scheme, _)| *scheme == signer.get_scheme())
+        {
+            Some((_, alg)) => alg,
+            None => return false,
+        };
+
+        let msg = b"certificate key check";
+        match (signer.sign(msg), webpki::EndEntityCert::from(cert)) {
+            (Ok(sig), Ok(cert)) => {
+                cert.verify_signature(alg, msg, &sig).is_ok()
+            }
+            _ => false,
+        }
+    }
+}
+// </tls-reload>
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::path::Path;
+    use std::time::Duration;
+
+    use ::openssl::asn1::Asn1Time;
+    use ::openssl::bn::BigNum;
+    use ::openssl::ec::{EcGroup, EcKey};
+    use ::openssl::nid::Nid;
+    use ::openssl::pkey::PKey;
+    use ::openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+    use ::openssl::x509::extension::SubjectAlternativeName;
+    use ::openssl::x509::{X509NameBuilder, X509};
+    use actix_web::rt::time::delay_for;
+    use actix_web::{web, App, HttpServer};
+
+    use super::{CertError, CertPaths};
+
+    #[cfg(all(feature = "openssl", not(feature = "rustls")))]
+    use super::openssl::Reloader;
+    #[cfg(feature = "rustls")]
+    use super::rustls::Reloader;
+
+    /// Writes a self-signed certificate for `common_name` and its key
+    fn self_signed(cert_path: &Path, key_path: &Path, common_name: &str) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, common_name)
+            .unwrap();
+        let name = name.build();
+
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        let serial = BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap();
+        cert.set_serial_number(&serial).unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_issuer_name(&name).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        let san = SubjectAlternativeName::new()
+            .dns("localhost")
+            .build(&cert.x509v3_context(None, None))
+            .unwrap();
+        cert.append_extension(san).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        cert.sign(&key, ::openssl::hash::MessageDigest::sha256())
+            .unwrap();
+
+        // write to a temporary file first so the watcher never sees a
+        // half-written file
+        let write = |path: &Path, data: &[u8]| {
+            let tmp = path.with_extension("tmp");
+            fs::File::create(&tmp).unwrap().write_all(data).unwrap();
+            fs::rename(&tmp, path).unwrap();
+        };
+        write(key_path, &key.private_key_to_pem_pkcs8().unwrap());
+        write(cert_path, &cert.build().to_pem().unwrap());
+    }
+
+    /// Performs a handshake and returns the negotiated protocol and the
+    /// common name of the server certificate.
+    fn handshake(
+        addr: std::net::SocketAddr,
+        server_name: bool,
+    ) -> (Vec<u8>, String) {
+        let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+        connector.set_verify(SslVerifyMode::NONE);
+        connector.set_alpn_protos(b"\x02h2\x08http/1.1").unwrap();
+
+        let stream = TcpStream::connect(addr).unwrap();
+        let stream = connector
+            .build()
+            .configure()
+            .unwrap()
+            .use_server_name_indication(server_name)
+            .connect("localhost", stream)
+            .unwrap();
+
+        let ssl = stream.ssl();
+        let protocol = ssl.selected_alpn_protocol().unwrap_or(b"").to_vec();
+        let cert = ssl.peer_certificate().unwrap();
+        let common_name = cert
+            .subject_name()
+            .entries_by_nid(Nid::COMMONNAME)
+            .next()
+            .unwrap()
+            .data()
+            .as_slice()
+            .to_vec();
+
+        (protocol, String::from_utf8(common_name).unwrap())
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = CertPaths::new(
+            dir.path().join("cert.pem"),
+            dir.path().join("key.pem"),
+        );
+
+        let err = Reloader::new(paths.clone()).err().unwrap();
+        assert!(matches!(err, CertError::Read { .. }));
+        assert!(err.to_string().contains("cert.pem"));
+
+        fs::write(&paths.cert, "not a certificate").unwrap();
+        let err = Reloader::new(paths.clone()).err().unwrap();
+        assert!(matches!(err, CertError::Invalid { .. }));
+
+        let other = CertPaths::new(
+            dir.path().join("other.pem"),
+            dir.path().join("other.key"),
+        );
+        self_signed(&paths.cert, &paths.key, "one");
+        self_signed(&other.cert, &other.key, "other");
+
+        let mismatched = CertPaths::new(&paths.cert, &other.key);
+        let err = Reloader::new(mismatched).err().unwrap();
+        assert!(matches!(err, CertError::KeyMismatch { .. }));
+
+        assert!(Reloader::new(paths).is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn test_alpn_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = CertPaths::new(
+            dir.path().join("cert.pem"),
+            dir.path().join("key.pem"),
+        );
+        self_signed(&paths.cert, &paths.key, "one");
+
+        let reloader = Reloader::new(paths.clone()).unwrap();
+        reloader.watch(Duration::from_millis(20));
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = HttpServer::new(|| {
+            App::new().route("/", web::get().to(|| async { "Hello." }))
+        })
+        .workers(1)
+        .disable_signals();
+        #[cfg(feature = "rustls")]
+        let server = server.listen_rustls(listener, reloader.config());
+        #[cfg(all(feature = "openssl", not(feature = "rustls")))]
+        let server =
+            server.listen_openssl(listener, reloader.acceptor().unwrap());
+        let server = server.unwrap().run();
+
+        let (protocol, common_name) = handshake(addr, true);
+        assert_eq!(protocol, b"h2");
+        assert_eq!(common_name, "one");
+        assert_eq!(handshake(addr, false), (b"h2".to_vec(), "one".into()));
+
+        // rotate the certificate, new connections pick it up without a restart
+        self_signed(&paths.cert, &paths.key, "two");
+        let mut common_name = String::new();
+        for _ in 0..100 {
+            delay_for(Duration::from_millis(20)).await;
+            common_name = handshake(addr, true).1;
+            if common_name == "two" {
+                break;
+            }
+        }
+        assert_eq!(common_name, "two");
+
+        // a broken rotation keeps serving the last good certificate
+        fs::write(&paths.key, "garbage").unwrap();
+        delay_for(Duration::from_millis(100)).await;
+        assert_eq!(handshake(addr, true).1, "two");
+
+        server.stop(false).await;
+    }
+}
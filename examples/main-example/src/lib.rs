@@ -0,0 +1,33 @@
+// <main-example>
+use actix_web::{web, HttpRequest, Responder};
+
+async fn greet(req: HttpRequest) -> impl Responder {
+    let name = req.match_info().get("name").unwrap_or("World");
+    format!("Hello {}!", &name)
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/", web::get().to(greet))
+        .route("/{name}", web::get().to(greet));
+}
+// </main-example>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn test_greet() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Hello World!");
+
+        let req = test::TestRequest::get().uri("/Ferris").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Hello Ferris!");
+    }
+}
@@ -0,0 +1,1077 @@
+#![allow(dead_code)]
+
+// <cache>
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use actix_service::{Service, Transform};
+use actix_web::body::{Body, MessageBody, ResponseBody};
+use actix_web::http::{header, HeaderMap, HeaderName, HeaderValue, Method};
+use actix_web::http::{StatusCode, Uri};
+use actix_web::{
+    dev::ServiceRequest, dev::ServiceResponse, Error, HttpRequest,
+    HttpResponse,
+};
+use futures::future::{ok, Ready};
+use futures::Future;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::etag::{buffer, Buffered};
+
+/// A stored response, along with the request headers it varies on.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+    vary: Vec<(String, Option<String>)>,
+    status: u16,
+    headers: Vec<(String, Vec<u8>)>,
+    #[serde(with = "base64_body")]
+    body: Vec<u8>,
+    stored: SystemTime,
+    fresh_for: Duration,
+    stale_for: Duration,
+}
+
+impl Entry {
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| joined(headers, name) == *value)
+    }
+
+    fn expires(&self) -> SystemTime {
+        self.stored + self.fresh_for + self.stale_for
+    }
+
+    fn size(&self) -> usize {
+        self.body.len()
+            + self
+                .headers
+                .iter()
+                .map(|(name, value)| name.len() + value.len())
+                .sum::<usize>()
+    }
+}
+
+mod base64_body {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        body: &[u8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(body))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        let body = String::deserialize(deserializer)?;
+        base64::decode(body).map_err(de::Error::custom)
+    }
+}
+
+/// All values of a request header, as a single one
+fn joined(headers: &HeaderMap, name: &str) -> Option<String> {
+    let values: Vec<_> = headers
+        .get_all(name)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        .collect();
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.join(", "))
+    }
+}
+
+/// Where the responses are kept. Every key holds the variants of one URL.
+///
+/// The middleware calls a store on the blocking thread pool, so it may wait
+/// for the disk or the network.
+pub trait Store: Send + Sync {
+    fn get(&self, key: &str) -> Option<Vec<Entry>>;
+    fn put(&self, key: &str, variants: Vec<Entry>);
+    fn remove(&self, key: &str);
+}
+
+/// Keeps up to `max_bytes` of responses in memory and evicts the least
+/// recently used URLs first.
+pub struct MemoryStore {
+    max_bytes: usize,
+    lru: Mutex<Lru>,
+}
+
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<String, (u64, Vec<Entry>)>,
+    // last use of every key, oldest first
+    order: BTreeMap<u64, String>,
+    tick: u64,
+    bytes: usize,
+}
+
+impl Lru {
+    fn remove(&mut self, key: &str) {
+        if let Some((tick, variants)) = self.entries.remove(key) {
+            self.order.remove(&tick);
+            self.bytes -= variants.iter().map(Entry::size).sum::<usize>();
+        }
+    }
+}
+
+impl MemoryStore {
+    pub fn new(max_bytes: usize) -> Self {
+        MemoryStore {
+            max_bytes,
+            lru: Mutex::new(Lru::default()),
+        }
+    }
+}
+
+impl Store for MemoryStore {
+    fn get(&self, key: &str) -> Option<Vec<Entry>> {
+        let mut guard = self.lru.lock().unwrap();
+        let lru = &mut *guard;
+        lru.tick += 1;
+        let tick = lru.tick;
+
+        let (used, variants) = lru.entries.get_mut(key)?;
+        let last = std::mem::replace(used, tick);
+        let variants = variants.clone();
+        lru.order.remove(&last);
+        lru.order.insert(tick, key.to_owned());
+        Some(variants)
+    }
+
+    fn put(&self, key: &str, variants: Vec<Entry>) {
+        let size = variants.iter().map(Entry::size).sum::<usize>();
+        let mut lru = self.lru.lock().unwrap();
+        lru.remove(key);
+        if size > self.max_bytes {
+            return;
+        }
+
+        while lru.bytes + size > self.max_bytes {
+            let oldest = match lru.order.keys().next() {
+                Some(&tick) => lru.order[&tick].clone(),
+                None => break,
+            };
+            lru.remove(&oldest);
+        }
+
+        lru.tick += 1;
+        let tick = lru.tick;
+        lru.entries.insert(key.to_owned(), (tick, variants));
+        lru.order.insert(tick, key.to_owned());
+        lru.bytes += size;
+    }
+
+    fn remove(&self, key: &str) {
+        self.lru.lock().unwrap().remove(key);
+    }
+}
+
+/// Keeps every URL in a JSON file in `dir`, so that the cache survives a
+/// restart. Expired entries are dropped when their URL is stored again.
+pub struct DiskStore {
+    dir: PathBuf,
+}
+
+impl DiskStore {
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(DiskStore { dir })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let name = hex::encode(Sha256::digest(key.as_bytes()));
+        self.dir.join(name + ".json")
+    }
+}
+
+impl Store for DiskStore {
+    fn get(&self, key: &str) -> Option<Vec<Entry>> {
+        let path = self.path(key);
+        let json = match fs::read(&path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+            Err(err) => {
+                log::warn!("cannot read {}: {}", path.display(), err);
+                return None;
+            }
+        };
+        match serde_json::from_slice(&json) {
+            Ok(variants) => Some(variants),
+            Err(err) => {
+                log::warn!("dropping corrupt {}: {}", path.display(), err);
+                self.remove(key);
+                None
+            }
+        }
+    }
+
+    fn put(&self, key: &str, variants: Vec<Entry>) {
+        let path = self.path(key);
+        // readers see either the old or the new file, never half of one,
+        // and every writer of the same key gets a temporary file of its own
+        let written = serde_json::to_vec(&variants)
+            .map_err(io::Error::from)
+            .and_then(|json| {
+                let mut tmp = tempfile::NamedTempFile::new_in(&self.dir)?;
+                tmp.write_all(&json)?;
+                tmp.persist(&path)?;
+                Ok(())
+            });
+        if let Err(err) = written {
+            log::warn!("cannot write {}: {}", path.display(), err);
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let path = self.path(key);
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                log::warn!("cannot remove {}: {}", path.display(), err);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Source of the current time, so that tests can let entries expire.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// The directives of a `Cache-Control` header that matter to a shared cache
+#[derive(Default)]
+struct CacheControl {
+    no_store: bool,
+    private: bool,
+    public: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+    stale_while_revalidate: Option<u64>,
+}
+
+impl CacheControl {
+    fn parse(headers: &HeaderMap) -> Self {
+        let mut cc = CacheControl::default();
+        let values = headers
+            .get_all(header::CACHE_CONTROL)
+            .filter_map(|value| value.to_str().ok());
+
+        for directive in values.flat_map(|value| value.split(',')) {
+            let mut parts = directive.splitn(2, '=');
+            let name = parts.next().unwrap().trim().to_ascii_lowercase();
+            let secs = parts
+                .next()
+                .and_then(|value| value.trim().trim_matches('"').parse().ok());
+
+            match name.as_str() {
+                "no-store" => cc.no_store = true,
+                "private" => cc.private = true,
+                "public" => cc.public = true,
+                "max-age" => cc.max_age = secs,
+                "s-maxage" => cc.s_maxage = secs,
+                "stale-while-revalidate" => cc.stale_while_revalidate = secs,
+                _ => {}
+            }
+        }
+        cc
+    }
+}
+
+/// Caches `GET` and `HEAD` responses that allow it with `Cache-Control`.
+///
+/// Responses are kept per method, path and query string, and per value of
+/// the request headers named in their `Vary` header. Every response gets an
+/// `X-Cache: HIT` or `X-Cache: MISS` header.
+#[derive(Clone)]
+pub struct Cache {
+    store: Arc<dyn Store>,
+    clock: Arc<dyn Clock>,
+    max_size: usize,
+    stale_while_revalidate: bool,
+    revalidating: Arc<Mutex<HashSet<String>>>,
+    // the store is read and written again to change a key, which must not
+    // interleave for the same key
+    writing: Arc<[Mutex<()>]>,
+}
+
+fn write_lock<'a>(locks: &'a [Mutex<()>], key: &str) -> MutexGuard<'a, ()> {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    let lock = &locks[hasher.finish() as usize % locks.len()];
+    lock.lock().unwrap()
+}
+
+impl Cache {
+    pub fn new(store: impl Store + 'static) -> Self {
+        Cache {
+            store: Arc::new(store),
+            clock: Arc::new(SystemClock),
+            max_size: 1024 * 1024,
+            stale_while_revalidate: false,
+            revalidating: Arc::new(Mutex::new(HashSet::new())),
+            writing: (0..16).map(|_| Mutex::new(())).collect(),
+        }
+    }
+
+    /// Honors `stale-while-revalidate`: once an entry is stale, the next
+    /// request goes to the handler and refreshes it, while the requests that
+    /// arrive in the meantime still get the stale copy.
+    pub fn stale_while_revalidate(mut self) -> Self {
+        self.stale_while_revalidate = true;
+        self
+    }
+
+    /// Responses larger than this are not stored.
+    pub fn max_size(mut self, bytes: usize) -> Self {
+        self.max_size = bytes;
+        self
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Runs `f` with the store on the blocking thread pool
+    async fn blocking<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&dyn Store) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let store = self.store.clone();
+        actix_web::web::block(move || Ok::<_, ()>(f(&*store)))
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Looks up a fresh entry, or a stale one while another request
+    /// refreshes it. Tells the request to refresh it otherwise.
+    async fn lookup(
+        &self,
+        key: &str,
+        headers: &HeaderMap,
+    ) -> Result<Lookup, Error> {
+        let variants = {
+            let key = key.to_owned();
+            let writing = self.writing.clone();
+            self.blocking(move |store| {
+                let variants = store.get(&key)?;
+                // a damaged file may hold any number
+                if variants
+                    .iter()
+                    .any(|e| StatusCode::from_u16(e.status).is_err())
+                {
+                    log::warn!("dropping entries with a bad status: {}", key);
+                    let _writing = write_lock(&writing, &key);
+                    store.remove(&key);
+                    return None;
+                }
+                Some(variants)
+            })
+            .await?
+        };
+        let now = self.clock.now();
+        let entry = match variants
+            .unwrap_or_default()
+            .into_iter()
+            .find(|e| e.matches(headers))
+        {
+            Some(entry) => entry,
+            None => return Ok(Lookup::Miss),
+        };
+
+        let age = now.duration_since(entry.stored).unwrap_or_default();
+        if age < entry.fresh_for {
+            return Ok(Lookup::Hit(entry, age));
+        }
+        if self.stale_while_revalidate && now < entry.expires() {
+            let mut revalidating = self.revalidating.lock().unwrap();
+            if !revalidating.insert(key.to_owned()) {
+                return Ok(Lookup::Hit(entry, age));
+            }
+            return Ok(Lookup::Revalidate(Revalidating {
+                keys: self.revalidating.clone(),
+                key: key.to_owned(),
+            }));
+        }
+        Ok(Lookup::Miss)
+    }
+
+    /// Builds an entry for a response, if it may be stored
+    fn entry<B>(
+        &self,
+        req: &HttpRequest,
+        res: &HttpResponse<B>,
+    ) -> Option<Entry> {
+        let headers = res.headers();
+        let cc = CacheControl::parse(headers);
+        if res.status() != StatusCode::OK
+            || cc.no_store
+            || cc.private
+            || headers.contains_key(header::SET_COOKIE)
+        {
+            return None;
+        }
+        // shared caches may only keep answers to authorized requests when
+        // the response says so
+        if req.headers().contains_key(header::AUTHORIZATION)
+            && !cc.public
+            && cc.s_maxage.is_none()
+        {
+            return None;
+        }
+        let fresh_for = cc.s_maxage.or(cc.max_age).filter(|&secs| secs > 0)?;
+
+        let mut vary = Vec::new();
+        for name in headers
+            .get_all(header::VARY)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+        {
+            let name = name.trim().to_ascii_lowercase();
+            if name == "*" {
+                return None;
+            }
+            let value = joined(req.headers(), &name);
+            vary.push((name, value));
+        }
+
+        Some(Entry {
+            vary,
+            status: res.status().as_u16(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| {
+                    (name.as_str().to_owned(), value.as_bytes().to_vec())
+                })
+                .collect(),
+            body: Vec::new(),
+            stored: self.clock.now(),
+            fresh_for: Duration::from_secs(fresh_for),
+            stale_for: Duration::from_secs(
+                cc.stale_while_revalidate.unwrap_or(0),
+            ),
+        })
+    }
+
+    async fn store(&self, key: String, entry: Entry) -> Result<(), Error> {
+        let now = self.clock.now();
+        let writing = self.writing.clone();
+        self.blocking(move |store| {
+            let _writing = write_lock(&writing, &key);
+            let mut variants = store.get(&key).unwrap_or_default();
+            variants.retain(|e| e.vary != entry.vary && e.expires() > now);
+            variants.push(entry);
+            store.put(&key, variants);
+        })
+        .await
+    }
+}
+
+enum Lookup {
+    Hit(Entry, Duration),
+    Revalidate(Revalidating),
+    Miss,
+}
+
+/// Lets other requests refresh the key again once dropped, which also
+/// happens when the request is cancelled before its handler is done.
+struct Revalidating {
+    keys: Arc<Mutex<HashSet<String>>>,
+    key: String,
+}
+
+impl Drop for Revalidating {
+    fn drop(&mut self) {
+        self.keys.lock().unwrap().remove(&self.key);
+    }
+}
+
+fn key(method: &Method, uri: &Uri) -> String {
+    let query = uri.query().map_or(String::new(), |q| format!("?{}", q));
+    format!("{} {}{}", method, uri.path(), query)
+}
+
+fn x_cache(headers: &mut HeaderMap, value: &'static str) {
+    headers.insert(
+        HeaderName::from_static("x-cache"),
+        HeaderValue::from_static(value),
+    );
+}
+
+fn cached_response(entry: Entry, age: Duration) -> HttpResponse {
+    // checked by `lookup`
+    let status = StatusCode::from_u16(entry.status).unwrap();
+    let mut res = HttpResponse::build(status).body(entry.body);
+
+    let headers = res.headers_mut();
+    for (name, value) in entry.headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_bytes(&value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    headers.insert(header::AGE, HeaderValue::from(age.as_secs()));
+    x_cache(headers, "HIT");
+    res
+}
+
+impl<S, B> Transform<S> for Cache
+where
+    S: Service<
+            Request = ServiceRequest,
+            Response = ServiceResponse<B>,
+            Error = Error,
+        > + 'static,
+    S::Future: 'static,
+    B: MessageBody + Unpin + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CacheMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(CacheMiddleware {
+            service: Rc::new(RefCell::new(service)),
+            cache: self.clone(),
+        })
+    }
+}
+
+pub struct CacheMiddleware<S> {
+    // the store is only asked from within the response future
+    service: Rc<RefCell<S>>,
+    cache: Cache,
+}
+
+impl<S, B> Service for CacheMiddleware<S>
+where
+    S: Service<
+            Request = ServiceRequest,
+            Response = ServiceResponse<B>,
+            Error = Error,
+        > + 'static,
+    S::Future: 'static,
+    B: MessageBody + Unpin + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.service.borrow_mut().poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let cache = self.cache.clone();
+        let service = self.service.clone();
+        let method = req.method().clone();
+
+        if method != Method::GET && method != Method::HEAD {
+            // a successful change makes the stored copies outdated
+            let uri = req.uri().clone();
+            let fut = service.borrow_mut().call(req);
+            return Box::pin(async move {
+                let res = fut.await?;
+                if !res.status().is_client_error()
+                    && !res.status().is_server_error()
+                {
+                    cache
+                        .blocking(move |store| {
+                            store.remove(&key(&Method::GET, &uri));
+                            store.remove(&key(&Method::HEAD, &uri));
+                        })
+                        .await?;
+                }
+                Ok(res.map_body(|_, body| {
+                    ResponseBody::Other(Body::from_message(body))
+                }))
+            });
+        }
+
+        Box::pin(async move {
+            let key = key(&method, req.uri());
+            // held until the refreshed entry is stored
+            let _revalidating = match cache.lookup(&key, req.headers()).await?
+            {
+                Lookup::Hit(entry, age) => {
+                    let res = cached_response(entry, age);
+                    return Ok(req.into_response(res));
+                }
+                Lookup::Revalidate(revalidating) => Some(revalidating),
+                Lookup::Miss => None,
+            };
+
+            let fut = service.borrow_mut().call(req);
+            let mut res = fut.await?;
+
+            let body = res.take_body();
+            let mut res = match cache.entry(res.request(), res.response()) {
+                Some(mut entry) => match buffer(body, cache.max_size).await? {
+                    Buffered::Complete(bytes) => {
+                        entry.body = bytes.to_vec();
+                        cache.store(key, entry).await?;
+                        res.map_body(|_, _| ResponseBody::Other(bytes.into()))
+                    }
+                    Buffered::TooLarge(body) => {
+                        res.map_body(|_, _| ResponseBody::Other(body))
+                    }
+                },
+                None => res.map_body(|_, _| {
+                    ResponseBody::Other(Body::from_message(body))
+                }),
+            };
+
+            x_cache(res.headers_mut(), "MISS");
+            Ok(res)
+        })
+    }
+}
+// </cache>
+
+// <cache-usage>
+use actix_web::{web, App, HttpServer};
+
+async fn report() -> HttpResponse {
+    HttpResponse::Ok()
+        .header(
+            header::CACHE_CONTROL,
+            "max-age=60, stale-while-revalidate=600",
+        )
+        .body("an expensive report")
+}
+
+async fn greeting() -> HttpResponse {
+    HttpResponse::Ok()
+        .header(header::CACHE_CONTROL, "public, max-age=5")
+        .header(header::VARY, "accept-language")
+        .body("Hello")
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    // shared by all workers
+    let memory = Cache::new(MemoryStore::new(64 * 1024 * 1024));
+    let disk = Cache::new(DiskStore::new("cache")?).stale_while_revalidate();
+
+    HttpServer::new(move || {
+        App::new()
+            .service(
+                web::scope("/reports")
+                    .wrap(disk.clone())
+                    .route("/daily", web::get().to(report)),
+            )
+            .service(
+                web::scope("")
+                    .wrap(memory.clone())
+                    .route("/", web::get().to(greeting)),
+            )
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await
+}
+// </cache-usage>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+    use futures::channel::oneshot;
+    use futures::future::{FutureExt, LocalBoxFuture};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct ManualClock(Mutex<SystemTime>);
+
+    impl ManualClock {
+        fn new() -> Arc<Self> {
+            Arc::new(ManualClock(Mutex::new(SystemTime::now())))
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.0.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> SystemTime {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    /// Counts its calls and answers with the given `Cache-Control`
+    fn counting(
+        calls: &Arc<AtomicUsize>,
+        cache_control: &'static str,
+    ) -> impl Fn(HttpRequest) -> HttpResponse + Clone + 'static {
+        let calls = calls.clone();
+        move |req| {
+            let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            let lang = req
+                .headers()
+                .get(header::ACCEPT_LANGUAGE)
+                .map_or("en", |lang| lang.to_str().unwrap());
+            HttpResponse::Ok()
+                .header(header::CACHE_CONTROL, cache_control)
+                .header(header::VARY, "accept-language")
+                .body(format!("{} {}", lang, n))
+        }
+    }
+
+    async fn get(
+        app: &mut impl Service<
+            Request = actix_http::Request,
+            Response = ServiceResponse,
+            Error = Error,
+        >,
+        req: test::TestRequest,
+    ) -> (String, String) {
+        let res = test::call_service(app, req.to_request()).await;
+        let x_cache = res.headers().get("x-cache").unwrap();
+        let x_cache = x_cache.to_str().unwrap().to_owned();
+        let body = test::read_body(res).await;
+        (x_cache, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn uri(uri: &str) -> test::TestRequest {
+        test::TestRequest::get().uri(uri)
+    }
+
+    #[actix_rt::test]
+    async fn test_max_age() {
+        let clock = ManualClock::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = Cache::new(MemoryStore::new(1024)).clock(clock.clone());
+        let mut app = test::init_service(
+            App::new()
+                .wrap(cache)
+                .route("/", web::get().to(counting(&calls, "max-age=60"))),
+        )
+        .await;
+
+        assert_eq!(
+            get(&mut app, uri("/")).await,
+            ("MISS".into(), "en 1".into())
+        );
+        clock.advance(Duration::from_secs(59));
+        let res = test::call_service(&mut app, uri("/").to_request()).await;
+        assert_eq!(res.headers().get("x-cache").unwrap(), "HIT");
+        assert_eq!(res.headers().get(header::AGE).unwrap(), "59");
+        assert_eq!(test::read_body(res).await, "en 1");
+
+        // the query string and the varying headers are part of the key
+        assert_eq!(get(&mut app, uri("/?q=1")).await.1, "en 2");
+        let de = uri("/").header(header::ACCEPT_LANGUAGE, "de");
+        assert_eq!(get(&mut app, de).await, ("MISS".into(), "de 3".into()));
+        let de = uri("/").header(header::ACCEPT_LANGUAGE, "de");
+        assert_eq!(get(&mut app, de).await, ("HIT".into(), "de 3".into()));
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            get(&mut app, uri("/")).await,
+            ("MISS".into(), "en 4".into())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_not_stored() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut app = test::init_service(
+            App::new()
+                .wrap(Cache::new(MemoryStore::new(1024)))
+                .route("/none", web::get().to(counting(&calls, "no-cache")))
+                .route(
+                    "/no-store",
+                    web::get().to(counting(&calls, "no-store")),
+                )
+                .route(
+                    "/private",
+                    web::get().to(counting(&calls, "private, max-age=60")),
+                )
+                .route(
+                    "/shared",
+                    web::get().to(counting(&calls, "s-maxage=60")),
+                ),
+        )
+        .await;
+
+        for path in &["/none", "/no-store", "/private"] {
+            assert_eq!(get(&mut app, uri(path)).await.0, "MISS");
+            assert_eq!(get(&mut app, uri(path)).await.0, "MISS");
+        }
+
+        let auth = || uri("/shared").header(header::AUTHORIZATION, "secret");
+        assert_eq!(get(&mut app, auth()).await.0, "MISS");
+        assert_eq!(get(&mut app, auth()).await.0, "HIT");
+        assert_eq!(calls.load(Ordering::SeqCst), 7);
+    }
+
+    #[actix_rt::test]
+    async fn test_invalidate() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut app = test::init_service(
+            App::new().wrap(Cache::new(MemoryStore::new(1024))).service(
+                web::resource("/")
+                    .route(web::get().to(counting(&calls, "max-age=60")))
+                    .route(web::post().to(HttpResponse::NoContent)),
+            ),
+        )
+        .await;
+
+        assert_eq!(get(&mut app, uri("/")).await.1, "en 1");
+        assert_eq!(get(&mut app, uri("/")).await.1, "en 1");
+        let req = test::TestRequest::post().uri("/").to_request();
+        test::call_service(&mut app, req).await;
+        assert_eq!(
+            get(&mut app, uri("/")).await,
+            ("MISS".into(), "en 2".into())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_lru() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        // each entry takes 46 bytes: 4 for the body, 42 for the headers
+        let mut app = test::init_service(
+            App::new()
+                .wrap(Cache::new(MemoryStore::new(120)))
+                .route("/{n}", web::get().to(counting(&calls, "max-age=60"))),
+        )
+        .await;
+
+        get(&mut app, uri("/a")).await;
+        get(&mut app, uri("/b")).await;
+        assert_eq!(get(&mut app, uri("/a")).await.0, "HIT");
+        // evicts /b, which was used longest ago
+        get(&mut app, uri("/c")).await;
+        assert_eq!(get(&mut app, uri("/a")).await.0, "HIT");
+        assert_eq!(get(&mut app, uri("/b")).await.0, "MISS");
+    }
+
+    /// Like `counting` with stale-while-revalidate, but call number `n`
+    /// waits until the returned sender fires
+    fn gated(
+        calls: &Arc<AtomicUsize>,
+        n: usize,
+    ) -> (
+        oneshot::Sender<()>,
+        impl Fn() -> LocalBoxFuture<'static, Result<HttpResponse, Error>>
+            + Clone
+            + 'static,
+    ) {
+        let (release, gate) = oneshot::channel();
+        let gate = Arc::new(Mutex::new(Some(gate)));
+        let calls = calls.clone();
+        let handler = move || {
+            let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            let gate = if call == n {
+                gate.lock().unwrap().take()
+            } else {
+                None
+            };
+            async move {
+                if let Some(gate) = gate {
+                    gate.await.unwrap();
+                }
+                Ok(HttpResponse::Ok()
+                    .header(
+                        header::CACHE_CONTROL,
+                        "max-age=10, stale-while-revalidate=30",
+                    )
+                    .body(format!("en {}", call)))
+            }
+            .boxed_local()
+        };
+        (release, handler)
+    }
+
+    /// Polls `fut` until the handler has been called `n` times
+    async fn until_called<F: Future + Unpin>(
+        fut: &mut F,
+        calls: &AtomicUsize,
+        n: usize,
+    ) {
+        while calls.load(Ordering::SeqCst) < n {
+            assert!(futures::poll!(&mut *fut).is_pending());
+            actix_rt::time::delay_for(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_stale_while_revalidate() {
+        let clock = ManualClock::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (release, handler) = gated(&calls, 2);
+        let cache = Cache::new(MemoryStore::new(1024))
+            .stale_while_revalidate()
+            .clock(clock.clone());
+        let mut app = test::init_service(
+            App::new().wrap(cache).route("/", web::get().to(handler)),
+        )
+        .await;
+
+        assert_eq!(get(&mut app, uri("/")).await.1, "en 1");
+        clock.advance(Duration::from_secs(20));
+
+        // the first request refreshes the entry, the second one does not wait
+        let mut refresh = Box::pin(app.call(uri("/").to_request()));
+        until_called(&mut refresh, &calls, 2).await;
+        assert_eq!(
+            get(&mut app, uri("/")).await,
+            ("HIT".into(), "en 1".into())
+        );
+        release.send(()).unwrap();
+        let refresh = refresh.await.unwrap();
+        assert_eq!(refresh.headers().get("x-cache").unwrap(), "MISS");
+        assert_eq!(test::read_body(refresh).await, "en 2");
+
+        assert_eq!(
+            get(&mut app, uri("/")).await,
+            ("HIT".into(), "en 2".into())
+        );
+        clock.advance(Duration::from_secs(40));
+        assert_eq!(get(&mut app, uri("/")).await.1, "en 3");
+    }
+
+    #[actix_rt::test]
+    async fn test_cancelled_revalidation() {
+        let clock = ManualClock::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (_release, handler) = gated(&calls, 2);
+        let cache = Cache::new(MemoryStore::new(1024))
+            .stale_while_revalidate()
+            .clock(clock.clone());
+        let mut app = test::init_service(
+            App::new().wrap(cache).route("/", web::get().to(handler)),
+        )
+        .await;
+
+        assert_eq!(get(&mut app, uri("/")).await.1, "en 1");
+        clock.advance(Duration::from_secs(20));
+
+        // the client goes away while the entry is being refreshed
+        let mut refresh = Box::pin(app.call(uri("/").to_request()));
+        until_called(&mut refresh, &calls, 2).await;
+        drop(refresh);
+
+        assert_eq!(
+            get(&mut app, uri("/")).await,
+            ("MISS".into(), "en 3".into())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_disk_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..2 {
+            // a new store and app, like after a restart
+            let store = DiskStore::new(dir.path()).unwrap();
+            let mut app = test::init_service(
+                App::new()
+                    .wrap(Cache::new(store))
+                    .route("/", web::get().to(counting(&calls, "max-age=60"))),
+            )
+            .await;
+            assert_eq!(get(&mut app, uri("/")).await.1, "en 1");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // a status that does not exist is a miss
+        let store = DiskStore::new(dir.path()).unwrap();
+        let json = fs::read_to_string(store.path("GET /")).unwrap();
+        let json = json.replace(r#""status":200"#, r#""status":1000"#);
+        fs::write(store.path("GET /"), json).unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .wrap(Cache::new(store))
+                .route("/", web::get().to(counting(&calls, "max-age=60"))),
+        )
+        .await;
+        assert_eq!(
+            get(&mut app, uri("/")).await,
+            ("MISS".into(), "en 2".into())
+        );
+
+        let store = DiskStore::new(dir.path()).unwrap();
+        fs::write(store.path("GET /"), "{").unwrap();
+        assert!(store.get("GET /").is_none());
+        assert!(!store.path("GET /").exists());
+    }
+
+    /// Takes its time to read, so that writers overlap
+    struct SlowStore(MemoryStore);
+
+    impl Store for SlowStore {
+        fn get(&self, key: &str) -> Option<Vec<Entry>> {
+            let variants = self.0.get(key);
+            std::thread::sleep(Duration::from_millis(50));
+            variants
+        }
+
+        fn put(&self, key: &str, variants: Vec<Entry>) {
+            self.0.put(key, variants)
+        }
+
+        fn remove(&self, key: &str) {
+            self.0.remove(key)
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_concurrent_store() {
+        let cache = Cache::new(SlowStore(MemoryStore::new(1024)));
+        let entry = |lang: &str| Entry {
+            vary: vec![("accept-language".into(), Some(lang.into()))],
+            status: 200,
+            headers: Vec::new(),
+            body: lang.into(),
+            stored: SystemTime::now(),
+            fresh_for: Duration::from_secs(60),
+            stale_for: Duration::from_secs(0),
+        };
+
+        let (en, de) = futures::join!(
+            cache.store("GET /".into(), entry("en")),
+            cache.store("GET /".into(), entry("de")),
+        );
+        en.unwrap();
+        de.unwrap();
+        assert_eq!(cache.store.get("GET /").unwrap().len(), 2);
+    }
+}
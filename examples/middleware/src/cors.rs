@@ -0,0 +1,623 @@
+#![allow(dead_code)]
+
+// <cors>
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use actix_service::{Service, Transform};
+use actix_web::dev::{RequestHead, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use actix_web::http::Method;
+use actix_web::{Error, HttpResponse};
+use futures::future::{ok, Future, Ready};
+
+type OriginFn = Arc<dyn Fn(&str, &RequestHead) -> bool + Send + Sync>;
+
+const ANY_ORIGIN_WITH_CREDENTIALS: &str =
+    "CORS: credentials cannot be allowed for every origin";
+
+/// Cross-origin resource sharing: tells browsers which other sites may call
+/// the wrapped routes, and answers their `OPTIONS` preflight requests.
+///
+/// Requests with an `Origin` header that is not allowed are rejected with
+/// `403 Forbidden`. Browsers send `Origin` with same-origin `POST`s too, so
+/// the site's own origin has to be allowed as well.
+#[derive(Clone)]
+pub struct Cors {
+    any_origin: bool,
+    origins: HashSet<String>,
+    /// Prefix and suffix around the `*`
+    wildcards: Vec<(String, String)>,
+    predicates: Vec<OriginFn>,
+    methods: Vec<Method>,
+    headers: HashSet<HeaderName>,
+    expose_headers: Vec<HeaderName>,
+    credentials: bool,
+    max_age: Option<u32>,
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Cors::new()
+    }
+}
+
+impl Cors {
+    /// Allows no origins yet, and the methods `GET`, `HEAD` and `POST`
+    pub fn new() -> Self {
+        Cors {
+            any_origin: false,
+            origins: HashSet::new(),
+            wildcards: Vec::new(),
+            predicates: Vec::new(),
+            methods: vec![Method::GET, Method::HEAD, Method::POST],
+            headers: HashSet::new(),
+            expose_headers: Vec::new(),
+            credentials: false,
+            max_age: None,
+        }
+    }
+
+    /// Allows an origin like `https://example.com`. A `*` in it matches any
+    /// host name labels, e.g. `https://*.example.com`, and `*` on its own
+    /// allows every origin.
+    ///
+    /// Panics if `*` is combined with [`allow_credentials`], which would let
+    /// every site make requests with the user's cookies.
+    ///
+    /// [`allow_credentials`]: Cors::allow_credentials
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        match origin.find('*') {
+            _ if origin == "*" => {
+                assert!(!self.credentials, "{}", ANY_ORIGIN_WITH_CREDENTIALS);
+                self.any_origin = true;
+            }
+            Some(star) => self.wildcards.push((
+                origin[..star].to_owned(),
+                origin[star + 1..].to_owned(),
+            )),
+            None => {
+                self.origins.insert(origin.to_owned());
+            }
+        }
+        self
+    }
+
+    /// Allows the origins for which `f` returns `true`
+    pub fn allow_origin_fn<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, &RequestHead) -> bool + Send + Sync + 'static,
+    {
+        self.predicates.push(Arc::new(f));
+        self
+    }
+
+    pub fn allow_methods(mut self, methods: &[Method]) -> Self {
+        self.methods = methods.to_vec();
+        self
+    }
+
+    /// Request headers that scripts may set, besides the ones browsers
+    /// always allow
+    pub fn allow_headers(mut self, headers: &[HeaderName]) -> Self {
+        self.headers.extend(headers.iter().cloned());
+        self
+    }
+
+    /// Response headers that scripts may read, besides the ones browsers
+    /// always expose
+    pub fn expose_headers(mut self, headers: &[HeaderName]) -> Self {
+        self.expose_headers.extend(headers.iter().cloned());
+        self
+    }
+
+    /// Allows requests with cookies and `Authorization` headers. Panics if
+    /// every origin is allowed.
+    pub fn allow_credentials(mut self) -> Self {
+        assert!(!self.any_origin, "{}", ANY_ORIGIN_WITH_CREDENTIALS);
+        self.credentials = true;
+        self
+    }
+
+    /// How long browsers may cache the answer to a preflight request
+    pub fn max_age(mut self, secs: u32) -> Self {
+        self.max_age = Some(secs);
+        self
+    }
+
+    fn allows_origin(&self, origin: &str, head: &RequestHead) -> bool {
+        self.any_origin
+            || self.origins.contains(origin)
+            || self.wildcards.iter().any(|(prefix, suffix)| {
+                origin.len() > prefix.len() + suffix.len()
+                    && origin.starts_with(prefix.as_str())
+                    && origin.ends_with(suffix.as_str())
+                    && !origin[prefix.len()..origin.len() - suffix.len()]
+                        .contains('/')
+            })
+            || self.predicates.iter().any(|f| f(origin, head))
+    }
+
+    /// Checks the method and headers a preflight request asks for
+    fn allows_preflight(&self, head: &RequestHead) -> bool {
+        let method = head
+            .headers()
+            .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+            .and_then(|method| Method::from_bytes(method.as_bytes()).ok());
+        if !matches!(method, Some(method) if self.methods.contains(&method)) {
+            return false;
+        }
+
+        requested_headers(head).iter().all(|name| {
+            matches!(
+                HeaderName::from_bytes(name.as_bytes()),
+                Ok(name) if self.headers.contains(&name)
+            )
+        })
+    }
+
+    fn set_headers(&self, headers: &mut HeaderMap, origin: &HeaderValue) {
+        if self.any_origin {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_ORIGIN,
+                HeaderValue::from_static("*"),
+            );
+        } else {
+            headers
+                .insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+        }
+        if self.credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+    }
+
+    fn preflight(
+        &self,
+        head: &RequestHead,
+        origin: &HeaderValue,
+    ) -> HttpResponse {
+        let mut res = HttpResponse::NoContent();
+        let methods: Vec<&str> =
+            self.methods.iter().map(Method::as_str).collect();
+        res.header(header::ACCESS_CONTROL_ALLOW_METHODS, methods.join(", "));
+
+        let requested = requested_headers(head);
+        if !requested.is_empty() {
+            res.header(
+                header::ACCESS_CONTROL_ALLOW_HEADERS,
+                requested.join(", "),
+            );
+        }
+        if let Some(max_age) = self.max_age {
+            res.header(header::ACCESS_CONTROL_MAX_AGE, u64::from(max_age));
+        }
+
+        let mut res = res.finish();
+        self.set_headers(res.headers_mut(), origin);
+        add_vary(
+            res.headers_mut(),
+            "origin, access-control-request-method, access-control-request-headers",
+        );
+        res
+    }
+}
+
+fn requested_headers(head: &RequestHead) -> Vec<String> {
+    head.headers()
+        .get_all(header::ACCESS_CONTROL_REQUEST_HEADERS)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Caches must not hand the response for one origin to another
+fn add_vary(headers: &mut HeaderMap, names: &'static str) {
+    headers.append(header::VARY, HeaderValue::from_static(names));
+}
+
+impl<S, B> Transform<S> for Cors
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CorsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(CorsMiddleware {
+            service,
+            cors: Arc::new(self.clone()),
+        })
+    }
+}
+
+pub struct CorsMiddleware<S> {
+    service: S,
+    cors: Arc<Cors>,
+}
+
+impl<S, B> Service for CorsMiddleware<S>
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let origin = match req.headers().get(header::ORIGIN) {
+            Some(origin) => origin.clone(),
+            // not a cross-origin request
+            None => {
+                let cors = self.cors.clone();
+                let fut = self.service.call(req);
+                return Box::pin(async move {
+                    let mut res = fut.await?;
+                    if !cors.any_origin {
+                        add_vary(res.headers_mut(), "origin");
+                    }
+                    Ok(res)
+                });
+            }
+        };
+
+        let allowed = matches!(
+            origin.to_str(),
+            Ok(origin) if self.cors.allows_origin(origin, req.head())
+        );
+        let is_preflight = req.method() == Method::OPTIONS
+            && req
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+        if !allowed
+            || (is_preflight && !self.cors.allows_preflight(req.head()))
+        {
+            // reject without calling the next service
+            let mut res =
+                HttpResponse::Forbidden().body("CORS request denied");
+            add_vary(res.headers_mut(), "origin");
+            return Box::pin(ok(req.into_response(res.into_body())));
+        }
+
+        if is_preflight {
+            let res = self.cors.preflight(req.head(), &origin);
+            return Box::pin(ok(req.into_response(res.into_body())));
+        }
+
+        let cors = self.cors.clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            let headers = res.headers_mut();
+            cors.set_headers(headers, &origin);
+            if !cors.expose_headers.is_empty() {
+                let names: Vec<&str> = cors
+                    .expose_headers
+                    .iter()
+                    .map(HeaderName::as_str)
+                    .collect();
+                headers.insert(
+                    header::ACCESS_CONTROL_EXPOSE_HEADERS,
+                    HeaderValue::from_str(&names.join(", ")).unwrap(),
+                );
+            }
+            if !cors.any_origin {
+                add_vary(headers, "origin");
+            }
+            Ok(res)
+        })
+    }
+}
+// </cors>
+
+// <cors-scopes>
+use actix_web::{web, App, HttpServer};
+
+async fn products() -> HttpResponse {
+    HttpResponse::Ok().json(vec!["coffee", "tea"])
+}
+
+async fn cart() -> HttpResponse {
+    HttpResponse::Ok().json(Vec::<String>::new())
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    HttpServer::new(|| {
+        App::new()
+            // a public, read-only API that any site may use
+            .service(
+                web::scope("/catalog")
+                    .wrap(
+                        Cors::new()
+                            .allow_origin("*")
+                            .allow_methods(&[Method::GET])
+                            .max_age(86400),
+                    )
+                    .route("/products", web::get().to(products)),
+            )
+            // only our own sites, with the session cookie
+            .service(
+                web::scope("/account")
+                    .wrap(
+                        Cors::new()
+                            .allow_origin("https://shop.example.com")
+                            .allow_origin("https://*.shop.example.com")
+                            .allow_origin_fn(|origin, _| {
+                                origin.starts_with("http://localhost:")
+                            })
+                            .allow_methods(&[Method::GET, Method::PUT])
+                            .allow_headers(&[header::CONTENT_TYPE])
+                            .expose_headers(&[header::ETAG])
+                            .allow_credentials()
+                            .max_age(600),
+                    )
+                    .route("/cart", web::get().to(cart))
+                    .route("/cart", web::put().to(cart)),
+            )
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await
+}
+// </cors-scopes>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test};
+
+    fn header(res: &ServiceResponse, name: HeaderName) -> Option<&str> {
+        res.headers().get(name).map(|value| value.to_str().unwrap())
+    }
+
+    fn vary(res: &ServiceResponse) -> String {
+        let values: Vec<_> = res
+            .headers()
+            .get_all(header::VARY)
+            .map(|value| value.to_str().unwrap())
+            .collect();
+        values.join(", ")
+    }
+
+    fn app() -> App<
+        impl actix_service::ServiceFactory<
+            Config = (),
+            Request = ServiceRequest,
+            Response = ServiceResponse,
+            Error = Error,
+            InitError = (),
+        >,
+        actix_web::body::Body,
+    > {
+        App::new()
+            .service(
+                web::scope("/catalog")
+                    .wrap(Cors::new().allow_origin("*"))
+                    .route("/products", web::get().to(products)),
+            )
+            .service(
+                web::scope("/account")
+                    .wrap(
+                        Cors::new()
+                            .allow_origin("https://shop.example.com")
+                            .allow_origin("https://*.shop.example.com")
+                            .allow_origin_fn(|origin, head| {
+                                origin == "https://partner.example.org"
+                                    && head.headers().contains_key("x-partner")
+                            })
+                            .allow_methods(&[Method::GET, Method::PUT])
+                            .allow_headers(&[header::CONTENT_TYPE])
+                            .expose_headers(&[header::ETAG])
+                            .allow_credentials()
+                            .max_age(600),
+                    )
+                    .route("/cart", web::get().to(cart))
+                    .route("/cart", web::put().to(cart)),
+            )
+    }
+
+    #[actix_rt::test]
+    async fn test_simple() {
+        let mut app = test::init_service(app()).await;
+
+        let req = test::TestRequest::get()
+            .uri("/catalog/products")
+            .header(header::ORIGIN, "https://anyone.example.net")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            header(&res, header::ACCESS_CONTROL_ALLOW_ORIGIN),
+            Some("*")
+        );
+        assert_eq!(
+            header(&res, header::ACCESS_CONTROL_ALLOW_CREDENTIALS),
+            None
+        );
+        assert_eq!(vary(&res), "");
+
+        for origin in &[
+            "https://shop.example.com",
+            "https://eu.shop.example.com",
+            "https://a.b.shop.example.com",
+        ] {
+            let req = test::TestRequest::get()
+                .uri("/account/cart")
+                .header(header::ORIGIN, *origin)
+                .to_request();
+            let res = test::call_service(&mut app, req).await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(
+                header(&res, header::ACCESS_CONTROL_ALLOW_ORIGIN),
+                Some(*origin)
+            );
+            assert_eq!(
+                header(&res, header::ACCESS_CONTROL_ALLOW_CREDENTIALS),
+                Some("true")
+            );
+            assert_eq!(
+                header(&res, header::ACCESS_CONTROL_EXPOSE_HEADERS),
+                Some("etag")
+            );
+            assert_eq!(vary(&res), "origin");
+        }
+
+        let req = test::TestRequest::get()
+            .uri("/account/cart")
+            .header(header::ORIGIN, "https://partner.example.org")
+            .header("x-partner", "1")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // same-origin requests without the header are left alone
+        let req = test::TestRequest::get().uri("/account/cart").to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(header(&res, header::ACCESS_CONTROL_ALLOW_ORIGIN), None);
+        assert_eq!(vary(&res), "origin");
+    }
+
+    #[actix_rt::test]
+    async fn test_preflight() {
+        let mut app = test::init_service(app()).await;
+
+        // no route handles OPTIONS, the middleware answers
+        let req = test::TestRequest::with_uri("/account/cart")
+            .method(Method::OPTIONS)
+            .header(header::ORIGIN, "https://eu.shop.example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "Content-Type")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            header(&res, header::ACCESS_CONTROL_ALLOW_ORIGIN),
+            Some("https://eu.shop.example.com")
+        );
+        assert_eq!(
+            header(&res, header::ACCESS_CONTROL_ALLOW_METHODS),
+            Some("GET, PUT")
+        );
+        assert_eq!(
+            header(&res, header::ACCESS_CONTROL_ALLOW_HEADERS),
+            Some("content-type")
+        );
+        assert_eq!(header(&res, header::ACCESS_CONTROL_MAX_AGE), Some("600"));
+        assert_eq!(
+            header(&res, header::ACCESS_CONTROL_ALLOW_CREDENTIALS),
+            Some("true")
+        );
+        assert!(vary(&res).contains("access-control-request-method"));
+
+        // other scopes have their own configuration
+        let req = test::TestRequest::with_uri("/catalog/products")
+            .method(Method::OPTIONS)
+            .header(header::ORIGIN, "https://anyone.example.net")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            header(&res, header::ACCESS_CONTROL_ALLOW_ORIGIN),
+            Some("*")
+        );
+        assert_eq!(header(&res, header::ACCESS_CONTROL_MAX_AGE), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_rejected() {
+        let mut app = test::init_service(app()).await;
+
+        let rejected = vec![
+            // unknown origins, the wildcard does not match the bare domain
+            // or a path
+            test::TestRequest::get()
+                .uri("/account/cart")
+                .header(header::ORIGIN, "https://evil.example.com"),
+            test::TestRequest::get()
+                .uri("/account/cart")
+                .header(header::ORIGIN, "https://.shop.example.com"),
+            test::TestRequest::get()
+                .uri("/account/cart")
+                .header(header::ORIGIN, "https://evil.com/.shop.example.com"),
+            // the predicate wants its header
+            test::TestRequest::get()
+                .uri("/account/cart")
+                .header(header::ORIGIN, "https://partner.example.org"),
+            // a method that is not allowed
+            test::TestRequest::with_uri("/account/cart")
+                .method(Method::OPTIONS)
+                .header(header::ORIGIN, "https://shop.example.com")
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "DELETE"),
+            // a header that is not allowed
+            test::TestRequest::with_uri("/account/cart")
+                .method(Method::OPTIONS)
+                .header(header::ORIGIN, "https://shop.example.com")
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+                .header(
+                    header::ACCESS_CONTROL_REQUEST_HEADERS,
+                    "content-type, x-admin",
+                ),
+        ];
+
+        for req in rejected {
+            let res = test::call_service(&mut app, req.to_request()).await;
+            assert_eq!(res.status(), StatusCode::FORBIDDEN);
+            assert_eq!(
+                header(&res, header::ACCESS_CONTROL_ALLOW_ORIGIN),
+                None
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "credentials cannot be allowed for every origin"
+    )]
+    fn test_any_origin_with_credentials() {
+        Cors::new().allow_origin("*").allow_credentials();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "credentials cannot be allowed for every origin"
+    )]
+    fn test_credentials_with_any_origin() {
+        Cors::new().allow_credentials().allow_origin("*");
+    }
+}
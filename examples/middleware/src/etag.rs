@@ -0,0 +1,656 @@
+#![allow(dead_code)]
+
+// <conditional>
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use actix_service::{Service, Transform};
+use actix_web::body::{Body, BodyStream, MessageBody, ResponseBody};
+use actix_web::http::header::{
+    self, EntityTag, Header, HttpDate, IfMatch, IfModifiedSince, IfNoneMatch,
+};
+use actix_web::http::{HeaderMap, HeaderValue, Method, StatusCode};
+use actix_web::web::{Bytes, BytesMut};
+use actix_web::{
+    dev::ServiceRequest, dev::ServiceResponse, Error, HttpMessage,
+    HttpResponse,
+};
+use futures::future::{ok, Ready};
+use futures::stream::{self, StreamExt};
+use futures::Future;
+use sha2::{Digest, Sha256};
+
+type CurrentTag = dyn Fn(&ServiceRequest) -> Option<EntityTag> + Send + Sync;
+
+/// Answers conditional requests.
+///
+/// Successful `GET` and `HEAD` responses get an `ETag` computed over their
+/// body, unless the handler already set one. `If-None-Match` and, with a
+/// `Last-Modified` header from the handler, `If-Modified-Since` turn them
+/// into `304 Not Modified`.
+///
+/// Unsafe methods are only checked against the tag from `current`. Without
+/// it, their `If-Match` and `If-None-Match` headers cannot be evaluated, so
+/// they fail with `412 Precondition Failed` and the handler never runs.
+///
+/// Register it after `Compress`, so that it wraps the compressed body and
+/// every encoding gets its own tag. Tags from `current` or from the handler
+/// are the same for every encoding, so do not compress the responses that
+/// carry them.
+#[derive(Clone)]
+pub struct Conditional {
+    weak: bool,
+    max_size: usize,
+    current: Option<Arc<CurrentTag>>,
+}
+
+impl Default for Conditional {
+    fn default() -> Self {
+        Conditional {
+            weak: false,
+            max_size: 1024 * 1024,
+            current: None,
+        }
+    }
+}
+
+impl Conditional {
+    pub fn new() -> Self {
+        Conditional::default()
+    }
+
+    /// Marks generated tags as weak, for bodies that may differ in
+    /// insignificant details, like the order of keys in a JSON object.
+    pub fn weak(mut self) -> Self {
+        self.weak = true;
+        self
+    }
+
+    /// Bodies larger than this are sent without a generated tag.
+    pub fn max_size(mut self, bytes: usize) -> Self {
+        self.max_size = bytes;
+        self
+    }
+
+    /// Looks up the tag of the current state of the resource before the
+    /// handler runs, `None` if the resource does not exist.
+    ///
+    /// Unsafe methods need this for `If-Match`, as their response comes too
+    /// late to prevent a lost update. Requests whose preconditions fail are
+    /// answered without calling the handler.
+    pub fn current<F>(mut self, current: F) -> Self
+    where
+        F: Fn(&ServiceRequest) -> Option<EntityTag> + Send + Sync + 'static,
+    {
+        self.current = Some(Arc::new(current));
+        self
+    }
+
+    fn generate(&self, body: &[u8]) -> EntityTag {
+        let digest = Sha256::digest(body);
+        EntityTag::new(self.weak, hex::encode(&digest[..16]))
+    }
+}
+
+enum Outcome {
+    Proceed,
+    NotModified,
+    Failed,
+}
+
+fn is_safe(method: &Method) -> bool {
+    method == Method::GET || method == Method::HEAD
+}
+
+/// Evaluates the preconditions in the order of RFC 7232, section 6.
+fn evaluate<T: HttpMessage>(
+    req: &T,
+    method: &Method,
+    tag: Option<&EntityTag>,
+    last_modified: Option<HttpDate>,
+) -> Outcome {
+    let headers = req.headers();
+
+    if headers.contains_key(header::IF_MATCH) {
+        // strong comparison, a weak tag never matches
+        let matched = match IfMatch::parse(req) {
+            Ok(IfMatch::Any) => tag.is_some(),
+            Ok(IfMatch::Items(items)) => match tag {
+                Some(tag) => items.iter().any(|item| item.strong_eq(tag)),
+                None => false,
+            },
+            Err(_) => false,
+        };
+        if !matched {
+            return Outcome::Failed;
+        }
+    }
+
+    if headers.contains_key(header::IF_NONE_MATCH) {
+        let matched = match IfNoneMatch::parse(req) {
+            Ok(IfNoneMatch::Any) => tag.is_some(),
+            Ok(IfNoneMatch::Items(items)) => match tag {
+                Some(tag) => items.iter().any(|item| item.weak_eq(tag)),
+                None => false,
+            },
+            Err(_) => false,
+        };
+        return match (matched, is_safe(method)) {
+            (false, _) => Outcome::Proceed,
+            (true, true) => Outcome::NotModified,
+            (true, false) => Outcome::Failed,
+        };
+    }
+
+    if is_safe(method) {
+        if let (Ok(IfModifiedSince(since)), Some(modified)) =
+            (IfModifiedSince::parse(req), last_modified)
+        {
+            if modified <= since {
+                return Outcome::NotModified;
+            }
+        }
+    }
+
+    Outcome::Proceed
+}
+
+fn not_modified(headers: &HeaderMap) -> HttpResponse {
+    let mut res = HttpResponse::NotModified();
+    // the headers a 200 response would have sent, except the content ones
+    for name in &[
+        header::CACHE_CONTROL,
+        header::CONTENT_LOCATION,
+        header::DATE,
+        header::ETAG,
+        header::EXPIRES,
+        header::LAST_MODIFIED,
+        header::VARY,
+    ] {
+        for value in headers.get_all(name) {
+            res.header(name.clone(), value.clone());
+        }
+    }
+    res.finish()
+}
+
+fn set_tag(headers: &mut HeaderMap, tag: &EntityTag) {
+    // the tag is made of hex digits or comes from a parsed header
+    let value = HeaderValue::from_str(&tag.to_string()).unwrap();
+    headers.insert(header::ETAG, value);
+}
+
+fn header_value<T: std::str::FromStr>(
+    headers: &HeaderMap,
+    name: header::HeaderName,
+) -> Option<T> {
+    headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+pub enum Buffered {
+    Complete(Bytes),
+    TooLarge(Body),
+}
+
+/// Reads a body into memory, unless it is larger than `max_size`.
+pub async fn buffer<B>(
+    mut body: ResponseBody<B>,
+    max_size: usize,
+) -> Result<Buffered, Error>
+where
+    B: MessageBody + Unpin + 'static,
+{
+    let mut buf = BytesMut::new();
+    while let Some(chunk) = body.next().await {
+        buf.extend_from_slice(&chunk?);
+
+        if buf.len() > max_size {
+            // send what was read so far, followed by the rest
+            let read = stream::once(ok::<_, Error>(buf.freeze()));
+            let body = BodyStream::new(read.chain(body));
+            return Ok(Buffered::TooLarge(Body::from_message(body)));
+        }
+    }
+    Ok(Buffered::Complete(buf.freeze()))
+}
+
+impl<S, B> Transform<S> for Conditional
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: MessageBody + Unpin + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ConditionalMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(ConditionalMiddleware {
+            service,
+            config: self.clone(),
+        })
+    }
+}
+
+pub struct ConditionalMiddleware<S> {
+    service: S,
+    config: Conditional,
+}
+
+impl<S, B> Service for ConditionalMiddleware<S>
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: MessageBody + Unpin + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let method = req.method().clone();
+
+        let current =
+            self.config.current.as_ref().map(|current| current(&req));
+        let has_preconditions = {
+            let headers = req.headers();
+            headers.contains_key(header::IF_MATCH)
+                || headers.contains_key(header::IF_NONE_MATCH)
+        };
+        if current.is_none() && !is_safe(&method) && has_preconditions {
+            // without `current` the state before the change is unknown
+            let res = HttpResponse::PreconditionFailed().finish();
+            return Box::pin(ok(req.into_response(res)));
+        }
+        if let Some(ref tag) = current {
+            match evaluate(&req, &method, tag.as_ref(), None) {
+                Outcome::Proceed => {}
+                Outcome::NotModified => {
+                    let mut headers = HeaderMap::new();
+                    set_tag(&mut headers, tag.as_ref().unwrap());
+                    let res = not_modified(&headers);
+                    return Box::pin(ok(req.into_response(res)));
+                }
+                Outcome::Failed => {
+                    let res = HttpResponse::PreconditionFailed().finish();
+                    return Box::pin(ok(req.into_response(res)));
+                }
+            }
+        }
+
+        let config = self.config.clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            let body = res.take_body();
+
+            if !is_safe(&method) || res.status() != StatusCode::OK {
+                return Ok(res.map_body(|_, _| {
+                    ResponseBody::Other(Body::from_message(body))
+                }));
+            }
+
+            // a tag from the handler wins over the looked up or generated one
+            let (tag, body) = match header_value(res.headers(), header::ETAG)
+                .or_else(|| current.flatten())
+            {
+                Some(tag) => (tag, Body::from_message(body)),
+                None => match buffer(body, config.max_size).await? {
+                    Buffered::Complete(bytes) => {
+                        (config.generate(&bytes), Body::from(bytes))
+                    }
+                    Buffered::TooLarge(body) => {
+                        return Ok(
+                            res.map_body(|_, _| ResponseBody::Other(body))
+                        )
+                    }
+                },
+            };
+
+            let headers = res.headers_mut();
+            set_tag(headers, &tag);
+            if headers.contains_key(header::CONTENT_ENCODING) {
+                // the tag belongs to this encoding only
+                let varies = headers.get_all(header::VARY).any(|value| {
+                    match value.to_str() {
+                        Ok(value) => value
+                            .to_ascii_lowercase()
+                            .contains("accept-encoding"),
+                        Err(_) => false,
+                    }
+                });
+                if !varies {
+                    headers.append(
+                        header::VARY,
+                        HeaderValue::from_static("accept-encoding"),
+                    );
+                }
+            }
+
+            let last_modified =
+                header_value(res.headers(), header::LAST_MODIFIED);
+            match evaluate(res.request(), &method, Some(&tag), last_modified) {
+                Outcome::Proceed => {
+                    Ok(res.map_body(|_, _| ResponseBody::Other(body)))
+                }
+                Outcome::NotModified => {
+                    let not_modified = not_modified(res.headers());
+                    Ok(res.into_response(not_modified))
+                }
+                Outcome::Failed => {
+                    let failed = HttpResponse::PreconditionFailed().finish();
+                    Ok(res.into_response(failed))
+                }
+            }
+        })
+    }
+}
+// </conditional>
+
+// <conditional-usage>
+use std::sync::Mutex;
+
+use actix_web::{web, App, HttpServer};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Deserialize, Serialize)]
+struct Document {
+    version: u64,
+    text: String,
+}
+
+/// The version is known without reading the whole document
+fn document_tag(req: &ServiceRequest) -> Option<EntityTag> {
+    let document = req.app_data::<web::Data<Mutex<Document>>>()?;
+    let version = document.lock().unwrap().version;
+    Some(EntityTag::strong(format!("v{}", version)))
+}
+
+async fn get_document(document: web::Data<Mutex<Document>>) -> HttpResponse {
+    HttpResponse::Ok().json(&*document.lock().unwrap())
+}
+
+/// Only called when the `If-Match` header names the current version
+async fn put_document(
+    document: web::Data<Mutex<Document>>,
+    text: String,
+) -> HttpResponse {
+    let mut document = document.lock().unwrap();
+    document.version += 1;
+    document.text = text;
+    HttpResponse::Ok().json(&*document)
+}
+
+async fn temperature() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "celsius": 21.5 }))
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let document = web::Data::new(Mutex::new(Document {
+        version: 1,
+        text: String::from("Hello"),
+    }));
+
+    HttpServer::new(move || {
+        App::new()
+            .app_data(document.clone())
+            .wrap(Conditional::new())
+            .route("/temperature", web::get().to(temperature))
+            .service(
+                web::resource("/document")
+                    .wrap(Conditional::new().current(document_tag))
+                    .route(web::get().to(get_document))
+                    .route(web::put().to(put_document)),
+            )
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await
+}
+// </conditional-usage>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{middleware::Compress, test};
+
+    fn etag(res: &ServiceResponse) -> String {
+        let etag = res.headers().get(header::ETAG).unwrap();
+        etag.to_str().unwrap().to_owned()
+    }
+
+    #[actix_rt::test]
+    async fn test_if_none_match() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(Conditional::new())
+                .route("/temperature", web::get().to(temperature)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/temperature").to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let tag = etag(&res);
+        assert!(tag.starts_with('"'));
+
+        let req = test::TestRequest::get()
+            .uri("/temperature")
+            .header(header::IF_NONE_MATCH, tag.as_str())
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(etag(&res), tag);
+        assert!(test::read_body(res).await.is_empty());
+
+        // If-None-Match uses the weak comparison
+        let req = test::TestRequest::get()
+            .uri("/temperature")
+            .header(header::IF_NONE_MATCH, format!("\"other\", W/{}", tag))
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+        let req = test::TestRequest::get()
+            .uri("/temperature")
+            .header(header::IF_NONE_MATCH, "\"other\"")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(!test::read_body(res).await.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn test_if_modified_since() {
+        let modified = "Wed, 21 Oct 2015 07:28:00 GMT";
+        let mut app = test::init_service(
+            App::new().wrap(Conditional::new().weak()).route(
+                "/",
+                web::get().to(move || {
+                    HttpResponse::Ok()
+                        .header(header::LAST_MODIFIED, modified)
+                        .body("static")
+                }),
+            ),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .header(header::IF_MODIFIED_SINCE, modified)
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert!(etag(&res).starts_with("W/"));
+        assert_eq!(
+            res.headers().get(header::LAST_MODIFIED).unwrap(),
+            modified
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .header(header::IF_MODIFIED_SINCE, "Tue, 20 Oct 2015 07:28:00 GMT")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_if_match() {
+        let document = web::Data::new(Mutex::new(Document {
+            version: 1,
+            text: String::from("Hello"),
+        }));
+        let mut app = test::init_service(
+            App::new().app_data(document.clone()).service(
+                web::resource("/document")
+                    .wrap(Conditional::new().current(document_tag))
+                    .route(web::get().to(get_document))
+                    .route(web::put().to(put_document)),
+            ),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/document").to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(etag(&res), "\"v1\"");
+
+        let req = test::TestRequest::put()
+            .uri("/document")
+            .header(header::IF_MATCH, "\"v1\"")
+            .set_payload("Hello, world")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(document.lock().unwrap().version, 2);
+
+        // a second writer with the old version is turned away
+        let req = test::TestRequest::put()
+            .uri("/document")
+            .header(header::IF_MATCH, "\"v1\"")
+            .set_payload("Bye")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(document.lock().unwrap().text, "Hello, world");
+
+        // the strong comparison never matches a weak tag
+        let req = test::TestRequest::put()
+            .uri("/document")
+            .header(header::IF_MATCH, "W/\"v2\"")
+            .set_payload("Bye")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+
+        let req = test::TestRequest::get()
+            .uri("/document")
+            .header(header::IF_NONE_MATCH, "\"v2\"")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[actix_rt::test]
+    async fn test_unsafe_without_current() {
+        let document = web::Data::new(Mutex::new(Document {
+            version: 1,
+            text: String::from("Hello"),
+        }));
+        let mut app = test::init_service(
+            App::new().app_data(document.clone()).service(
+                web::resource("/document")
+                    .wrap(Conditional::new())
+                    .route(web::put().to(put_document)),
+            ),
+        )
+        .await;
+
+        for (name, value) in
+            &[(header::IF_MATCH, "\"v1\""), (header::IF_NONE_MATCH, "*")]
+        {
+            let req = test::TestRequest::put()
+                .uri("/document")
+                .header(name.clone(), *value)
+                .set_payload("Bye")
+                .to_request();
+            let res = test::call_service(&mut app, req).await;
+            assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+        }
+        assert_eq!(document.lock().unwrap().version, 1);
+
+        // unconditional changes still go through
+        let req = test::TestRequest::put()
+            .uri("/document")
+            .set_payload("Bye")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(document.lock().unwrap().version, 2);
+    }
+
+    #[actix_rt::test]
+    async fn test_compress() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(Compress::default())
+                .wrap(Conditional::new())
+                .route("/temperature", web::get().to(temperature)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/temperature").to_request();
+        let res = test::call_service(&mut app, req).await;
+        let identity = etag(&res);
+        assert!(res.headers().get(header::VARY).is_none());
+
+        let req = test::TestRequest::get()
+            .uri("/temperature")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(
+            res.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "gzip"
+        );
+        assert_eq!(
+            res.headers().get(header::VARY).unwrap(),
+            "accept-encoding"
+        );
+        let gzip = etag(&res);
+        assert_ne!(gzip, identity);
+
+        let req = test::TestRequest::get()
+            .uri("/temperature")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .header(header::IF_NONE_MATCH, gzip.as_str())
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert!(res.headers().get(header::CONTENT_ENCODING).is_none());
+        assert!(test::read_body(res).await.is_empty());
+    }
+}
@@ -1,6 +1,13 @@
+pub mod cache;
+pub mod cors;
 pub mod default_headers;
 pub mod errorhandler;
+pub mod etag;
 pub mod logger;
+pub mod metrics;
+pub mod rate_limit;
+pub mod security_headers;
+pub mod trace_context;
 pub mod user_sessions;
 pub mod wrap_fn;
 
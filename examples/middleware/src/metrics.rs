@@ -0,0 +1,441 @@
+#![allow(dead_code)]
+
+// <metrics>
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use actix_service::{Service, Transform};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{
+    dev::ServiceRequest, dev::ServiceResponse, web, Error, HttpResponse,
+};
+use futures::future::{ok, Ready};
+use futures::Future;
+
+/// Upper bounds of the latency buckets in seconds, the defaults of the
+/// Prometheus client libraries
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Requests that no route matched share one label, so that scanners probing
+/// random paths cannot create new time series.
+const UNMATCHED: &str = "<unmatched>";
+
+#[derive(Default)]
+struct Histogram {
+    // not cumulative, the last one counts what is above all bounds
+    buckets: [u64; BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        let bucket = BUCKETS
+            .iter()
+            .position(|&le| secs <= le)
+            .unwrap_or(BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += secs;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    /// By pattern, method and status class
+    requests: BTreeMap<(String, String, &'static str), Histogram>,
+    /// By pattern and method
+    in_flight: BTreeMap<(String, String), i64>,
+}
+
+/// Counts requests and their latency by matched route pattern, method and
+/// status class, and the requests being handled right now.
+///
+/// Clones share their numbers, so one `Metrics` can wrap several scopes and
+/// be handed to the `/metrics` endpoint.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    /// The numbers in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let registry = self.registry.lock().unwrap();
+        let mut out = String::new();
+
+        out.push_str(
+            "# HELP http_requests_total Requests handled, by route pattern, \
+             method and status class.\n\
+             # TYPE http_requests_total counter\n",
+        );
+        for ((pattern, method, status), histogram) in &registry.requests {
+            let labels = labels(&[
+                ("pattern", pattern),
+                ("method", method),
+                ("status", status),
+            ]);
+            writeln!(
+                out,
+                "http_requests_total{{{}}} {}",
+                labels, histogram.count
+            )
+            .unwrap();
+        }
+
+        out.push_str(
+            "# HELP http_request_duration_seconds Time until the response \
+             was ready.\n\
+             # TYPE http_request_duration_seconds histogram\n",
+        );
+        for ((pattern, method, status), histogram) in &registry.requests {
+            let labels = labels(&[
+                ("pattern", pattern),
+                ("method", method),
+                ("status", status),
+            ]);
+            let mut cumulative = 0;
+            let bounds = BUCKETS.iter().map(|le| le.to_string());
+            for (le, count) in bounds
+                .chain(Some(String::from("+Inf")))
+                .zip(histogram.buckets.iter())
+            {
+                cumulative += count;
+                writeln!(
+                    out,
+                    "http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, le, cumulative
+                )
+                .unwrap();
+            }
+            writeln!(
+                out,
+                "http_request_duration_seconds_sum{{{}}} {}\n\
+                 http_request_duration_seconds_count{{{}}} {}",
+                labels, histogram.sum, labels, histogram.count
+            )
+            .unwrap();
+        }
+
+        out.push_str(
+            "# HELP http_requests_in_flight Requests being handled.\n\
+             # TYPE http_requests_in_flight gauge\n",
+        );
+        for ((pattern, method), count) in &registry.in_flight {
+            let labels = labels(&[("pattern", pattern), ("method", method)]);
+            writeln!(out, "http_requests_in_flight{{{}}} {}", labels, count)
+                .unwrap();
+        }
+
+        out
+    }
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let pairs: Vec<_> = pairs
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+    pairs.join(",")
+}
+
+/// Extension methods share one label for the same reason as unmatched paths
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::CONNECT => "CONNECT",
+        Method::OPTIONS => "OPTIONS",
+        Method::TRACE => "TRACE",
+        Method::PATCH => "PATCH",
+        _ => "other",
+    }
+}
+
+fn status_class(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    }
+}
+
+/// Serves the numbers of the `web::Data<Metrics>` registered with the app
+pub async fn metrics_endpoint(metrics: web::Data<Metrics>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(metrics.render())
+}
+
+/// Counts a request as in flight until it is dropped, even when the client
+/// goes away before the response is ready.
+struct InFlight {
+    registry: Arc<Mutex<Registry>>,
+    key: (String, String),
+}
+
+impl InFlight {
+    fn new(registry: &Arc<Mutex<Registry>>, key: (String, String)) -> Self {
+        *registry
+            .lock()
+            .unwrap()
+            .in_flight
+            .entry(key.clone())
+            .or_insert(0) += 1;
+        InFlight {
+            registry: registry.clone(),
+            key,
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut registry = self.registry.lock().unwrap();
+        if let Some(count) = registry.in_flight.get_mut(&self.key) {
+            *count -= 1;
+        }
+    }
+}
+
+impl<S, B> Transform<S> for Metrics
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = MetricsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(MetricsMiddleware {
+            service,
+            metrics: self.clone(),
+        })
+    }
+}
+
+pub struct MetricsMiddleware<S> {
+    service: S,
+    metrics: Metrics,
+}
+
+impl<S, B> Service for MetricsMiddleware<S>
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        // the routes are known before routing, the path is matched again
+        let pattern = req
+            .match_pattern()
+            .unwrap_or_else(|| String::from(UNMATCHED));
+        let method = String::from(method_label(req.method()));
+
+        let registry = self.metrics.registry.clone();
+        let in_flight = InFlight::new(&registry, (pattern, method));
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await;
+            let status = match res {
+                Ok(ref res) => res.status(),
+                Err(ref err) => err.as_response_error().status_code(),
+            };
+
+            let (pattern, method) = in_flight.key.clone();
+            let key = (pattern, method, status_class(status));
+            registry
+                .lock()
+                .unwrap()
+                .requests
+                .entry(key)
+                .or_default()
+                .observe(start.elapsed().as_secs_f64());
+            drop(in_flight);
+
+            res
+        })
+    }
+}
+// </metrics>
+
+// <metrics-usage>
+use actix_web::{App, HttpServer};
+
+async fn user(id: web::Path<u32>) -> HttpResponse {
+    HttpResponse::Ok().body(format!("user {}", id))
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    // one registry for all workers
+    let metrics = Metrics::new();
+
+    HttpServer::new(move || {
+        App::new()
+            .wrap(metrics.clone())
+            .data(metrics.clone())
+            .route("/metrics", web::get().to(metrics_endpoint))
+            .route("/users/{id}", web::get().to(user))
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await
+}
+// </metrics-usage>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+
+    async fn scrape(
+        app: &mut impl Service<
+            Request = actix_http::Request,
+            Response = ServiceResponse,
+            Error = Error,
+        >,
+    ) -> String {
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = test::read_body(test::call_service(app, req).await).await;
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn test_counters() {
+        let metrics = Metrics::new();
+        let mut app = test::init_service(
+            App::new()
+                .wrap(metrics.clone())
+                .data(metrics)
+                .route("/metrics", web::get().to(metrics_endpoint))
+                .route("/users/{id}", web::get().to(user))
+                .route(
+                    "/fail",
+                    web::get().to(HttpResponse::InternalServerError),
+                ),
+        )
+        .await;
+
+        for uri in &["/users/1", "/users/2", "/users/x", "/fail", "/nope"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            test::call_service(&mut app, req).await;
+        }
+        for method in &["PURGE", "PROPFIND"] {
+            let req = test::TestRequest::default()
+                .method(Method::from_bytes(method.as_bytes()).unwrap())
+                .uri("/fail")
+                .to_request();
+            test::call_service(&mut app, req).await;
+        }
+
+        let text = scrape(&mut app).await;
+        let lines: Vec<_> = text.lines().collect();
+        for line in &[
+            "# TYPE http_requests_total counter",
+            r#"http_requests_total{pattern="/users/{id}",method="GET",status="2xx"} 2"#,
+            r#"http_requests_total{pattern="/users/{id}",method="GET",status="4xx"} 1"#,
+            r#"http_requests_total{pattern="/fail",method="GET",status="5xx"} 1"#,
+            r#"http_requests_total{pattern="<unmatched>",method="GET",status="4xx"} 1"#,
+            r#"http_requests_total{pattern="/fail",method="other",status="4xx"} 2"#,
+            "# TYPE http_request_duration_seconds histogram",
+            r#"http_request_duration_seconds_bucket{pattern="/users/{id}",method="GET",status="2xx",le="+Inf"} 2"#,
+            r#"http_request_duration_seconds_count{pattern="/users/{id}",method="GET",status="2xx"} 2"#,
+            "# TYPE http_requests_in_flight gauge",
+            r#"http_requests_in_flight{pattern="/users/{id}",method="GET"} 0"#,
+            // the scrape itself
+            r#"http_requests_in_flight{pattern="/metrics",method="GET"} 1"#,
+        ] {
+            assert!(lines.contains(line), "missing `{}` in\n{}", line, text);
+        }
+
+        // buckets are cumulative
+        let buckets: Vec<u64> = lines
+            .iter()
+            .filter(|line| {
+                line.starts_with(
+                    r#"http_request_duration_seconds_bucket{pattern="/users/{id}",method="GET",status="2xx""#,
+                )
+            })
+            .map(|line| line.rsplit(' ').next().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(buckets.len(), BUCKETS.len() + 1);
+        assert!(buckets.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[actix_rt::test]
+    async fn test_in_flight() {
+        let metrics = Metrics::new();
+        let mut app = test::init_service(
+            App::new()
+                .wrap(metrics.clone())
+                .data(metrics)
+                .route("/metrics", web::get().to(metrics_endpoint))
+                .route("/upload", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        // in flight from the moment it reaches the middleware
+        let req = test::TestRequest::post().uri("/upload").to_request();
+        let upload = app.call(req);
+        let gauge =
+            r#"http_requests_in_flight{pattern="/upload",method="POST"}"#;
+        assert!(scrape(&mut app).await.contains(&format!("{} 1", gauge)));
+
+        upload.await.unwrap();
+        let text = scrape(&mut app).await;
+        assert!(text.contains(&format!("{} 0", gauge)));
+        assert!(text.contains(
+            r#"http_requests_total{pattern="/upload",method="POST",status="2xx"} 1"#
+        ));
+    }
+}
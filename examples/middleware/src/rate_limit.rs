@@ -0,0 +1,541 @@
+#![allow(dead_code)]
+
+// <rate-limit>
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use actix_service::{Service, Transform};
+use actix_web::http::{header, HeaderMap, HeaderName, HeaderValue};
+use actix_web::{
+    dev::ServiceRequest, dev::ServiceResponse, Error, HttpResponse,
+};
+use futures::future::{ok, Ready};
+use futures::Future;
+
+/// Source of the current time, so that tests can control how fast buckets refill.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+#[derive(Clone)]
+enum KeyBy {
+    PeerIp,
+    Header(HeaderName),
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Default)]
+struct Buckets {
+    clients: HashMap<String, Bucket>,
+    last_sweep: Option<Instant>,
+}
+
+struct Decision {
+    allowed: bool,
+    remaining: u32,
+    reset: Duration,
+    retry_after: Duration,
+}
+
+/// Token bucket rate limiter.
+///
+/// Every client may send `burst` requests at once, after which its bucket
+/// refills at `burst` requests per `period`. Each `RateLimiter` keeps its own
+/// buckets, so different scopes can be wrapped with different limits. Build
+/// the limiter outside of the `HttpServer::new` closure and clone it in to
+/// share the buckets between all workers.
+#[derive(Clone)]
+pub struct RateLimiter {
+    burst: u32,
+    period: Duration,
+    idle_timeout: Duration,
+    key_by: KeyBy,
+    clock: Arc<dyn Clock>,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    pub fn new(burst: u32, period: Duration) -> Self {
+        assert!(burst > 0, "burst must allow at least one request");
+
+        RateLimiter {
+            burst,
+            period,
+            // a bucket left alone for a whole period is full again, so
+            // forgetting it does not change any future decision
+            idle_timeout: period,
+            key_by: KeyBy::PeerIp,
+            clock: Arc::new(SystemClock),
+            buckets: Arc::new(Mutex::new(Buckets::default())),
+        }
+    }
+
+    /// Identify clients by the value of a request header (e.g. an API key)
+    /// instead of their IP address. Requests without the header fall back
+    /// to the IP address.
+    ///
+    /// Clients choose the header's value, so one that sends a new value with
+    /// every request is never limited. Only use this behind a proxy that
+    /// sets the header itself, e.g. after checking the API key.
+    pub fn key_by_header(mut self, name: &'static str) -> Self {
+        self.key_by = KeyBy::Header(HeaderName::from_static(name));
+        self
+    }
+
+    /// Forget buckets of clients that have been idle for longer than this.
+    /// Timeouts shorter than the period are raised to it, as forgetting a
+    /// bucket before it is full again would hand out a fresh burst.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout.max(self.period);
+        self
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    fn client_key(&self, req: &ServiceRequest) -> String {
+        if let KeyBy::Header(ref name) = self.key_by {
+            if let Some(value) = req.headers().get(name) {
+                return format!(
+                    "{}:{}",
+                    name,
+                    String::from_utf8_lossy(value.as_bytes())
+                );
+            }
+        }
+
+        match req.peer_addr() {
+            Some(addr) => addr.ip().to_string(),
+            None => String::from("unknown"),
+        }
+    }
+
+    fn acquire(&self, key: String) -> Decision {
+        let now = self.clock.now();
+        let burst = f64::from(self.burst);
+        let rate = burst / self.period.as_secs_f64();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        self.evict_idle(&mut buckets, now);
+
+        let bucket = buckets.clients.entry(key).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.updated);
+        bucket.tokens =
+            (bucket.tokens + elapsed.as_secs_f64() * rate).min(burst);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        Decision {
+            allowed,
+            remaining: bucket.tokens.floor() as u32,
+            reset: Duration::from_secs_f64((burst - bucket.tokens) / rate),
+            retry_after: Duration::from_secs_f64(
+                (1.0 - bucket.tokens).max(0.0) / rate,
+            ),
+        }
+    }
+
+    fn evict_idle(&self, buckets: &mut Buckets, now: Instant) {
+        let due = match buckets.last_sweep {
+            Some(last) => {
+                now.saturating_duration_since(last) >= self.idle_timeout
+            }
+            None => true,
+        };
+
+        if due {
+            let idle_timeout = self.idle_timeout;
+            buckets.clients.retain(|_, bucket| {
+                now.saturating_duration_since(bucket.updated) < idle_timeout
+            });
+            buckets.last_sweep = Some(now);
+        }
+    }
+
+    fn set_headers(&self, headers: &mut HeaderMap, decision: &Decision) {
+        headers.insert(
+            HeaderName::from_static("x-ratelimit-limit"),
+            HeaderValue::from(self.burst),
+        );
+        headers.insert(
+            HeaderName::from_static("x-ratelimit-remaining"),
+            HeaderValue::from(decision.remaining),
+        );
+        headers.insert(
+            HeaderName::from_static("x-ratelimit-reset"),
+            HeaderValue::from(ceil_secs(decision.reset)),
+        );
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+impl<S, B> Transform<S> for RateLimiter
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimiterMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RateLimiterMiddleware {
+            service,
+            limiter: self.clone(),
+        })
+    }
+}
+
+pub struct RateLimiterMiddleware<S> {
+    service: S,
+    limiter: RateLimiter,
+}
+
+impl<S, B> Service for RateLimiterMiddleware<S>
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let decision = self.limiter.acquire(self.limiter.client_key(&req));
+
+        if !decision.allowed {
+            // reject without calling the next service
+            let mut res =
+                HttpResponse::TooManyRequests().body("Too many requests");
+            self.limiter.set_headers(res.headers_mut(), &decision);
+            res.headers_mut().insert(
+                header::RETRY_AFTER,
+                HeaderValue::from(ceil_secs(decision.retry_after)),
+            );
+
+            return Box::pin(ok(req.into_response(res.into_body())));
+        }
+
+        let limiter = self.limiter.clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            limiter.set_headers(res.headers_mut(), &decision);
+            Ok(res)
+        })
+    }
+}
+// </rate-limit>
+
+// <rate-limit-scopes>
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    use actix_web::{web, App, HttpServer};
+
+    // created once so that all workers share the same buckets
+    let api_limiter = RateLimiter::new(10, Duration::from_secs(60))
+        .key_by_header("x-api-key");
+    let site_limiter = RateLimiter::new(100, Duration::from_secs(60));
+
+    HttpServer::new(move || {
+        App::new()
+            .service(
+                web::scope("/api")
+                    .wrap(api_limiter.clone())
+                    .route("/status", web::get().to(HttpResponse::Ok)),
+            )
+            .service(
+                web::scope("")
+                    .wrap(site_limiter.clone())
+                    .route("/", web::get().to(HttpResponse::Ok)),
+            )
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await
+}
+// </rate-limit-scopes>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, web, App};
+
+    struct ManualClock(Mutex<Instant>);
+
+    impl ManualClock {
+        fn new() -> Arc<Self> {
+            Arc::new(ManualClock(Mutex::new(Instant::now())))
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.0.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn from_ip(ip: &str) -> test::TestRequest {
+        test::TestRequest::get()
+            .uri("/")
+            .peer_addr(format!("{}:4711", ip).parse().unwrap())
+    }
+
+    fn header<'a>(res: &'a ServiceResponse, name: &str) -> &'a str {
+        res.headers().get(name).unwrap().to_str().unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn test_burst_then_refill() {
+        let clock = ManualClock::new();
+        let limiter =
+            RateLimiter::new(2, Duration::from_secs(10)).clock(clock.clone());
+        let mut app = test::init_service(
+            App::new()
+                .wrap(limiter)
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let res =
+            test::call_service(&mut app, from_ip("10.0.0.1").to_request())
+                .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(header(&res, "x-ratelimit-limit"), "2");
+        assert_eq!(header(&res, "x-ratelimit-remaining"), "1");
+        assert_eq!(header(&res, "x-ratelimit-reset"), "5");
+
+        let res =
+            test::call_service(&mut app, from_ip("10.0.0.1").to_request())
+                .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(header(&res, "x-ratelimit-remaining"), "0");
+
+        let res =
+            test::call_service(&mut app, from_ip("10.0.0.1").to_request())
+                .await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(header(&res, "retry-after"), "5");
+        assert_eq!(header(&res, "x-ratelimit-reset"), "10");
+
+        // one token is back after half of the period
+        clock.advance(Duration::from_secs(5));
+        let res =
+            test::call_service(&mut app, from_ip("10.0.0.1").to_request())
+                .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res =
+            test::call_service(&mut app, from_ip("10.0.0.1").to_request())
+                .await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[actix_rt::test]
+    async fn test_clients_are_limited_separately() {
+        let clock = ManualClock::new();
+        let limiter =
+            RateLimiter::new(1, Duration::from_secs(10)).clock(clock);
+        let mut app = test::init_service(
+            App::new()
+                .wrap(limiter)
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let res =
+            test::call_service(&mut app, from_ip("10.0.0.1").to_request())
+                .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res =
+            test::call_service(&mut app, from_ip("10.0.0.1").to_request())
+                .await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let res =
+            test::call_service(&mut app, from_ip("10.0.0.2").to_request())
+                .await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_key_by_header() {
+        let clock = ManualClock::new();
+        let limiter = RateLimiter::new(1, Duration::from_secs(10))
+            .key_by_header("x-api-key")
+            .clock(clock);
+        let mut app = test::init_service(
+            App::new()
+                .wrap(limiter)
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        // same address, different keys
+        let req = from_ip("10.0.0.1")
+            .header("x-api-key", "alice")
+            .to_request();
+        assert_eq!(
+            test::call_service(&mut app, req).await.status(),
+            StatusCode::OK
+        );
+        let req = from_ip("10.0.0.1").header("x-api-key", "bob").to_request();
+        assert_eq!(
+            test::call_service(&mut app, req).await.status(),
+            StatusCode::OK
+        );
+
+        // same key, different addresses
+        let req = from_ip("10.0.0.2")
+            .header("x-api-key", "alice")
+            .to_request();
+        assert_eq!(
+            test::call_service(&mut app, req).await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_idle_buckets_are_evicted() {
+        let clock = ManualClock::new();
+        let limiter =
+            RateLimiter::new(5, Duration::from_secs(10)).clock(clock.clone());
+        let buckets = limiter.buckets.clone();
+        let mut app = test::init_service(
+            App::new()
+                .wrap(limiter)
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        test::call_service(&mut app, from_ip("10.0.0.1").to_request()).await;
+        clock.advance(Duration::from_secs(4));
+        test::call_service(&mut app, from_ip("10.0.0.2").to_request()).await;
+        assert_eq!(buckets.lock().unwrap().clients.len(), 2);
+
+        // only the first client has been idle for a whole period
+        clock.advance(Duration::from_secs(6));
+        test::call_service(&mut app, from_ip("10.0.0.3").to_request()).await;
+        let clients = &buckets.lock().unwrap().clients;
+        assert_eq!(clients.len(), 2);
+        assert!(!clients.contains_key("10.0.0.1"));
+    }
+
+    #[actix_rt::test]
+    async fn test_short_idle_timeout_keeps_drained_buckets() {
+        let clock = ManualClock::new();
+        let limiter = RateLimiter::new(2, Duration::from_secs(10))
+            .idle_timeout(Duration::from_secs(1))
+            .clock(clock.clone());
+        let mut app = test::init_service(
+            App::new()
+                .wrap(limiter)
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        for _ in 0..2 {
+            let req = from_ip("10.0.0.1").to_request();
+            test::call_service(&mut app, req).await;
+        }
+
+        // a bucket forgotten now would be full again
+        clock.advance(Duration::from_secs(2));
+        let res =
+            test::call_service(&mut app, from_ip("10.0.0.1").to_request())
+                .await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[actix_rt::test]
+    async fn test_limits_per_scope() {
+        let clock = ManualClock::new();
+        let mut app = test::init_service(
+            App::new()
+                .service(
+                    web::scope("/strict")
+                        .wrap(
+                            RateLimiter::new(1, Duration::from_secs(10))
+                                .clock(clock.clone()),
+                        )
+                        .route("", web::get().to(HttpResponse::Ok)),
+                )
+                .service(
+                    web::scope("/relaxed")
+                        .wrap(
+                            RateLimiter::new(3, Duration::from_secs(10))
+                                .clock(clock),
+                        )
+                        .route("", web::get().to(HttpResponse::Ok)),
+                ),
+        )
+        .await;
+
+        for (uri, allowed) in &[("/strict", 1), ("/relaxed", 3)] {
+            for i in 0..4 {
+                let req = from_ip("10.0.0.1").uri(uri).to_request();
+                let res = test::call_service(&mut app, req).await;
+                let expected = if i < *allowed {
+                    StatusCode::OK
+                } else {
+                    StatusCode::TOO_MANY_REQUESTS
+                };
+                assert_eq!(res.status(), expected, "request {} to {}", i, uri);
+            }
+        }
+    }
+}
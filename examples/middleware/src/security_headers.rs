@@ -0,0 +1,424 @@
+#![allow(dead_code)]
+
+// <security-headers>
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use actix_service::{Service, Transform};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::{error, Error, FromRequest, HttpMessage, HttpRequest};
+use futures::future::{ok, ready, Future, Ready};
+
+/// The nonce of the current request's `Content-Security-Policy`. Inline
+/// `<script>` and `<style>` elements only run if they carry it, e.g.
+/// `<script nonce="{{ nonce }}">` in a template.
+#[derive(Clone, Debug)]
+pub struct CspNonce(String);
+
+impl CspNonce {
+    fn new() -> Self {
+        let bytes: [u8; 16] = rand::random();
+        // URL-safe, so templates do not escape it
+        CspNonce(base64::encode_config(bytes, base64::URL_SAFE_NO_PAD))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromRequest for CspNonce {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let nonce = req.extensions().get::<CspNonce>().cloned();
+        ready(nonce.ok_or_else(|| {
+            error::ErrorInternalServerError(
+                "SecurityHeaders is not registered",
+            )
+        }))
+    }
+}
+
+/// Marks responses whose headers an inner `SecurityHeaders` already set
+struct Applied;
+
+/// Adds headers that make browsers more careful with the responses.
+///
+/// Headers the handler sets itself are kept. When routes are wrapped with
+/// `SecurityHeaders` again, the innermost one decides, so a resource can
+/// override the defaults of its app.
+#[derive(Clone)]
+pub struct SecurityHeaders {
+    hsts: Option<HeaderValue>,
+    /// Values may contain `{nonce}`
+    headers: Vec<(HeaderName, String)>,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        SecurityHeaders::new()
+    }
+}
+
+impl SecurityHeaders {
+    /// Strict defaults that suit an HTML application without third-party
+    /// content
+    pub fn new() -> Self {
+        SecurityHeaders {
+            hsts: None,
+            headers: Vec::new(),
+        }
+        .hsts(63_072_000, true)
+        .content_security_policy(
+            "default-src 'self'; script-src 'self' 'nonce-{nonce}'; \
+             style-src 'self' 'nonce-{nonce}'; object-src 'none'; \
+             base-uri 'self'; frame-ancestors 'none'",
+        )
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(header::REFERRER_POLICY, "strict-origin-when-cross-origin")
+        .header(
+            HeaderName::from_static("permissions-policy"),
+            "camera=(), microphone=(), geolocation=()",
+        )
+        .header(header::X_FRAME_OPTIONS, "DENY")
+    }
+
+    /// `Strict-Transport-Security`, only sent with responses over TLS, as
+    /// browsers ignore it otherwise
+    pub fn hsts(mut self, max_age: u64, include_subdomains: bool) -> Self {
+        let mut value = format!("max-age={}", max_age);
+        if include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        self.hsts = Some(HeaderValue::from_str(&value).unwrap());
+        self
+    }
+
+    /// Every `{nonce}` in the policy is replaced with the request's nonce
+    pub fn content_security_policy(self, policy: &str) -> Self {
+        self.header(header::CONTENT_SECURITY_POLICY, policy)
+    }
+
+    pub fn referrer_policy(self, policy: &str) -> Self {
+        self.header(header::REFERRER_POLICY, policy)
+    }
+
+    pub fn permissions_policy(self, policy: &str) -> Self {
+        self.header(HeaderName::from_static("permissions-policy"), policy)
+    }
+
+    /// `DENY` or `SAMEORIGIN`
+    pub fn frame_options(self, value: &str) -> Self {
+        self.header(header::X_FRAME_OPTIONS, value)
+    }
+
+    /// Sets any other header
+    pub fn header(mut self, name: HeaderName, value: &str) -> Self {
+        // fail when building the app rather than with every response
+        HeaderValue::from_str(value).expect("invalid header value");
+        self = self.without(name.clone());
+        self.headers.push((name, value.to_owned()));
+        self
+    }
+
+    /// Leaves out one of the headers
+    pub fn without(mut self, name: HeaderName) -> Self {
+        if name == header::STRICT_TRANSPORT_SECURITY {
+            self.hsts = None;
+        }
+        self.headers.retain(|(other, _)| *other != name);
+        self
+    }
+}
+
+impl<S, B> Transform<S> for SecurityHeaders
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = SecurityHeadersMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(SecurityHeadersMiddleware {
+            service,
+            config: Rc::new(self.clone()),
+        })
+    }
+}
+
+pub struct SecurityHeadersMiddleware<S> {
+    service: S,
+    config: Rc<SecurityHeaders>,
+}
+
+impl<S, B> Service for SecurityHeadersMiddleware<S>
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        // nested middleware share the nonce of the outermost one
+        let existing = req.extensions().get::<CspNonce>().cloned();
+        let nonce = existing.unwrap_or_else(|| {
+            let nonce = CspNonce::new();
+            req.extensions_mut().insert(nonce.clone());
+            nonce
+        });
+        let https = req.connection_info().scheme() == "https";
+
+        let config = self.config.clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if res.response().extensions().contains::<Applied>() {
+                return Ok(res);
+            }
+            res.response_mut().extensions_mut().insert(Applied);
+
+            let headers = res.headers_mut();
+            if let Some(ref hsts) = config.hsts {
+                if https
+                    && !headers.contains_key(header::STRICT_TRANSPORT_SECURITY)
+                {
+                    headers.insert(
+                        header::STRICT_TRANSPORT_SECURITY,
+                        hsts.clone(),
+                    );
+                }
+            }
+            for (name, value) in &config.headers {
+                if headers.contains_key(name) {
+                    continue;
+                }
+                let value = value.replace("{nonce}", nonce.as_str());
+                // base64 is a valid header value
+                headers.insert(
+                    name.clone(),
+                    HeaderValue::from_str(&value).unwrap(),
+                );
+            }
+            Ok(res)
+        })
+    }
+}
+// </security-headers>
+
+// <security-headers-usage>
+use actix_web::{web, App, HttpResponse, HttpServer};
+use templating::{Template, Templates};
+
+const INDEX: &str = r#"<!doctype html>
+<style nonce="{{ nonce }}">body { font-family: sans-serif }</style>
+<script nonce="{{ nonce }}">console.log("allowed")</script>
+<script>console.log("blocked, it has no nonce")</script>"#;
+
+async fn index(nonce: CspNonce) -> Template {
+    Template::new("index.html").with("nonce", nonce.as_str())
+}
+
+/// Other sites may show this page in a frame
+async fn widget() -> HttpResponse {
+    HttpResponse::Ok().body("<p>widget</p>")
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let templates =
+        web::Data::new(Templates::new(&[("index.html", INDEX)]).unwrap());
+
+    HttpServer::new(move || {
+        App::new()
+            .app_data(templates.clone())
+            .wrap(SecurityHeaders::new())
+            .route("/", web::get().to(index))
+            .service(
+                web::resource("/widget")
+                    .wrap(
+                        SecurityHeaders::new()
+                            .content_security_policy("default-src 'self'")
+                            .without(header::X_FRAME_OPTIONS),
+                    )
+                    .route(web::get().to(widget)),
+            )
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await
+}
+// </security-headers-usage>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+
+    fn header(res: &ServiceResponse, name: HeaderName) -> Option<&str> {
+        res.headers().get(name).map(|value| value.to_str().unwrap())
+    }
+
+    #[actix_rt::test]
+    async fn test_defaults_and_nonce() {
+        let templates =
+            web::Data::new(Templates::new(&[("index.html", INDEX)]).unwrap());
+        let mut app = test::init_service(
+            App::new()
+                .app_data(templates)
+                .wrap(SecurityHeaders::new())
+                .route("/", web::get().to(index)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(
+            header(&res, header::X_CONTENT_TYPE_OPTIONS),
+            Some("nosniff")
+        );
+        assert_eq!(header(&res, header::X_FRAME_OPTIONS), Some("DENY"));
+        assert_eq!(
+            header(&res, header::REFERRER_POLICY),
+            Some("strict-origin-when-cross-origin")
+        );
+        assert!(header(&res, HeaderName::from_static("permissions-policy"))
+            .is_some());
+        // not over TLS
+        assert_eq!(header(&res, header::STRICT_TRANSPORT_SECURITY), None);
+
+        let csp = header(&res, header::CONTENT_SECURITY_POLICY)
+            .unwrap()
+            .to_owned();
+        let nonce = csp
+            .split("'nonce-")
+            .nth(1)
+            .and_then(|rest| rest.split('\'').next())
+            .unwrap()
+            .to_owned();
+        let bytes = base64::decode_config(&nonce, base64::URL_SAFE_NO_PAD);
+        assert_eq!(bytes.unwrap().len(), 16);
+        let body = test::read_body(res).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains(&format!(r#"<script nonce="{}">"#, nonce)));
+
+        // a new nonce for every request
+        let req = test::TestRequest::get().uri("/").to_request();
+        let res = test::call_service(&mut app, req).await;
+        let other = header(&res, header::CONTENT_SECURITY_POLICY).unwrap();
+        assert!(!other.contains(&nonce));
+    }
+
+    #[actix_rt::test]
+    async fn test_hsts_over_tls() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(SecurityHeaders::new().hsts(600, false))
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("https://localhost/")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(
+            header(&res, header::STRICT_TRANSPORT_SECURITY),
+            Some("max-age=600")
+        );
+
+        // behind a proxy that terminates TLS
+        let req = test::TestRequest::get()
+            .uri("/")
+            .header("x-forwarded-proto", "https")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(
+            header(&res, header::STRICT_TRANSPORT_SECURITY),
+            Some("max-age=600")
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_overrides() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(SecurityHeaders::new())
+                .route(
+                    "/report",
+                    web::get().to(|| {
+                        HttpResponse::Ok()
+                            .header(header::REFERRER_POLICY, "no-referrer")
+                            .finish()
+                    }),
+                )
+                .service(
+                    web::resource("/widget")
+                        .wrap(
+                            SecurityHeaders::new()
+                                .content_security_policy(
+                                    "script-src 'nonce-{nonce}'",
+                                )
+                                .without(header::X_FRAME_OPTIONS),
+                        )
+                        .route(web::get().to(|nonce: CspNonce| {
+                            HttpResponse::Ok().body(nonce.as_str().to_owned())
+                        })),
+                ),
+        )
+        .await;
+
+        // the handler's own header wins
+        let req = test::TestRequest::get().uri("/report").to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(header(&res, header::REFERRER_POLICY), Some("no-referrer"));
+        assert_eq!(header(&res, header::X_FRAME_OPTIONS), Some("DENY"));
+
+        // the resource's configuration replaces the app's
+        let req = test::TestRequest::get().uri("/widget").to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(header(&res, header::X_FRAME_OPTIONS), None);
+        let csp = header(&res, header::CONTENT_SECURITY_POLICY)
+            .unwrap()
+            .to_owned();
+        let nonce = test::read_body(res).await;
+        assert_eq!(
+            csp,
+            format!(
+                "script-src 'nonce-{}'",
+                std::str::from_utf8(&nonce).unwrap()
+            )
+        );
+    }
+}
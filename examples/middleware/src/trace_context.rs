@@ -0,0 +1,663 @@
+#![allow(dead_code)]
+
+// <trace-context>
+use actix_web::dev::Payload;
+use actix_web::error::BlockingError;
+use actix_web::{error, web, Error, FromRequest, HttpMessage, HttpRequest};
+use awc::ClientRequest;
+use futures::future::{ready, Ready};
+use request_id::current;
+
+pub const TRACEPARENT: &str = "traceparent";
+pub const TRACESTATE: &str = "tracestate";
+
+/// The position of a span in a distributed trace, as defined by
+/// [W3C Trace Context](https://www.w3.org/TR/trace-context/).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    sampled: bool,
+    state: Option<String>,
+}
+
+fn parse_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    // only lowercase hex digits are valid
+    if hex.len() != 2 * N
+        || !hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    {
+        return None;
+    }
+    let mut bytes = [0; N];
+    hex::decode_to_slice(hex, &mut bytes).ok()?;
+    Some(bytes)
+}
+
+/// Trace and span ids of all zeros are invalid
+fn non_zero<const N: usize>(id: [u8; N]) -> Option<[u8; N]> {
+    Some(id).filter(|id| id.iter().any(|&b| b != 0))
+}
+
+impl TraceContext {
+    /// Parses the incoming `traceparent` and `tracestate` headers. Returns
+    /// `None` if `traceparent` is invalid, in which case a new trace starts.
+    pub fn parse(traceparent: &str, tracestate: Option<&str>) -> Option<Self> {
+        let parts: Vec<&str> = traceparent.trim().split('-').collect();
+        if parts.len() < 4 {
+            return None;
+        }
+        let [version] = parse_hex::<1>(parts[0])?;
+        // later versions may append fields, version 00 may not
+        if version == 0xff || (version == 0 && parts.len() != 4) {
+            return None;
+        }
+        let [flags] = parse_hex::<1>(parts[3])?;
+
+        Some(TraceContext {
+            trace_id: non_zero(parse_hex(parts[1])?)?,
+            span_id: non_zero(parse_hex(parts[2])?)?,
+            sampled: flags & 1 == 1,
+            state: tracestate
+                .map(str::trim)
+                .filter(|state| !state.is_empty() && state.len() <= 512)
+                .map(String::from),
+        })
+    }
+
+    /// Starts a new, sampled trace
+    fn root() -> Self {
+        TraceContext {
+            trace_id: new_id(),
+            span_id: new_id(),
+            sampled: true,
+            state: None,
+        }
+    }
+
+    /// A new span in the same trace
+    fn child(&self) -> Self {
+        TraceContext {
+            span_id: new_id(),
+            ..self.clone()
+        }
+    }
+
+    pub fn trace_id(&self) -> String {
+        hex::encode(self.trace_id)
+    }
+
+    pub fn span_id(&self) -> String {
+        hex::encode(self.span_id)
+    }
+
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            self.trace_id(),
+            self.span_id(),
+            self.sampled as u8
+        )
+    }
+
+    /// The context of the request being handled on this thread
+    pub fn current() -> Option<TraceContext> {
+        current::get()
+    }
+
+    /// Adds `traceparent` and `tracestate` to a request to another service,
+    /// so that its spans join this trace as children of this span.
+    pub fn inject(&self, req: ClientRequest) -> ClientRequest {
+        let req = req.set_header(TRACEPARENT, self.traceparent());
+        match self.state {
+            Some(ref state) => req.set_header(TRACESTATE, state.as_str()),
+            None => req,
+        }
+    }
+}
+
+fn new_id<const N: usize>() -> [u8; N] {
+    loop {
+        let mut id = [0; N];
+        rand::Rng::fill(&mut rand::thread_rng(), &mut id[..]);
+        if let Some(id) = non_zero(id) {
+            return id;
+        }
+    }
+}
+
+impl FromRequest for TraceContext {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let ctx = req.extensions().get::<TraceContext>().cloned();
+        ready(ctx.ok_or_else(|| {
+            error::ErrorInternalServerError("Tracing is not registered")
+        }))
+    }
+}
+
+/// Like `web::block`, but `TraceContext::current()` works in `f` as well.
+pub async fn block<F, I, E>(f: F) -> Result<I, BlockingError<E>>
+where
+    F: FnOnce() -> Result<I, E> + Send + 'static,
+    I: Send + 'static,
+    E: Send + std::fmt::Debug + 'static,
+{
+    match TraceContext::current() {
+        Some(ctx) => web::block(move || current::scope(&ctx, f)).await,
+        None => web::block(f).await,
+    }
+}
+// </trace-context>
+
+// <tracing>
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_service::{Service, Transform};
+use actix_web::{dev::ServiceRequest, dev::ServiceResponse};
+use futures::future::{ok, Future};
+use serde_json::{json, Value};
+
+/// A finished span
+#[derive(Clone, Debug)]
+pub struct SpanData {
+    pub name: String,
+    pub context: TraceContext,
+    pub parent_span_id: Option<[u8; 8]>,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub attributes: Vec<(&'static str, Value)>,
+    pub error: bool,
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    let nanos = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    nanos.as_nanos().to_string()
+}
+
+impl SpanData {
+    /// The span as in the JSON encoding of OTLP, the OpenTelemetry protocol
+    pub fn to_otlp(&self) -> Value {
+        let attributes: Vec<Value> = self
+            .attributes
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::Number(n) => json!({ "intValue": n.to_string() }),
+                    Value::String(s) => json!({ "stringValue": s }),
+                    other => json!({ "stringValue": other.to_string() }),
+                };
+                json!({ "key": key, "value": value })
+            })
+            .collect();
+
+        json!({
+            "traceId": self.context.trace_id(),
+            "spanId": self.context.span_id(),
+            "parentSpanId": self.parent_span_id.map(hex::encode).unwrap_or_default(),
+            "traceState": self.context.state.clone().unwrap_or_default(),
+            "name": self.name,
+            // SPAN_KIND_SERVER
+            "kind": 2,
+            "startTimeUnixNano": unix_nanos(self.start),
+            "endTimeUnixNano": unix_nanos(self.end),
+            "attributes": attributes,
+            // STATUS_CODE_ERROR or STATUS_CODE_UNSET
+            "status": { "code": if self.error { 2 } else { 0 } },
+        })
+    }
+}
+
+/// Where finished spans go
+pub trait Exporter: Send + Sync {
+    fn export(&self, span: &SpanData);
+}
+
+/// Prints every span as a line of JSON
+pub struct Stdout;
+
+impl Exporter for Stdout {
+    fn export(&self, span: &SpanData) {
+        println!("{}", span.to_otlp());
+    }
+}
+
+/// Sends every span to an OpenTelemetry collector, using OTLP over HTTP
+/// with JSON bodies, e.g. to `http://localhost:4318/v1/traces`.
+pub struct Otlp {
+    endpoint: String,
+    service_name: String,
+}
+
+impl Otlp {
+    pub fn new(endpoint: &str, service_name: &str) -> Self {
+        Otlp {
+            endpoint: endpoint.to_owned(),
+            service_name: service_name.to_owned(),
+        }
+    }
+}
+
+thread_local! {
+    // clients keep their connections to the collector open, but cannot be
+    // shared between threads, so every worker has one of its own
+    static CLIENT: awc::Client = awc::Client::new();
+}
+
+impl Exporter for Otlp {
+    fn export(&self, span: &SpanData) {
+        let body = json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [{
+                        "key": "service.name",
+                        "value": { "stringValue": self.service_name },
+                    }],
+                },
+                "scopeSpans": [{
+                    "scope": { "name": "actix-web-examples" },
+                    "spans": [span.to_otlp()],
+                }],
+            }],
+        });
+
+        // the response is sent without waiting for the collector
+        let req = CLIENT.with(|client| client.post(&self.endpoint));
+        actix_rt::spawn(async move {
+            match req.send_json(&body).await {
+                Ok(res) if res.status().is_success() => {}
+                Ok(res) => log::warn!("collector answered {}", res.status()),
+                Err(err) => log::warn!("cannot export span: {}", err),
+            }
+        });
+    }
+}
+
+/// Continues the trace of an incoming `traceparent` header, or starts a new
+/// one, and exports a span for every request.
+///
+/// Handlers get the span's context with the `TraceContext` extractor.
+#[derive(Clone)]
+pub struct Tracing {
+    exporter: Arc<dyn Exporter>,
+}
+
+impl Tracing {
+    pub fn new(exporter: impl Exporter + 'static) -> Self {
+        Tracing {
+            exporter: Arc::new(exporter),
+        }
+    }
+}
+
+impl<S, B> Transform<S> for Tracing
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = TracingMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(TracingMiddleware {
+            service,
+            exporter: self.exporter.clone(),
+        })
+    }
+}
+
+pub struct TracingMiddleware<S> {
+    service: S,
+    exporter: Arc<dyn Exporter>,
+}
+
+fn header<'a>(req: &'a ServiceRequest, name: &str) -> Option<&'a str> {
+    req.headers().get(name)?.to_str().ok()
+}
+
+impl<S, B> Service for TracingMiddleware<S>
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let parent = header(&req, TRACEPARENT).and_then(|traceparent| {
+            TraceContext::parse(traceparent, header(&req, TRACESTATE))
+        });
+        let ctx = match parent {
+            Some(ref parent) => parent.child(),
+            None => TraceContext::root(),
+        };
+        req.extensions_mut().insert(ctx.clone());
+
+        let method = req.method().to_string();
+        let route = req.match_pattern();
+        let mut span = SpanData {
+            name: match route {
+                Some(ref route) => format!("{} {}", method, route),
+                None => method.clone(),
+            },
+            context: ctx.clone(),
+            parent_span_id: parent.map(|parent| parent.span_id),
+            start: SystemTime::now(),
+            end: SystemTime::now(),
+            attributes: vec![
+                ("http.method", Value::from(method)),
+                // without the query, which may hold tokens or personal data
+                ("http.target", Value::from(req.path())),
+            ],
+            error: false,
+        };
+        if let Some(route) = route {
+            span.attributes.push(("http.route", Value::from(route)));
+        }
+
+        let exporter = self.exporter.clone();
+        let service = &mut self.service;
+        let fut = current::scope(&ctx, || service.call(req));
+        let fut = current::Scoped::new(ctx.clone(), fut);
+
+        Box::pin(async move {
+            let res = fut.await;
+            let status = match res {
+                Ok(ref res) => res.status(),
+                Err(ref err) => err.as_response_error().status_code(),
+            };
+
+            span.end = SystemTime::now();
+            span.error = status.is_server_error();
+            span.attributes
+                .push(("http.status_code", Value::from(status.as_u16())));
+            // the caller decided whether the trace is recorded
+            if ctx.sampled {
+                exporter.export(&span);
+            }
+            res
+        })
+    }
+}
+// </tracing>
+
+// <tracing-usage>
+use actix_web::{App, HttpResponse, HttpServer};
+
+/// Calls another service, which continues the trace
+async fn inventory(ctx: TraceContext) -> Result<HttpResponse, Error> {
+    let client = awc::Client::new();
+    let req = ctx.inject(client.get("http://localhost:8081/stock"));
+    let mut res = req.send().await.map_err(error::ErrorBadGateway)?;
+    let stock = res.body().await?;
+
+    // the closure runs on another thread, but in the same trace
+    let report = block(|| {
+        let ctx = TraceContext::current().unwrap();
+        Ok::<_, ()>(format!("report for trace {}", ctx.trace_id()))
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().body(format!("{}\n{:?}", report, stock)))
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    HttpServer::new(|| {
+        // OTEL_EXPORTER_OTLP_ENDPOINT=http://localhost:4318 sends the spans
+        // to a collector instead of printing them
+        let tracing = match std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+            Ok(endpoint) => Tracing::new(Otlp::new(
+                &format!("{}/v1/traces", endpoint),
+                "inventory",
+            )),
+            Err(_) => Tracing::new(Stdout),
+        };
+
+        App::new()
+            .wrap(tracing)
+            .route("/inventory", web::get().to(inventory))
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await
+}
+// </tracing-usage>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    const PARENT: &str =
+        "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+    #[test]
+    fn test_parse() {
+        let ctx =
+            TraceContext::parse(PARENT, Some("congo=t61rcWkgMzE")).unwrap();
+        assert_eq!(ctx.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(ctx.span_id(), "b7ad6b7169203331");
+        assert!(ctx.sampled);
+        assert_eq!(ctx.traceparent(), PARENT);
+
+        let child = ctx.child();
+        assert_eq!(child.trace_id(), ctx.trace_id());
+        assert_ne!(child.span_id(), ctx.span_id());
+        assert_eq!(child.state.as_deref(), Some("congo=t61rcWkgMzE"));
+
+        // later versions may add fields
+        let future = format!("cc{}-what-comes-next", &PARENT[2..]);
+        assert!(TraceContext::parse(&future, None).is_some());
+
+        for invalid in &[
+            "",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-extra",
+            "ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            "00-0AF7651916CD43DD8448EB211C80319C-b7ad6b7169203331-01",
+            "00-00000000000000000000000000000000-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319c-0000000000000000-01",
+            "00-0af7651916cd43dd8448eb211c8031-b7ad6b7169203331-01",
+        ] {
+            assert!(
+                TraceContext::parse(invalid, None).is_none(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<SpanData>>>);
+
+    impl Exporter for Collect {
+        fn export(&self, span: &SpanData) {
+            self.0.lock().unwrap().push(span.clone());
+        }
+    }
+
+    async fn ids(ctx: TraceContext) -> Result<HttpResponse, Error> {
+        let in_block = block(|| Ok::<_, ()>(TraceContext::current())).await?;
+        assert_eq!(in_block.as_ref(), Some(&ctx));
+        Ok(HttpResponse::Ok().body(ctx.traceparent()))
+    }
+
+    #[actix_rt::test]
+    async fn test_spans() {
+        let spans = Collect::default();
+        let mut app = test::init_service(
+            App::new()
+                .wrap(Tracing::new(spans.clone()))
+                .route("/items/{id}", web::get().to(ids)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/items/7?token=secret")
+            .header(TRACEPARENT, PARENT)
+            .header(TRACESTATE, "congo=t61rcWkgMzE")
+            .to_request();
+        let body =
+            test::read_body(test::call_service(&mut app, req).await).await;
+        let traceparent = std::str::from_utf8(&body).unwrap();
+        assert!(
+            traceparent.starts_with("00-0af7651916cd43dd8448eb211c80319c-")
+        );
+
+        let span = spans.0.lock().unwrap().pop().unwrap();
+        assert_eq!(span.name, "GET /items/{id}");
+        assert_eq!(span.context.traceparent(), traceparent);
+        assert_eq!(
+            hex::encode(span.parent_span_id.unwrap()),
+            "b7ad6b7169203331"
+        );
+        let otlp = span.to_otlp();
+        assert_eq!(otlp["traceState"], "congo=t61rcWkgMzE");
+        let attributes = otlp["attributes"].as_array().unwrap();
+        assert!(attributes.contains(&json!({
+            "key": "http.route", "value": { "stringValue": "/items/{id}" }
+        })));
+        assert!(attributes.contains(&json!({
+            "key": "http.target", "value": { "stringValue": "/items/7" }
+        })));
+        assert!(attributes.contains(&json!({
+            "key": "http.status_code", "value": { "intValue": "200" }
+        })));
+
+        // without a valid parent a new trace starts
+        let req = test::TestRequest::get()
+            .uri("/items/7")
+            .header(TRACEPARENT, "garbage")
+            .to_request();
+        test::call_service(&mut app, req).await;
+        let span = spans.0.lock().unwrap().pop().unwrap();
+        assert_ne!(
+            span.context.trace_id(),
+            "0af7651916cd43dd8448eb211c80319c"
+        );
+        assert!(span.parent_span_id.is_none());
+
+        // the caller did not sample the trace
+        let unsampled = format!("{}-00", &PARENT[..PARENT.len() - 3]);
+        let req = test::TestRequest::get()
+            .uri("/missing")
+            .header(TRACEPARENT, unsampled.as_str())
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert!(spans.0.lock().unwrap().is_empty());
+    }
+
+    /// Echoes the headers a downstream service receives
+    async fn downstream(req: HttpRequest) -> HttpResponse {
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .map_or("", |value| value.to_str().unwrap())
+                .to_owned()
+        };
+        HttpResponse::Ok().json(json!({
+            "traceparent": header(TRACEPARENT),
+            "tracestate": header(TRACESTATE),
+        }))
+    }
+
+    #[actix_rt::test]
+    async fn test_propagation_and_otlp() {
+        // stand-ins for another service and an OpenTelemetry collector
+        let service =
+            test::start(|| App::new().default_service(web::to(downstream)));
+        let exported = Arc::new(Mutex::new(Vec::<Value>::new()));
+        let received = exported.clone();
+        let collector = test::start(move || {
+            let received = received.clone();
+            App::new().route(
+                "/v1/traces",
+                web::post().to(move |body: web::Json<Value>| {
+                    received.lock().unwrap().push(body.into_inner());
+                    HttpResponse::Ok()
+                }),
+            )
+        });
+
+        let downstream_url = service.url("/stock");
+        let otlp = Otlp::new(&collector.url("/v1/traces"), "inventory");
+        let mut app =
+            test::init_service(App::new().wrap(Tracing::new(otlp)).route(
+                "/inventory",
+                web::get().to(move |ctx: TraceContext| {
+                    let req =
+                        ctx.inject(awc::Client::new().get(&downstream_url));
+                    async move {
+                        let mut res = req.send().await.unwrap();
+                        let seen: Value = res.json().await.unwrap();
+                        Ok::<_, Error>(HttpResponse::Ok().json(json!({
+                            "span": ctx.traceparent(),
+                            "downstream": seen,
+                        })))
+                    }
+                }),
+            ))
+            .await;
+
+        let req = test::TestRequest::get()
+            .uri("/inventory")
+            .header(TRACEPARENT, PARENT)
+            .header(TRACESTATE, "congo=t61rcWkgMzE")
+            .to_request();
+        let body: Value = test::read_response_json(&mut app, req).await;
+        assert_eq!(body["downstream"]["traceparent"], body["span"]);
+        assert_eq!(body["downstream"]["tracestate"], "congo=t61rcWkgMzE");
+
+        // the span is exported in the background
+        for _ in 0..50 {
+            if !exported.lock().unwrap().is_empty() {
+                break;
+            }
+            actix_rt::time::delay_for(Duration::from_millis(20)).await;
+        }
+        let exported = exported.lock().unwrap();
+        let resource = &exported[0]["resourceSpans"][0];
+        assert_eq!(
+            resource["resource"]["attributes"][0]["value"]["stringValue"],
+            "inventory"
+        );
+        let span = &resource["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["name"], "GET /inventory");
+        assert_eq!(span["parentSpanId"], "b7ad6b7169203331");
+        let traceparent = body["span"].as_str().unwrap();
+        assert_eq!(span["spanId"], traceparent[36..52]);
+    }
+}
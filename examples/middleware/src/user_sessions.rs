@@ -20,16 +20,318 @@ async fn index(session: Session) -> Result<HttpResponse, Error> {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| {
+    // the signing key must stay the same across restarts and never be
+    // committed, so it comes from the environment rather than the code
+    let key = session_key_from_env()?;
+    let users =
+        web::Data::new(Users::default().with_user(1, "ferris", "crab"));
+
+    HttpServer::new(move || {
         App::new()
             .wrap(
-                CookieSession::signed(&[0; 32]) // <- create cookie based session middleware
-                    .secure(false),
+                CookieSession::signed(&key) // <- create cookie based session middleware
+                    .secure(false)
+                    // a stolen cookie stays valid until it expires
+                    .max_age(SESSION_MAX_AGE),
             )
+            .app_data(users.clone())
             .service(web::resource("/").to(index))
+            .configure(auth_config)
     })
     .bind("127.0.0.1:8080")?
     .run()
     .await
 }
 // </user-session>
+
+// <session-key>
+/// Reads the hex encoded cookie signing key from `SESSION_KEY`.
+///
+/// A key can be generated with `openssl rand -hex 32`.
+fn session_key_from_env() -> std::io::Result<Vec<u8>> {
+    use std::io::{Error, ErrorKind};
+
+    let hex_key = std::env::var("SESSION_KEY").map_err(|_| {
+        Error::new(ErrorKind::NotFound, "SESSION_KEY is not set")
+    })?;
+    let key = hex::decode(hex_key.trim()).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("SESSION_KEY is not valid hex: {}", e),
+        )
+    })?;
+
+    if key.len() < 32 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "SESSION_KEY must be at least 32 bytes long",
+        ));
+    }
+
+    Ok(key)
+}
+// </session-key>
+
+// <session-auth>
+use std::collections::HashMap;
+
+use actix_session::UserSession;
+use actix_web::{dev, error, http, FromRequest, HttpRequest};
+use futures::future::{ready, Ready};
+use serde::Deserialize;
+
+const USER_ID_KEY: &str = "user_id";
+
+/// Seconds until the session cookie expires, one day
+const SESSION_MAX_AGE: i64 = 24 * 60 * 60;
+
+struct StoredUser {
+    id: u32,
+    password_hash: String,
+}
+
+/// In-memory user table; a real application would load this from a database.
+pub struct Users {
+    users: HashMap<String, StoredUser>,
+    /// Checked for unknown users, so that they take as long as known ones
+    dummy_hash: String,
+}
+
+fn hash_password(password: &str) -> String {
+    let salt: [u8; 16] = rand::random();
+    argon2::hash_encoded(
+        password.as_bytes(),
+        &salt,
+        &argon2::Config::default(),
+    )
+    .expect("default argon2 config is valid")
+}
+
+impl Default for Users {
+    fn default() -> Self {
+        Users {
+            users: HashMap::new(),
+            dummy_hash: hash_password("not anyone's password"),
+        }
+    }
+}
+
+impl Users {
+    /// Hashes the password right away, which is slow on purpose; a handler
+    /// that registers users should call it with `web::block`.
+    pub fn with_user(
+        mut self,
+        id: u32,
+        username: &str,
+        password: &str,
+    ) -> Self {
+        let password_hash = hash_password(password);
+        self.users
+            .insert(username.to_owned(), StoredUser { id, password_hash });
+        self
+    }
+
+    fn verify(&self, username: &str, password: &str) -> Option<u32> {
+        // hash the password even for unknown users, otherwise the response
+        // time tells whether a username exists
+        let (id, hash) = match self.users.get(username) {
+            Some(user) => (Some(user.id), &user.password_hash),
+            None => (None, &self.dummy_hash),
+        };
+        let valid = argon2::verify_encoded(hash, password.as_bytes());
+
+        id.filter(|_| valid.unwrap_or(false))
+    }
+}
+
+/// Extractor for the id of the logged in user.
+///
+/// Handlers that take a `LoggedInUser` are never called for anonymous
+/// requests, those are rejected with `401 Unauthorized` instead.
+pub struct LoggedInUser(pub u32);
+
+impl FromRequest for LoggedInUser {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut dev::Payload) -> Self::Future {
+        let user_id = req.get_session().get::<u32>(USER_ID_KEY);
+
+        ready(match user_id {
+            Ok(Some(id)) => Ok(LoggedInUser(id)),
+            Ok(None) => Err(error::ErrorUnauthorized("login required")),
+            Err(err) => Err(err),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Credentials {
+    username: String,
+    password: String,
+}
+
+fn login_page(message: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<body>
+  <p>{}</p>
+  <form action="/login" method="post">
+    <input type="text" name="username" placeholder="username">
+    <input type="password" name="password" placeholder="password">
+    <button type="submit">Log in</button>
+  </form>
+</body>
+</html>"#,
+        message
+    )
+}
+
+async fn login_form() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(login_page("Please log in."))
+}
+
+async fn login(
+    session: Session,
+    users: web::Data<Users>,
+    form: web::Form<Credentials>,
+) -> Result<HttpResponse, Error> {
+    // argon2 is slow on purpose, so it runs on the blocking thread pool
+    // rather than stalling every other request on this worker
+    let Credentials { username, password } = form.into_inner();
+    let user_id =
+        web::block(move || Ok::<_, ()>(users.verify(&username, &password)))
+            .await?;
+
+    match user_id {
+        Some(id) => {
+            // the user gains privileges, so issue a fresh session to
+            // prevent session fixation
+            session.renew();
+            session.set(USER_ID_KEY, id)?;
+
+            Ok(HttpResponse::SeeOther()
+                .header(http::header::LOCATION, "/account")
+                .finish())
+        }
+        None => Ok(HttpResponse::Unauthorized()
+            .content_type("text/html; charset=utf-8")
+            .body(login_page("Invalid username or password."))),
+    }
+}
+
+/// The session lives in the cookie itself, so purging it only replaces the
+/// browser's copy. A copy stolen earlier stays valid until its `max_age`
+/// runs out; revoking sessions needs a server-side session store.
+async fn logout(session: Session) -> HttpResponse {
+    session.purge();
+
+    HttpResponse::SeeOther()
+        .header(http::header::LOCATION, "/login")
+        .finish()
+}
+
+async fn account(user: LoggedInUser) -> HttpResponse {
+    HttpResponse::Ok().body(format!("Welcome back, user #{}!", user.0))
+}
+
+fn auth_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/login")
+            .route(web::get().to(login_form))
+            .route(web::post().to(login)),
+    )
+    .route("/logout", web::post().to(logout))
+    .service(web::scope("/account").route("", web::get().to(account)));
+}
+// </session-auth>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{cookie::Cookie, test};
+
+    fn session_cookie(resp: &dev::ServiceResponse) -> Cookie<'static> {
+        resp.response()
+            .cookies()
+            .find(|c| c.name() == "actix-session")
+            .expect("response sets the session cookie")
+            .into_owned()
+    }
+
+    #[actix_rt::test]
+    async fn test_login_logout() {
+        let users =
+            web::Data::new(Users::default().with_user(7, "ferris", "crab"));
+        let mut app = test::init_service(
+            App::new()
+                .wrap(
+                    CookieSession::signed(&[7; 32])
+                        .secure(false)
+                        .max_age(SESSION_MAX_AGE),
+                )
+                .app_data(users)
+                .configure(auth_config),
+        )
+        .await;
+
+        // anonymous requests are rejected
+        let req = test::TestRequest::get().uri("/account").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+
+        // wrong password
+        let req = test::TestRequest::post()
+            .uri("/login")
+            .set_form(&[("username", "ferris"), ("password", "lobster")])
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+
+        // unknown user
+        let req = test::TestRequest::post()
+            .uri("/login")
+            .set_form(&[("username", "corro"), ("password", "crab")])
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+
+        // correct password
+        let req = test::TestRequest::post()
+            .uri("/login")
+            .set_form(&[("username", "ferris"), ("password", "crab")])
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::SEE_OTHER);
+        let cookie = session_cookie(&resp);
+        let max_age = cookie.max_age().map(|age| age.whole_seconds());
+        assert_eq!(max_age, Some(SESSION_MAX_AGE));
+
+        let req = test::TestRequest::get()
+            .uri("/account")
+            .cookie(cookie.clone())
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, web::Bytes::from_static(b"Welcome back, user #7!"));
+
+        // logout replaces the session with an empty one
+        let req = test::TestRequest::post()
+            .uri("/logout")
+            .cookie(cookie)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::SEE_OTHER);
+        let cookie = session_cookie(&resp);
+
+        let req = test::TestRequest::get()
+            .uri("/account")
+            .cookie(cookie)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+    }
+}
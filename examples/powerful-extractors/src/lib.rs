@@ -0,0 +1,269 @@
+use actix_web::{error, web, Error, HttpRequest, HttpResponse, Responder};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use templating::{Template, Templates};
+use validation::{length, pattern, range, Validate, Validated, Validator};
+
+pub mod store;
+
+use store::{EventStore, Filter};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Event {
+    id: Option<i32>,
+    timestamp: f64,
+    kind: String,
+    tags: Vec<String>,
+}
+
+static KIND: Lazy<Regex> =
+    Lazy::new(|| Regex::new("^[a-z][a-z0-9 _-]*$").unwrap());
+
+impl Validate for Event {
+    fn validate(&self, v: &mut Validator) {
+        // 2000-01-01 up to 2100-01-01, in seconds
+        v.field(
+            "timestamp",
+            &self.timestamp,
+            &[&range(946_684_800.0, 4_102_444_800.0)],
+        )
+        .field(
+            "kind",
+            &self.kind,
+            &[
+                &length(1, 64),
+                &pattern(&KIND, "must be lowercase and start with a letter"),
+            ],
+        )
+        .field("tags", &self.tags, &[&length(0, 16)])
+        .each("tags", &self.tags, &[&length(1, 32)]);
+    }
+}
+
+/// Appends wait for the disk, so they run on the blocking thread pool
+async fn store_in_db(
+    store: web::Data<EventStore>,
+    evt: Event,
+) -> Result<Event, Error> {
+    web::block(move || store.append(evt))
+        .await
+        .map_err(error::ErrorInternalServerError)
+}
+
+async fn capture_event(
+    store: web::Data<EventStore>,
+    evt: Validated<web::Json<Event>>,
+) -> Result<impl Responder, Error> {
+    let new_event = store_in_db(store, evt.into_inner().into_inner()).await?;
+    Ok(format!("got event {}", new_event.id.unwrap()))
+}
+
+async fn get_event(
+    store: web::Data<EventStore>,
+    id: web::Path<i32>,
+) -> HttpResponse {
+    match store.get(id.into_inner()) {
+        Some(evt) => HttpResponse::Ok().json(evt),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+#[derive(Deserialize)]
+struct EventQuery {
+    kind: Option<String>,
+    tag: Option<String>,
+    from: Option<f64>,
+    to: Option<f64>,
+    /// `next_cursor` of the previous page
+    cursor: Option<i32>,
+    limit: Option<usize>,
+}
+
+impl Validate for EventQuery {
+    fn validate(&self, v: &mut Validator) {
+        v.optional("limit", &self.limit, &[&range(1, 100)]);
+    }
+}
+
+#[derive(Serialize)]
+struct EventPage {
+    events: Vec<Event>,
+    next_cursor: Option<i32>,
+}
+
+/// Events matching the query, oldest first
+async fn list_events(
+    store: web::Data<EventStore>,
+    query: Validated<web::Query<EventQuery>>,
+) -> HttpResponse {
+    let query = query.into_inner().into_inner();
+    let filter = Filter {
+        kind: query.kind,
+        tag: query.tag,
+        from: query.from,
+        to: query.to,
+    };
+
+    let page = store.list(&filter, query.cursor, query.limit.unwrap_or(20));
+    HttpResponse::Ok().json(EventPage {
+        events: page.events,
+        next_cursor: page.next,
+    })
+}
+
+async fn index(req: HttpRequest) -> Result<Template, Error> {
+    let event_url = req.url_for_static("event")?;
+    Ok(Template::new("index.html").with("event_url", event_url.as_str()))
+}
+
+/// Edits to `templates/` show up on the next request in debug builds
+fn templates() -> Templates {
+    let sources = [("index.html", include_str!("../templates/index.html"))];
+    Templates::new(&sources)
+        .expect("invalid template")
+        .reload_from(concat!(env!("CARGO_MANIFEST_DIR"), "/templates"))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.data(templates())
+        .route("/", web::get().to(index))
+        .service(
+            web::resource("/event")
+                .name("event")
+                .route(web::post().to(capture_event))
+                .route(web::get().to(list_events)),
+        )
+        .route("/event/{id}", web::get().to(get_event));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+    use serde_json::{json, Value};
+
+    #[actix_rt::test]
+    async fn test_index() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body = test::read_response(&mut app, req).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains(r#"fetch("http://localhost:8080/event", {"#));
+    }
+
+    #[actix_rt::test]
+    async fn test_capture_event() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EventStore::open(dir.path().join("events.jsonl")).unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .configure(configure),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/event")
+            .set_json(&json!({
+                "timestamp": 1607700000.0,
+                "kind": "click",
+                "tags": ["ui"]
+            }))
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "got event 1");
+
+        let req = test::TestRequest::post()
+            .uri("/event")
+            .set_json(&json!({ "kind": "click" }))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/event")
+            .set_json(&json!({
+                "timestamp": 12345,
+                "kind": "Click",
+                "tags": ["ui", ""]
+            }))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        let fields: Vec<_> = body["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|err| err["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, ["timestamp", "kind", "tags[1]"]);
+    }
+
+    #[actix_rt::test]
+    async fn test_get_and_list_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EventStore::open(dir.path().join("events.jsonl")).unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .configure(configure),
+        )
+        .await;
+
+        for (timestamp, kind) in &[
+            (1607700000.0, "click"),
+            (1607700001.0, "view"),
+            (1607700002.0, "click"),
+            (1607700003.0, "click"),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/event")
+                .set_json(&json!({
+                    "timestamp": timestamp,
+                    "kind": kind,
+                    "tags": ["ui"]
+                }))
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+
+        let req = test::TestRequest::get().uri("/event/2").to_request();
+        let body: Value = test::read_response_json(&mut app, req).await;
+        assert_eq!(body["kind"], "view");
+
+        let req = test::TestRequest::get().uri("/event/5").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::get()
+            .uri("/event?kind=click&tag=ui&limit=2")
+            .to_request();
+        let body: Value = test::read_response_json(&mut app, req).await;
+        assert_eq!(body["events"][0]["id"], 1);
+        assert_eq!(body["events"][1]["id"], 3);
+        assert_eq!(body["next_cursor"], 3);
+
+        let req = test::TestRequest::get()
+            .uri("/event?kind=click&limit=2&cursor=3")
+            .to_request();
+        let body: Value = test::read_response_json(&mut app, req).await;
+        assert_eq!(body["events"][0]["id"], 4);
+        assert_eq!(body["next_cursor"], Value::Null);
+
+        let req = test::TestRequest::get()
+            .uri("/event?from=1607700001&to=1607700003")
+            .to_request();
+        let body: Value = test::read_response_json(&mut app, req).await;
+        assert_eq!(body["events"].as_array().unwrap().len(), 2);
+
+        let req = test::TestRequest::get().uri("/event?limit=0").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
@@ -0,0 +1,255 @@
+//! Append-only event log: one JSON event per line, ids counting up from 1.
+//! All events are kept in memory as well, so only appends touch the disk.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+
+use super::Event;
+
+pub struct EventStore {
+    log: Mutex<Log>,
+    events: RwLock<Vec<Event>>,
+}
+
+struct Log {
+    file: File,
+    len: u64,
+}
+
+/// Conditions an event has to meet to be listed, all of them optional
+#[derive(Debug, Default)]
+pub struct Filter {
+    pub kind: Option<String>,
+    pub tag: Option<String>,
+    /// Earliest timestamp, inclusive
+    pub from: Option<f64>,
+    /// Latest timestamp, exclusive
+    pub to: Option<f64>,
+}
+
+impl Filter {
+    fn matches(&self, evt: &Event) -> bool {
+        let kind = match &self.kind {
+            Some(kind) => evt.kind == *kind,
+            None => true,
+        };
+        let tag = match &self.tag {
+            Some(tag) => evt.tags.contains(tag),
+            None => true,
+        };
+        let from = match self.from {
+            Some(from) => evt.timestamp >= from,
+            None => true,
+        };
+        let to = match self.to {
+            Some(to) => evt.timestamp < to,
+            None => true,
+        };
+
+        kind && tag && from && to
+    }
+}
+
+pub struct Page {
+    pub events: Vec<Event>,
+    /// Id of the last event, pass it as `after` to get the next page.
+    /// `None` on the last page.
+    pub next: Option<i32>,
+}
+
+impl EventStore {
+    /// Opens or creates the log at `path`. A last line that was only
+    /// partly written before a crash is dropped.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<EventStore> {
+        let path = path.as_ref();
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+
+        let mut events = Vec::new();
+        let mut len = 0;
+        for line in contents.split_inclusive(|&b| b == b'\n') {
+            // only the last line can lack its newline
+            if !line.ends_with(b"\n") {
+                break;
+            }
+            let evt: Event = serde_json::from_slice(line).map_err(|err| {
+                io::Error::new(io::ErrorKind::InvalidData, err)
+            })?;
+            events.push(evt);
+            len += line.len();
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        if len < contents.len() {
+            file.set_len(len as u64)?;
+        }
+
+        Ok(EventStore {
+            log: Mutex::new(Log {
+                file,
+                len: len as u64,
+            }),
+            events: RwLock::new(events),
+        })
+    }
+
+    /// Stores `evt` under the next id and returns it with the id set. This
+    /// waits for the disk, so call it from `web::block`.
+    pub fn append(&self, mut evt: Event) -> io::Result<Event> {
+        // writers are serialized by the log, readers only wait for `push`
+        let mut log = self.log.lock().unwrap();
+
+        let last = self.events.read().unwrap().last().and_then(|evt| evt.id);
+        evt.id = Some(last.unwrap_or(0) + 1);
+
+        let mut line = serde_json::to_vec(&evt)?;
+        line.push(b'\n');
+
+        let written =
+            log.file.write_all(&line).and_then(|_| log.file.sync_data());
+        if let Err(err) = written {
+            // drop what made it to the file, so the next line starts fresh
+            let _ = log.file.set_len(log.len);
+            return Err(err);
+        }
+        log.len += line.len() as u64;
+
+        self.events.write().unwrap().push(evt.clone());
+        Ok(evt)
+    }
+
+    pub fn get(&self, id: i32) -> Option<Event> {
+        let events = self.events.read().unwrap();
+        events
+            .binary_search_by_key(&Some(id), |evt| evt.id)
+            .ok()
+            .map(|idx| events[idx].clone())
+    }
+
+    /// Up to `limit` matching events with an id above `after`, oldest
+    /// first.
+    pub fn list(
+        &self,
+        filter: &Filter,
+        after: Option<i32>,
+        limit: usize,
+    ) -> Page {
+        let events = self.events.read().unwrap();
+        let start = after.map_or(0, |after| {
+            events.partition_point(|evt| evt.id <= Some(after))
+        });
+
+        let mut matching = events[start..]
+            .iter()
+            .filter(|evt| filter.matches(evt))
+            .take(limit + 1)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let next = if matching.len() > limit {
+            matching.truncate(limit);
+            matching.last().and_then(|evt| evt.id)
+        } else {
+            None
+        };
+
+        Page {
+            events: matching,
+            next,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(timestamp: f64, kind: &str, tags: &[&str]) -> Event {
+        Event {
+            id: None,
+            timestamp,
+            kind: kind.to_owned(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+
+    fn ids(page: &Page) -> Vec<i32> {
+        page.events.iter().map(|evt| evt.id.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_append_and_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+
+        let store = EventStore::open(&path).unwrap();
+        assert_eq!(
+            store.append(event(1.0, "click", &[])).unwrap().id,
+            Some(1)
+        );
+        assert_eq!(store.append(event(2.0, "view", &[])).unwrap().id, Some(2));
+        drop(store);
+
+        // a crash in the middle of a write leaves a torn last line
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"id":3,"timest"#).unwrap();
+        drop(file);
+
+        let store = EventStore::open(&path).unwrap();
+        assert_eq!(store.get(2).unwrap().kind, "view");
+        assert!(store.get(3).is_none());
+        assert_eq!(
+            store.append(event(3.0, "click", &[])).unwrap().id,
+            Some(3)
+        );
+        drop(store);
+
+        let store = EventStore::open(&path).unwrap();
+        assert_eq!(ids(&store.list(&Filter::default(), None, 10)), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_corrupt_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        fs::write(&path, "not json\n{}\n").unwrap();
+
+        let err = EventStore::open(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_filter_and_paginate() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EventStore::open(dir.path().join("events.jsonl")).unwrap();
+        for i in 0..10 {
+            let kind = if i % 2 == 0 { "click" } else { "view" };
+            let tags: &[&str] = if i % 3 == 0 { &["ui"] } else { &[] };
+            store.append(event(i as f64, kind, tags)).unwrap();
+        }
+
+        let clicks = Filter {
+            kind: Some(String::from("click")),
+            ..Filter::default()
+        };
+        let page = store.list(&clicks, None, 2);
+        assert_eq!(ids(&page), [1, 3]);
+        let page = store.list(&clicks, page.next, 2);
+        assert_eq!(ids(&page), [5, 7]);
+        let page = store.list(&clicks, page.next, 2);
+        assert_eq!(ids(&page), [9]);
+        assert_eq!(page.next, None);
+
+        let filter = Filter {
+            tag: Some(String::from("ui")),
+            from: Some(1.0),
+            to: Some(9.0),
+            ..Filter::default()
+        };
+        assert_eq!(ids(&store.list(&filter, None, 10)), [4, 7]);
+    }
+}
@@ -51,3 +51,38 @@ async fn main() -> std::io::Result<()> {
     .await
 }
 // </arc>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+
+    #[actix_rt::test]
+    async fn test_counts() {
+        let global_count = Arc::new(AtomicUsize::new(0));
+        let app = || {
+            App::new()
+                .data(AppState {
+                    local_count: Cell::new(0),
+                    global_count: global_count.clone(),
+                })
+                .service(show_count)
+                .service(add_one)
+        };
+        // like two workers
+        let mut first = test::init_service(app()).await;
+        let mut second = test::init_service(app()).await;
+
+        let req = test::TestRequest::get().uri("/add").to_request();
+        let body = test::read_response(&mut first, req).await;
+        assert_eq!(body, "global_count: 1\nlocal_count: 1");
+
+        let req = test::TestRequest::get().uri("/add").to_request();
+        let body = test::read_response(&mut second, req).await;
+        assert_eq!(body, "global_count: 2\nlocal_count: 1");
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body = test::read_response(&mut first, req).await;
+        assert_eq!(body, "global_count: 2\nlocal_count: 1");
+    }
+}
@@ -0,0 +1,53 @@
+pub mod handlers_arc;
+// <data>
+use actix_web::{web, Responder};
+use std::cell::Cell;
+
+struct AppState {
+    count: Cell<usize>,
+}
+
+async fn show_count(data: web::Data<AppState>) -> impl Responder {
+    format!("count: {}", data.count.get())
+}
+
+async fn add_one(data: web::Data<AppState>) -> impl Responder {
+    let count = data.count.get();
+    data.count.set(count + 1);
+
+    format!("count: {}", data.count.get())
+}
+
+/// Every worker calls this for its own `App`, so each gets a fresh counter
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.data(AppState {
+        count: Cell::new(0),
+    })
+    .route("/", web::to(show_count))
+    .route("/add", web::to(add_one));
+}
+// </data>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn test_data() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "count: 0");
+
+        let req = test::TestRequest::get().uri("/add").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "count: 1");
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "count: 1");
+    }
+}
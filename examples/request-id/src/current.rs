@@ -0,0 +1,71 @@
+//! Values that are current while a request is handled, like its id.
+//!
+//! A middleware makes a value current while it calls the next service and
+//! while the response future is polled, so that code without access to the
+//! request can still read it. There is one current value per type.
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::Future;
+
+thread_local! {
+    static CURRENT: RefCell<HashMap<TypeId, Box<dyn Any>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// The current `T` on this thread
+pub fn get<T: Clone + 'static>() -> Option<T> {
+    CURRENT.with(|current| {
+        let current = current.borrow();
+        current.get(&TypeId::of::<T>())?.downcast_ref().cloned()
+    })
+}
+
+/// Makes `value` the current `T` while `f` runs
+pub fn scope<T: Clone + 'static, R>(value: &T, f: impl FnOnce() -> R) -> R {
+    let key = TypeId::of::<T>();
+    let outer = CURRENT.with(|current| {
+        current.borrow_mut().insert(key, Box::new(value.clone()))
+    });
+    let result = f();
+    CURRENT.with(|current| {
+        let mut current = current.borrow_mut();
+        match outer {
+            Some(outer) => current.insert(key, outer),
+            None => current.remove(&key),
+        }
+    });
+    result
+}
+
+/// Polls a future with `value` as the current `T`
+pub struct Scoped<T, F> {
+    value: T,
+    fut: Pin<Box<F>>,
+}
+
+impl<T, F> Scoped<T, F> {
+    pub fn new(value: T, fut: F) -> Self {
+        Scoped {
+            value,
+            fut: Box::pin(fut),
+        }
+    }
+}
+
+impl<T: Clone + Unpin + 'static, F: Future> Future for Scoped<T, F> {
+    type Output = F::Output;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<F::Output> {
+        let this = &mut *self;
+        let fut = &mut this.fut;
+        scope(&this.value, || fut.as_mut().poll(cx))
+    }
+}
@@ -0,0 +1,411 @@
+//! Request ids and structured access logs for actix-web.
+//!
+//! [`RequestLog`] gives every request an id, taken from its `X-Request-Id`
+//! header or generated, and logs one JSON [`Record`] per request. Handlers
+//! get the id with the [`RequestId`] extractor.
+
+pub mod current;
+
+// <request-id>
+use std::fmt;
+
+use actix_web::dev::Payload;
+use actix_web::http::HeaderValue;
+use actix_web::{error, Error, FromRequest, HttpMessage, HttpRequest};
+use futures::future::{ready, Ready};
+use uuid::Uuid;
+
+pub const HEADER: &str = "x-request-id";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    fn generate() -> Self {
+        RequestId(Uuid::new_v4().to_string())
+    }
+
+    /// Takes over the id of a client or proxy, unless it could garble a log
+    /// line or a header.
+    fn from_header(value: &HeaderValue) -> Option<Self> {
+        let value = value.to_str().ok()?;
+        let valid = !value.is_empty()
+            && value.len() <= 128
+            && value
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"-_.:".contains(&b));
+
+        if valid {
+            Some(RequestId(value.to_owned()))
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The id of the request being handled on this thread, for code without
+    /// access to the request, like `ResponseError::error_response()`.
+    /// Closures passed to `web::block` run on another thread, so they have
+    /// to be handed the id.
+    pub fn current() -> Option<RequestId> {
+        current::get()
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromRequest for RequestId {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(req.extensions().get::<RequestId>().cloned().ok_or_else(|| {
+            error::ErrorInternalServerError("RequestLog is not registered")
+        }))
+    }
+}
+// </request-id>
+
+// <request-log>
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use actix_web::body::{Body, BodySize, MessageBody, ResponseBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::HeaderName;
+use actix_web::web::Bytes;
+use futures::future::{ok, Future};
+use serde::Serialize;
+
+/// One line of the access log
+#[derive(Clone, Debug, Serialize)]
+pub struct Record {
+    pub request_id: String,
+    pub method: String,
+    pub path: String,
+    /// The pattern of the matched route, like `/users/{id}`
+    pub pattern: Option<String>,
+    pub status: u16,
+    pub latency_ms: f64,
+    pub bytes: u64,
+    pub peer: Option<String>,
+    /// The error the response was created from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+type Sink = dyn Fn(&Record) + Send + Sync;
+
+/// Logs every record as JSON, with target `request_log`. Server errors are
+/// logged at `ERROR` level, everything else at `INFO`.
+fn log_record(record: &Record) {
+    // plain strings and numbers always serialize
+    let json = serde_json::to_string(record).unwrap();
+    if record.status >= 500 {
+        log::error!(target: "request_log", "{}", json);
+    } else {
+        log::info!(target: "request_log", "{}", json);
+    }
+}
+
+/// Assigns or propagates `X-Request-Id` and logs a [`Record`] per request,
+/// once its body has been sent.
+#[derive(Clone)]
+pub struct RequestLog {
+    sink: Arc<Sink>,
+}
+
+impl Default for RequestLog {
+    fn default() -> Self {
+        RequestLog {
+            sink: Arc::new(log_record),
+        }
+    }
+}
+
+impl RequestLog {
+    pub fn new() -> Self {
+        RequestLog::default()
+    }
+
+    /// Hands the records to `sink` instead of the `log` crate.
+    pub fn sink<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(&Record) + Send + Sync + 'static,
+    {
+        self.sink = Arc::new(sink);
+        self
+    }
+}
+
+impl<S, B> Transform<S> for RequestLog
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: MessageBody + Unpin + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestLogMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestLogMiddleware {
+            service,
+            sink: self.sink.clone(),
+        })
+    }
+}
+
+pub struct RequestLogMiddleware<S> {
+    service: S,
+    sink: Arc<Sink>,
+}
+
+impl<S, B> Service for RequestLogMiddleware<S>
+where
+    S: Service<
+        Request = ServiceRequest,
+        Response = ServiceResponse<B>,
+        Error = Error,
+    >,
+    S::Future: 'static,
+    B: MessageBody + Unpin + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let id = req
+            .headers()
+            .get(HEADER)
+            .and_then(RequestId::from_header)
+            .unwrap_or_else(RequestId::generate);
+        req.extensions_mut().insert(id.clone());
+
+        let mut record = Record {
+            request_id: id.to_string(),
+            method: req.method().to_string(),
+            path: req.path().to_owned(),
+            pattern: None,
+            status: 0,
+            latency_ms: 0.0,
+            bytes: 0,
+            peer: req.peer_addr().map(|addr| addr.to_string()),
+            error: None,
+        };
+        let sink = self.sink.clone();
+
+        let service = &mut self.service;
+        let fut = current::scope(&id, || service.call(req));
+        let fut = current::Scoped::new(id.clone(), fut);
+
+        Box::pin(async move {
+            let mut res = match fut.await {
+                Ok(res) => res,
+                Err(err) => {
+                    record.status =
+                        err.as_response_error().status_code().as_u16();
+                    record.error = Some(err.to_string());
+                    record.latency_ms = millis(start);
+                    sink(&record);
+                    return Err(err);
+                }
+            };
+
+            // the id only contains characters that are valid in a header
+            let value = HeaderValue::from_str(id.as_str()).unwrap();
+            res.headers_mut()
+                .insert(HeaderName::from_static(HEADER), value);
+
+            record.pattern = res.request().match_pattern();
+            record.status = res.status().as_u16();
+            record.error = res.response().error().map(|err| err.to_string());
+
+            Ok(res.map_body(move |_, body| {
+                ResponseBody::Other(Body::from_message(LoggedBody {
+                    body,
+                    start,
+                    record: Some(record),
+                    sink,
+                }))
+            }))
+        })
+    }
+}
+
+fn millis(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+/// Counts the bytes of a body and logs the record when it is done
+struct LoggedBody<B> {
+    body: ResponseBody<B>,
+    start: Instant,
+    record: Option<Record>,
+    sink: Arc<Sink>,
+}
+
+impl<B: MessageBody + Unpin> MessageBody for LoggedBody<B> {
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Error>>> {
+        let this = &mut *self;
+        let chunk = Pin::new(&mut this.body).poll_next(cx);
+        if let (Poll::Ready(Some(Ok(bytes))), Some(record)) =
+            (&chunk, this.record.as_mut())
+        {
+            record.bytes += bytes.len() as u64;
+        }
+        chunk
+    }
+}
+
+impl<B> Drop for LoggedBody<B> {
+    fn drop(&mut self) {
+        if let Some(mut record) = self.record.take() {
+            record.latency_ms = millis(self.start);
+            (self.sink)(&record);
+        }
+    }
+}
+// </request-log>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+    use derive_more::{Display, Error};
+    use std::sync::Mutex;
+
+    fn collect() -> (RequestLog, Arc<Mutex<Vec<serde_json::Value>>>) {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = records.clone();
+        let log = RequestLog::new().sink(move |record| {
+            let json = serde_json::to_value(record).unwrap();
+            sink.lock().unwrap().push(json);
+        });
+        (log, records)
+    }
+
+    async fn echo(id: RequestId) -> String {
+        id.to_string()
+    }
+
+    #[actix_rt::test]
+    async fn test_request_id() {
+        let (log, records) = collect();
+        let mut app = test::init_service(
+            App::new()
+                .wrap(log)
+                .route("/users/{id}", web::get().to(echo)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/users/42?tab=posts")
+            .header(HEADER, "req-42")
+            .peer_addr("10.0.0.1:4711".parse().unwrap())
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.headers().get(HEADER).unwrap(), "req-42");
+        assert_eq!(test::read_body(resp).await, "req-42");
+
+        let record = records.lock().unwrap().pop().unwrap();
+        assert_eq!(record["request_id"], "req-42");
+        assert_eq!(record["method"], "GET");
+        assert_eq!(record["path"], "/users/42");
+        assert_eq!(record["pattern"], "/users/{id}");
+        assert_eq!(record["status"], 200);
+        assert_eq!(record["bytes"], 6);
+        assert_eq!(record["peer"], "10.0.0.1:4711");
+        assert!(record["latency_ms"].as_f64().unwrap() >= 0.0);
+        assert!(record.get("error").is_none());
+
+        // ids that do not look like one are replaced
+        let req = test::TestRequest::get()
+            .uri("/users/42")
+            .header(HEADER, "{\"injected\": true}")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let id = resp.headers().get(HEADER).unwrap().clone();
+        assert_eq!(id.len(), 36);
+        assert_eq!(test::read_body(resp).await, id.as_bytes());
+    }
+
+    #[derive(Debug, Display, Error)]
+    #[display(fmt = "database is down")]
+    struct DbError;
+
+    impl error::ResponseError for DbError {
+        fn error_response(&self) -> HttpResponse {
+            let id = RequestId::current().unwrap();
+            HttpResponse::InternalServerError().body(format!("see {}", id))
+        }
+    }
+
+    async fn failing() -> Result<String, DbError> {
+        Err(DbError)
+    }
+
+    #[actix_rt::test]
+    async fn test_errors() {
+        let (log, records) = collect();
+        let mut app = test::init_service(
+            App::new()
+                .wrap(log)
+                .route("/failing", web::get().to(failing)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/failing")
+            .header(HEADER, "req-1")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(test::read_body(resp).await, "see req-1");
+        assert!(RequestId::current().is_none());
+
+        let req = test::TestRequest::get().uri("/missing").to_request();
+        test::read_body(test::call_service(&mut app, req).await).await;
+
+        let records = records.lock().unwrap();
+        assert_eq!(records[0]["status"], 500);
+        assert_eq!(records[0]["error"], "database is down");
+        assert_eq!(records[1]["status"], 404);
+        assert_eq!(records[1]["pattern"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,36 @@
+// <request-routing>
+use actix_web::{web, HttpRequest, Responder};
+
+async fn index(_req: HttpRequest) -> impl Responder {
+    "Hello from the index page."
+}
+
+async fn hello(path: web::Path<String>) -> impl Responder {
+    format!("Hello {}!", &path)
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/").to(index))
+        .service(web::resource("/{name}").to(hello));
+}
+// </request-routing>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn test_routing() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Hello from the index page.");
+
+        let req = test::TestRequest::get().uri("/Ferris").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Hello Ferris!");
+    }
+}
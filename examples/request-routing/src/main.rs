@@ -1,23 +1,10 @@
-// <request-routing>
-use actix_web::{web, App, HttpRequest, HttpServer, Responder};
-
-async fn index(_req: HttpRequest) -> impl Responder {
-    "Hello from the index page."
-}
-
-async fn hello(path: web::Path<String>) -> impl Responder {
-    format!("Hello {}!", &path)
-}
+use actix_web::{App, HttpServer};
+use request_routing::configure;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| {
-        App::new()
-            .service(web::resource("/").to(index))
-            .service(web::resource("/{name}").to(hello))
-    })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+    HttpServer::new(|| App::new().configure(configure))
+        .bind("127.0.0.1:8080")?
+        .run()
+        .await
 }
-// </request-routing>
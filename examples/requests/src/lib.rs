@@ -0,0 +1,23 @@
+pub mod manual;
+pub mod multipart;
+pub mod streaming;
+pub mod urlencoded;
+
+// <json-request>
+use actix_web::{web, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Info {
+    username: String,
+}
+
+/// extract `Info` using serde
+async fn index(info: web::Json<Info>) -> Result<String> {
+    Ok(format!("Welcome {}!", info.username))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/", web::post().to(index));
+}
+// </json-request>
@@ -1,27 +1,10 @@
-pub mod manual;
-pub mod multipart;
-pub mod streaming;
-pub mod urlencoded;
-
-// <json-request>
-use actix_web::{web, App, HttpServer, Result};
-use serde::Deserialize;
-
-#[derive(Deserialize)]
-struct Info {
-    username: String,
-}
-
-/// extract `Info` using serde
-async fn index(info: web::Json<Info>) -> Result<String> {
-    Ok(format!("Welcome {}!", info.username))
-}
+use actix_web::{App, HttpServer};
+use requests::configure;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| App::new().route("/", web::post().to(index)))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
-// </json-request>
@@ -34,8 +34,12 @@ async fn index_manual(mut payload: web::Payload) -> Result<HttpResponse, Error>
 async fn main() -> std::io::Result<()> {
     use actix_web::HttpServer;
 
-    HttpServer::new(|| App::new().service(index_manual))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index_manual);
+}
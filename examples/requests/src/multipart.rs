@@ -1,25 +1,459 @@
 // <multipart>
-// use actix_web::{error, Error, HttpRequest, HttpResponse};
-// use futures::Future;
-
-// pub fn index(req: HttpRequest) -> Box<Future<Item = HttpResponse, Error = Error>> {
-//     // get multipart and iterate over multipart items
-//     req.multipart().and_then(|item| match item {
-//         multipart::MultipartItem::Field(field) => {
-//             println!(
-//                 "==== FIELD ==== {:?} {:?}",
-//                 field.headers(),
-//                 field.content_type()
-//             );
-//             Either::A(
-//                 field
-//                     .map(|chunk| {
-//                         println!("-- CHUNK: \n{}", std::str::from_utf8(&chunk).unwrap());
-//                     })
-//                     .fold((), |_, _| result(Ok(()))),
-//             )
-//         }
-//         multipart::MultipartItem::Nested(mp) => Either::B(result(Ok(()))),
-//     })
-// }
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use actix_multipart::{Field, Multipart};
+use actix_web::{error, web, Error, HttpResponse};
+use futures::{StreamExt, TryStreamExt};
+use serde::Serialize;
+
+/// Where uploads are written and how much a single request may store.
+pub struct UploadConfig {
+    pub dir: PathBuf,
+    pub max_field_size: usize,
+    pub max_total_size: usize,
+    pub allowed_types: Vec<mime::Mime>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Manifest {
+    pub fields: Vec<TextField>,
+    pub files: Vec<StoredFile>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TextField {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StoredFile {
+    pub field: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size: usize,
+    pub path: PathBuf,
+}
+
+async fn upload(
+    payload: Multipart,
+    config: web::Data<UploadConfig>,
+) -> Result<HttpResponse, Error> {
+    let mut manifest = Manifest::default();
+
+    match store_fields(payload, &config, &mut manifest).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(manifest)),
+        Err(err) => {
+            // don't leave half of a rejected upload lying around
+            for file in manifest.files {
+                let _ =
+                    web::block(move || std::fs::remove_file(file.path)).await;
+            }
+            Err(err)
+        }
+    }
+}
+
+async fn store_fields(
+    mut payload: Multipart,
+    config: &UploadConfig,
+    manifest: &mut Manifest,
+) -> Result<(), Error> {
+    let mut total = 0;
+
+    // iterate over multipart stream
+    while let Some(field) = payload.try_next().await? {
+        let disposition = field.content_disposition().ok_or_else(|| {
+            error::ErrorBadRequest(
+                "multipart field has no content disposition",
+            )
+        })?;
+        let name = disposition
+            .get_name()
+            .ok_or_else(|| {
+                error::ErrorBadRequest("multipart field has no name")
+            })?
+            .to_owned();
+
+        match disposition.get_filename() {
+            None => {
+                let value =
+                    read_text(field, &name, config, &mut total).await?;
+                manifest.fields.push(TextField { name, value });
+            }
+            Some(filename) => {
+                let content_type = field.content_type().clone();
+                // parameters like `charset` don't matter here
+                let allowed = config
+                    .allowed_types
+                    .iter()
+                    .any(|t| t.essence_str() == content_type.essence_str());
+                if !allowed {
+                    return Err(error::ErrorUnsupportedMediaType(format!(
+                        "content type `{}` is not allowed for field `{}`",
+                        content_type, name
+                    )));
+                }
+
+                let filename = sanitize_filename(filename);
+                let (file, path) = create_file(&config.dir, &filename).await?;
+
+                // record the file before writing so it is cleaned up on error
+                manifest.files.push(StoredFile {
+                    field: name,
+                    filename,
+                    content_type: content_type.to_string(),
+                    size: 0,
+                    path,
+                });
+
+                let size = write_file(field, file, config, &mut total).await?;
+                manifest.files.last_mut().unwrap().size = size;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn read_text(
+    mut field: Field,
+    name: &str,
+    config: &UploadConfig,
+    total: &mut usize,
+) -> Result<String, Error> {
+    let mut buf = web::BytesMut::new();
+
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk?;
+        check_limits(
+            name,
+            buf.len() + chunk.len(),
+            chunk.len(),
+            config,
+            total,
+        )?;
+        buf.extend_from_slice(&chunk);
+    }
+
+    String::from_utf8(buf.to_vec()).map_err(|_| {
+        error::ErrorBadRequest(format!("field `{}` is not valid UTF-8", name))
+    })
+}
+
+/// Creates a new file with a random prefix, so that uploads with the same
+/// name never overwrite each other.
+async fn create_file(
+    dir: &Path,
+    filename: &str,
+) -> Result<(File, PathBuf), Error> {
+    let dir = dir.to_owned();
+    let suffix = format!("-{}", filename);
+
+    // creating files is blocking operation, use threadpool
+    let created = web::block(move || {
+        tempfile::Builder::new()
+            .prefix("")
+            .suffix(&suffix)
+            .tempfile_in(dir)?
+            .keep()
+            .map_err(|err| err.error)
+    })
+    .await?;
+
+    Ok(created)
+}
+
+async fn write_file(
+    mut field: Field,
+    mut f: File,
+    config: &UploadConfig,
+    total: &mut usize,
+) -> Result<usize, Error> {
+    let name = field
+        .content_disposition()
+        .and_then(|cd| cd.get_name().map(str::to_owned))
+        .unwrap_or_default();
+    let mut size = 0;
+
+    // Field in turn is stream of *Bytes* object
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk?;
+        size += chunk.len();
+        check_limits(&name, size, chunk.len(), config, total)?;
+
+        // filesystem operations are blocking, we have to use threadpool
+        f = web::block(move || f.write_all(&chunk).map(|_| f)).await?;
+    }
+
+    Ok(size)
+}
+
+fn check_limits(
+    name: &str,
+    field_size: usize,
+    chunk_size: usize,
+    config: &UploadConfig,
+    total: &mut usize,
+) -> Result<(), Error> {
+    *total += chunk_size;
+
+    if field_size > config.max_field_size {
+        return Err(error::ErrorPayloadTooLarge(format!(
+            "field `{}` is larger than {} bytes",
+            name, config.max_field_size
+        )));
+    }
+
+    if *total > config.max_total_size {
+        return Err(error::ErrorPayloadTooLarge(format!(
+            "upload is larger than {} bytes",
+            config.max_total_size
+        )));
+    }
+
+    Ok(())
+}
+
+/// Strips any directory components and unusual characters from a
+/// client-supplied file name.
+fn sanitize_filename(filename: &str) -> String {
+    let name: String = Path::new(filename)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        .collect();
+
+    if name.trim_matches('.').is_empty() {
+        String::from("upload")
+    } else {
+        name
+    }
+}
 // </multipart>
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    use actix_web::{App, HttpServer};
+
+    let dir = std::env::temp_dir().join("actix-uploads");
+    std::fs::create_dir_all(&dir)?;
+
+    let config = web::Data::new(UploadConfig {
+        dir,
+        max_field_size: 1024 * 1024,
+        max_total_size: 4 * 1024 * 1024,
+        allowed_types: vec![
+            mime::IMAGE_PNG,
+            mime::IMAGE_JPEG,
+            mime::TEXT_PLAIN,
+        ],
+    });
+
+    HttpServer::new(move || {
+        App::new().app_data(config.clone()).configure(configure)
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await
+}
+
+/// `upload` also needs a `web::Data<UploadConfig>`, provided by the `App`
+/// or `Scope` this is mounted in
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/upload", web::post().to(upload));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::dev::Service;
+    use actix_web::{http, test, App};
+
+    const BOUNDARY: &str = "----actix-test-boundary";
+
+    fn text_part(name: &str, value: &str) -> String {
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+            BOUNDARY, name, value
+        )
+    }
+
+    fn file_part(name: &str, filename: &str, ct: &str, data: &str) -> String {
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"; \
+             filename=\"{}\"\r\nContent-Type: {}\r\n\r\n{}\r\n",
+            BOUNDARY, name, filename, ct, data
+        )
+    }
+
+    fn multipart_req(parts: &[String]) -> test::TestRequest {
+        let body = format!("{}--{}--\r\n", parts.concat(), BOUNDARY);
+
+        test::TestRequest::post()
+            .uri("/upload")
+            .header(
+                http::header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", BOUNDARY),
+            )
+            .set_payload(body)
+    }
+
+    fn config(dir: &Path) -> web::Data<UploadConfig> {
+        web::Data::new(UploadConfig {
+            dir: dir.to_owned(),
+            max_field_size: 16,
+            max_total_size: 32,
+            allowed_types: vec![mime::TEXT_PLAIN],
+        })
+    }
+
+    fn stored_files(dir: &Path) -> usize {
+        std::fs::read_dir(dir).unwrap().count()
+    }
+
+    #[actix_rt::test]
+    async fn test_upload_text_and_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(config(dir.path()))
+                .route("/upload", web::post().to(upload)),
+        )
+        .await;
+
+        let req = multipart_req(&[
+            text_part("title", "notes"),
+            file_part("doc", "../../etc/notes.txt", "text/plain", "hello"),
+        ])
+        .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let manifest: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(manifest["fields"][0]["name"], "title");
+        assert_eq!(manifest["fields"][0]["value"], "notes");
+        assert_eq!(manifest["files"][0]["field"], "doc");
+        assert_eq!(manifest["files"][0]["filename"], "notes.txt");
+        assert_eq!(manifest["files"][0]["content_type"], "text/plain");
+        assert_eq!(manifest["files"][0]["size"], 5);
+
+        let path = Path::new(manifest["files"][0]["path"].as_str().unwrap());
+        assert_eq!(path.parent(), Some(dir.path()));
+        assert!(path.to_str().unwrap().ends_with("-notes.txt"));
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "hello");
+    }
+
+    #[actix_rt::test]
+    async fn test_upload_same_filename() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(config(dir.path()))
+                .route("/upload", web::post().to(upload)),
+        )
+        .await;
+
+        // both requests are in flight at the same time
+        let first = app.call(
+            multipart_req(&[file_part(
+                "doc",
+                "notes.txt",
+                "text/plain; charset=utf-8",
+                "first",
+            )])
+            .to_request(),
+        );
+        let second = app.call(
+            multipart_req(&[file_part(
+                "doc",
+                "notes.txt",
+                "text/plain; charset=utf-8",
+                "second",
+            )])
+            .to_request(),
+        );
+        let (first, second) = futures::join!(first, second);
+
+        let mut contents = Vec::new();
+        for resp in [first.unwrap(), second.unwrap()] {
+            assert_eq!(resp.status(), http::StatusCode::OK);
+            let manifest: serde_json::Value = test::read_body_json(resp).await;
+            let path = manifest["files"][0]["path"].as_str().unwrap();
+            contents.push(std::fs::read_to_string(path).unwrap());
+        }
+        contents.sort();
+        assert_eq!(contents, ["first", "second"]);
+        assert_eq!(stored_files(dir.path()), 2);
+    }
+
+    #[actix_rt::test]
+    async fn test_upload_rejects_content_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(config(dir.path()))
+                .route("/upload", web::post().to(upload)),
+        )
+        .await;
+
+        let req = multipart_req(&[file_part(
+            "doc",
+            "run.sh",
+            "application/x-sh",
+            "rm -rf /",
+        )])
+        .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(stored_files(dir.path()), 0);
+    }
+
+    #[actix_rt::test]
+    async fn test_upload_field_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(config(dir.path()))
+                .route("/upload", web::post().to(upload)),
+        )
+        .await;
+
+        let req = multipart_req(&[file_part(
+            "doc",
+            "big.txt",
+            "text/plain",
+            "this is longer than sixteen bytes",
+        )])
+        .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(stored_files(dir.path()), 0);
+    }
+
+    #[actix_rt::test]
+    async fn test_upload_total_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(config(dir.path()))
+                .route("/upload", web::post().to(upload)),
+        )
+        .await;
+
+        // every field fits on its own, but not all of them together
+        let req = multipart_req(&[
+            file_part("a", "a.txt", "text/plain", "0123456789abcdef"),
+            text_part("b", "0123456789abcdef"),
+            file_part("c", "c.txt", "text/plain", "0123456789"),
+        ])
+        .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(stored_files(dir.path()), 0);
+    }
+}
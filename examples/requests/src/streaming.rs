@@ -19,8 +19,12 @@ async fn index(mut body: web::Payload) -> Result<HttpResponse, Error> {
 async fn main() -> std::io::Result<()> {
     use actix_web::{App, HttpServer};
 
-    HttpServer::new(|| App::new().service(index))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index);
+}
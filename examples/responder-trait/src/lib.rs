@@ -0,0 +1,41 @@
+pub mod negotiated;
+
+// <responder-trait>
+use actix_web::{Error, HttpRequest, HttpResponse, Responder};
+use futures::future::{ready, Ready};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct MyObj {
+    name: &'static str,
+}
+
+// Responder
+impl Responder for MyObj {
+    type Error = Error;
+    type Future = Ready<Result<HttpResponse, Error>>;
+
+    fn respond_to(self, _req: &HttpRequest) -> Self::Future {
+        let body = match serde_json::to_string(&self) {
+            Ok(body) => body,
+            Err(err) => return ready(Err(err.into())),
+        };
+
+        // Create response and set content type
+        ready(Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(body)))
+    }
+}
+
+async fn index() -> impl Responder {
+    MyObj { name: "user" }
+}
+// </responder-trait>
+
+pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
+    use actix_web::web;
+
+    cfg.route("/", web::get().to(index));
+    negotiated::configure(cfg);
+}
@@ -1,38 +1,9 @@
-// <responder-trait>
-use actix_web::{Error, HttpRequest, HttpResponse, Responder};
-use futures::future::{ready, Ready};
-use serde::Serialize;
-
-#[derive(Serialize)]
-struct MyObj {
-    name: &'static str,
-}
-
-// Responder
-impl Responder for MyObj {
-    type Error = Error;
-    type Future = Ready<Result<HttpResponse, Error>>;
-
-    fn respond_to(self, _req: &HttpRequest) -> Self::Future {
-        let body = serde_json::to_string(&self).unwrap();
-
-        // Create response and set content type
-        ready(Ok(HttpResponse::Ok()
-            .content_type("application/json")
-            .body(body)))
-    }
-}
-
-async fn index() -> impl Responder {
-    MyObj { name: "user" }
-}
-// </responder-trait>
+use actix_web::{App, HttpServer};
+use responder_trait::configure;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    use actix_web::{web, App, HttpServer};
-
-    HttpServer::new(|| App::new().route("/", web::get().to(index)))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
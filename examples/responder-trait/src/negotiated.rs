@@ -0,0 +1,365 @@
+// <negotiated>
+use std::fmt;
+
+use actix_web::http::header::{self, Accept, Header, Quality};
+use actix_web::{
+    error, http::StatusCode, HttpRequest, HttpResponse, Responder,
+};
+use futures::future::{ready, Ready};
+use serde::Serialize;
+
+/// Formats a `Negotiated` value can be rendered in, in order of preference
+/// when the client accepts several of them equally.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Cbor,
+    MessagePack,
+    Yaml,
+    UrlEncoded,
+    /// Same rendering as YAML, which reads well in a terminal
+    Text,
+}
+
+impl Format {
+    pub const ALL: [Format; 6] = [
+        Format::Json,
+        Format::Cbor,
+        Format::MessagePack,
+        Format::Yaml,
+        Format::UrlEncoded,
+        Format::Text,
+    ];
+
+    /// Media types that select this format, the first one is sent back
+    fn media_types(self) -> &'static [&'static str] {
+        match self {
+            Format::Json => &["application/json"],
+            Format::Cbor => &["application/cbor"],
+            Format::MessagePack => {
+                &["application/msgpack", "application/x-msgpack"]
+            }
+            Format::Yaml => &["application/yaml", "application/x-yaml"],
+            Format::UrlEncoded => &["application/x-www-form-urlencoded"],
+            Format::Text => &["text/plain"],
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Text => "text/plain; charset=utf-8",
+            format => format.media_types()[0],
+        }
+    }
+
+    pub fn serialize<T: Serialize>(
+        self,
+        value: &T,
+    ) -> Result<Vec<u8>, NegotiationError> {
+        let failed = |err: &dyn fmt::Display| NegotiationError::Serialize {
+            format: self,
+            message: err.to_string(),
+        };
+
+        match self {
+            Format::Json => serde_json::to_vec(value).map_err(|e| failed(&e)),
+            Format::Cbor => serde_cbor::to_vec(value).map_err(|e| failed(&e)),
+            Format::MessagePack => {
+                rmp_serde::to_vec_named(value).map_err(|e| failed(&e))
+            }
+            Format::Yaml | Format::Text => {
+                serde_yaml::to_vec(value).map_err(|e| failed(&e))
+            }
+            Format::UrlEncoded => serde_urlencoded::to_string(value)
+                .map(String::into_bytes)
+                .map_err(|e| failed(&e)),
+        }
+    }
+
+    /// Picks the format the client ranks highest. Without an `Accept`
+    /// header, or with one that cannot be parsed, every format is
+    /// acceptable.
+    pub fn negotiate(req: &HttpRequest) -> Option<Format> {
+        if !req.headers().contains_key(header::ACCEPT) {
+            return Some(Format::Json);
+        }
+        // media ranges that do not parse are left out of the list
+        let accept = match Accept::parse(req) {
+            Ok(accept) if !accept.is_empty() => accept,
+            _ => return Some(Format::Json),
+        };
+
+        let mut best: Option<(Format, Quality)> = None;
+        for &format in Format::ALL.iter() {
+            let quality = format
+                .media_types()
+                .iter()
+                .filter_map(|ct| quality_for(&accept, &ct.parse().unwrap()))
+                .max();
+            if let Some(quality) = quality {
+                // `>` keeps the earlier format on a tie
+                let better = match best {
+                    Some((_, best)) => quality > best,
+                    None => true,
+                };
+                if better {
+                    best = Some((format, quality));
+                }
+            }
+        }
+
+        best.map(|(format, _)| format)
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.media_types()[0])
+    }
+}
+
+/// Quality the client assigned to `offered`, taken from the most specific
+/// matching media range. `None` if it is not acceptable at all.
+pub fn quality_for(accept: &Accept, offered: &mime::Mime) -> Option<Quality> {
+    accept
+        .iter()
+        .filter_map(|range| {
+            let item = &range.item;
+            let specificity = if item.type_() == mime::STAR {
+                0
+            } else if item.type_() != offered.type_() {
+                return None;
+            } else if item.subtype() == mime::STAR {
+                1
+            } else if item.subtype() != offered.subtype() {
+                return None;
+            } else {
+                2
+            };
+            Some((specificity, range.quality))
+        })
+        .max_by_key(|(specificity, _)| *specificity)
+        .map(|(_, quality)| quality)
+        .filter(|quality| *quality > header::q(0))
+}
+
+#[derive(Debug)]
+pub enum NegotiationError {
+    NotAcceptable,
+    Serialize { format: Format, message: String },
+}
+
+impl fmt::Display for NegotiationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NegotiationError::NotAcceptable => {
+                f.write_str(
+                    "none of the accepted media types is available: ",
+                )?;
+                let available: Vec<_> = Format::ALL
+                    .iter()
+                    .map(|format| format.to_string())
+                    .collect();
+                f.write_str(&available.join(", "))
+            }
+            NegotiationError::Serialize { format, message } => {
+                write!(f, "cannot render response as {}: {}", format, message)
+            }
+        }
+    }
+}
+
+impl error::ResponseError for NegotiationError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            NegotiationError::NotAcceptable => StatusCode::NOT_ACCEPTABLE,
+            NegotiationError::Serialize { .. } => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .set_header(header::VARY, "accept")
+            .content_type("text/plain; charset=utf-8")
+            .body(self.to_string())
+    }
+}
+
+/// Serializes `T` in whichever format the request's `Accept` header
+/// prefers.
+pub struct Negotiated<T>(pub T);
+
+impl<T: Serialize> Responder for Negotiated<T> {
+    type Error = NegotiationError;
+    type Future = Ready<Result<HttpResponse, NegotiationError>>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        let response = Format::negotiate(req)
+            .ok_or(NegotiationError::NotAcceptable)
+            .and_then(|format| {
+                let body = format.serialize(&self.0)?;
+                Ok(HttpResponse::Ok()
+                    .set_header(header::VARY, "accept")
+                    .content_type(format.content_type())
+                    .body(body))
+            });
+
+        ready(response)
+    }
+}
+// </negotiated>
+
+// <negotiated-handler>
+use actix_web::{get, web};
+
+#[derive(Serialize)]
+struct Measurement {
+    sensor: &'static str,
+    celsius: f32,
+}
+
+#[get("/measurement")]
+async fn measurement() -> impl Responder {
+    Negotiated(Measurement {
+        sensor: "greenhouse",
+        celsius: 21.5,
+    })
+}
+// </negotiated-handler>
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(measurement);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::dev::ServiceResponse;
+    use actix_web::{test, App};
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Reading {
+        sensor: String,
+        celsius: f32,
+    }
+
+    #[derive(Serialize)]
+    struct Nested {
+        inner: Vec<u8>,
+    }
+
+    #[get("/nested")]
+    async fn nested() -> impl Responder {
+        Negotiated(Nested { inner: vec![1, 2] })
+    }
+
+    async fn get(uri: &str, accept: Option<&str>) -> ServiceResponse {
+        let mut app = test::init_service(
+            App::new().configure(configure).service(nested),
+        )
+        .await;
+
+        let mut req = test::TestRequest::get().uri(uri);
+        if let Some(accept) = accept {
+            req = req.header(header::ACCEPT, accept);
+        }
+        test::call_service(&mut app, req.to_request()).await
+    }
+
+    fn header_of<'a>(resp: &'a ServiceResponse, name: &str) -> &'a str {
+        resp.headers().get(name).unwrap().to_str().unwrap()
+    }
+
+    fn expected() -> Reading {
+        Reading {
+            sensor: String::from("greenhouse"),
+            celsius: 21.5,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_default_is_json() {
+        let resp = get("/measurement", None).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(header_of(&resp, "content-type"), "application/json");
+        assert_eq!(header_of(&resp, "vary"), "accept");
+
+        let body = test::read_body(resp).await;
+        let reading: Reading = serde_json::from_slice(&body).unwrap();
+        assert_eq!(reading, expected());
+
+        let resp = get("/measurement", Some("json")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(header_of(&resp, "content-type"), "application/json");
+    }
+
+    #[actix_rt::test]
+    async fn test_binary_formats() {
+        let accept = "application/json;q=0.5, application/cbor";
+        let resp = get("/measurement", Some(accept)).await;
+        assert_eq!(header_of(&resp, "content-type"), "application/cbor");
+        let body = test::read_body(resp).await;
+        let reading: Reading = serde_cbor::from_slice(&body).unwrap();
+        assert_eq!(reading, expected());
+
+        let resp = get("/measurement", Some("application/x-msgpack")).await;
+        assert_eq!(header_of(&resp, "content-type"), "application/msgpack");
+        let body = test::read_body(resp).await;
+        let reading: Reading = rmp_serde::from_slice(&body).unwrap();
+        assert_eq!(reading, expected());
+    }
+
+    #[actix_rt::test]
+    async fn test_text_formats() {
+        let resp = get("/measurement", Some("text/*")).await;
+        assert_eq!(
+            header_of(&resp, "content-type"),
+            "text/plain; charset=utf-8"
+        );
+        let body = test::read_body(resp).await;
+        let reading: Reading = serde_yaml::from_slice(&body).unwrap();
+        assert_eq!(reading, expected());
+
+        let accept = "application/x-www-form-urlencoded";
+        let resp = get("/measurement", Some(accept)).await;
+        let body = test::read_body(resp).await;
+        assert_eq!(body, "sensor=greenhouse&celsius=21.5");
+    }
+
+    #[actix_rt::test]
+    async fn test_q_values_and_ties() {
+        // the most specific range wins, so JSON is excluded
+        let accept = "application/*;q=0.8, application/json;q=0";
+        let resp = get("/measurement", Some(accept)).await;
+        assert_eq!(header_of(&resp, "content-type"), "application/cbor");
+
+        // on equal quality the server's preference decides
+        let accept = "application/yaml, application/json";
+        let resp = get("/measurement", Some(accept)).await;
+        assert_eq!(header_of(&resp, "content-type"), "application/json");
+    }
+
+    #[actix_rt::test]
+    async fn test_not_acceptable() {
+        let resp = get("/measurement", Some("text/html, image/*")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_ACCEPTABLE);
+        assert_eq!(header_of(&resp, "vary"), "accept");
+    }
+
+    #[actix_rt::test]
+    async fn test_serialize_error() {
+        // urlencoded cannot represent a sequence
+        let accept = "application/x-www-form-urlencoded";
+        let resp = get("/nested", Some(accept)).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body = test::read_body(resp).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.starts_with(
+            "cannot render response as application/x-www-form-urlencoded"
+        ));
+    }
+}
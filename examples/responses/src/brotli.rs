@@ -1,6 +1,6 @@
 // <brotli>
 use actix_web::{
-    dev::BodyEncoding, get, http::ContentEncoding, middleware, App, HttpResponse, HttpServer,
+    dev::BodyEncoding, get, http::ContentEncoding, middleware, web, App, HttpResponse, HttpServer,
 };
 
 #[get("/")]
@@ -10,15 +10,45 @@ async fn index_br() -> HttpResponse {
         .body("data")
 }
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index_br);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     HttpServer::new(|| {
         App::new()
             .wrap(middleware::Compress::default())
-            .service(index_br)
+            .configure(configure)
     })
     .bind("127.0.0.1:8080")?
     .run()
     .await
 }
 // </brotli>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::header, test};
+
+    #[actix_rt::test]
+    async fn test_brotli() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(middleware::Compress::default())
+                .configure(configure),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .header(header::ACCEPT_ENCODING, "gzip, br")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "br"
+        );
+    }
+}
@@ -1,21 +1,51 @@
 // <brotli-two>
-use actix_web::{http::ContentEncoding, dev::BodyEncoding, HttpResponse};
+use actix_web::{http::ContentEncoding, web, HttpResponse};
 
 async fn index_br() -> HttpResponse {
     HttpResponse::Ok().body("data")
 }
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/", web::get().to(index_br));
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    use actix_web::{middleware, web, App, HttpServer};
+    use actix_web::{middleware, App, HttpServer};
 
     HttpServer::new(|| {
         App::new()
             .wrap(middleware::Compress::new(ContentEncoding::Br))
-            .route("/", web::get().to(index_br))
+            .configure(configure)
     })
     .bind("127.0.0.1:8080")?
     .run()
     .await
 }
 // </brotli-two>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::header, middleware, test, App};
+
+    #[actix_rt::test]
+    async fn test_brotli() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(middleware::Compress::new(ContentEncoding::Br))
+                .configure(configure),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .header(header::ACCEPT_ENCODING, "br")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "br"
+        );
+    }
+}
@@ -9,10 +9,30 @@ async fn index(_req: HttpRequest) -> HttpResponse {
 }
 // </chunked>
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index);
+}
+
 #[actix_web::main]
-async fn main() {
-    HttpServer::new(|| App::new().service(index))
-        .bind("127.0.0.1:8080")
-        .unwrap()
-        .run();
+async fn main() -> std::io::Result<()> {
+    HttpServer::new(|| App::new().configure(configure))
+        .bind("127.0.0.1:8080")?
+        .run()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+
+    #[actix_rt::test]
+    async fn test_chunked() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "data");
+    }
 }
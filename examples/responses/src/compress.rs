@@ -1,20 +1,50 @@
 // <compress>
-use actix_web::{get, middleware, App, HttpResponse, HttpServer};
+use actix_web::{get, middleware, web, App, HttpResponse, HttpServer};
 
 #[get("/")]
 async fn index_br() -> HttpResponse {
     HttpResponse::Ok().body("data")
 }
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index_br);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     HttpServer::new(|| {
         App::new()
             .wrap(middleware::Compress::default())
-            .service(index_br)
+            .configure(configure)
     })
     .bind("127.0.0.1:8080")?
     .run()
     .await
 }
 // </compress>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::header, test};
+
+    #[actix_rt::test]
+    async fn test_compress() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(middleware::Compress::default())
+                .configure(configure),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "gzip"
+        );
+    }
+}
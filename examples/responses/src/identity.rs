@@ -1,6 +1,6 @@
 // <identity>
 use actix_web::{
-    dev::BodyEncoding, get, http::ContentEncoding, middleware, App, HttpResponse, HttpServer,
+    dev::BodyEncoding, get, http::ContentEncoding, middleware, web, App, HttpResponse, HttpServer,
 };
 
 #[get("/")]
@@ -11,15 +11,43 @@ async fn index() -> HttpResponse {
         .body("data")
 }
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     HttpServer::new(|| {
         App::new()
             .wrap(middleware::Compress::default())
-            .service(index)
+            .configure(configure)
     })
     .bind("127.0.0.1:8080")?
     .run()
     .await
 }
 // </identity>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::header, test};
+
+    #[actix_rt::test]
+    async fn test_identity() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(middleware::Compress::default())
+                .configure(configure),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+        assert_eq!(test::read_body(resp).await, "data");
+    }
+}
@@ -1,6 +1,6 @@
 // <identity-two>
 use actix_web::{
-    dev::BodyEncoding, get, http::ContentEncoding, middleware, App, HttpResponse, HttpServer,
+    dev::BodyEncoding, get, http::ContentEncoding, middleware, web, App, HttpResponse, HttpServer,
 };
 
 static HELLO_WORLD: &[u8] = &[
@@ -17,14 +17,45 @@ async fn index() -> HttpResponse {
 }
 // </identity-two>
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     HttpServer::new(|| {
         App::new()
             .wrap(middleware::Compress::default())
-            .service(index)
+            .configure(configure)
     })
     .bind("127.0.0.1:8080")?
     .run()
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::header, test};
+
+    #[actix_rt::test]
+    async fn test_precompressed() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(middleware::Compress::default())
+                .configure(configure),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "gzip"
+        );
+        assert_eq!(test::read_body(resp).await, HELLO_WORLD);
+    }
+}
@@ -0,0 +1,43 @@
+pub mod auto;
+pub mod brotli;
+pub mod brotli_two;
+pub mod chunked;
+pub mod compress;
+pub mod identity;
+pub mod identity_two;
+pub mod json_resp;
+
+// <builder>
+use actix_web::HttpResponse;
+
+async fn index() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("plain/text")
+        .header("X-Hdr", "sample")
+        .body("data")
+}
+// </builder>
+
+pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
+    use actix_web::web;
+
+    cfg.route("/", web::get().to(index));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn test_builder() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.headers().get("content-type").unwrap(), "plain/text");
+        assert_eq!(resp.headers().get("x-hdr").unwrap(), "sample");
+        assert_eq!(test::read_body(resp).await, "data");
+    }
+}
@@ -0,0 +1,530 @@
+// <config>
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use actix_http::{Request, Response};
+use actix_service::{IntoServiceFactory, Service, ServiceFactory};
+use actix_web::dev::{AppConfig, MessageBody};
+use actix_web::{Error, HttpServer};
+use serde::Deserialize;
+
+use crate::ssl;
+
+/// Deployment parameters for `HttpServer`
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Address to listen on
+    pub bind: SocketAddr,
+    /// Number of workers, defaults to the number of logical CPUs
+    pub workers: Option<usize>,
+    /// Keep-alive timeout in seconds, `0` disables keep-alive
+    pub keep_alive: usize,
+    /// Seconds workers get to finish in-flight requests on shutdown
+    pub shutdown_timeout: u64,
+    /// Serve HTTPS when both paths are set
+    pub tls: Tls,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tls {
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            bind: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            workers: None,
+            keep_alive: 5,
+            shutdown_timeout: 30,
+            tls: Tls::default(),
+        }
+    }
+}
+
+/// Every setting that can be overridden
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Setting {
+    Bind,
+    Workers,
+    KeepAlive,
+    ShutdownTimeout,
+    TlsCert,
+    TlsKey,
+}
+
+const KEYS: &[Setting] = &[
+    Setting::Bind,
+    Setting::Workers,
+    Setting::KeepAlive,
+    Setting::ShutdownTimeout,
+    Setting::TlsCert,
+    Setting::TlsKey,
+];
+
+impl Setting {
+    /// Name as written in the config file
+    fn name(self) -> &'static str {
+        match self {
+            Setting::Bind => "bind",
+            Setting::Workers => "workers",
+            Setting::KeepAlive => "keep_alive",
+            Setting::ShutdownTimeout => "shutdown_timeout",
+            Setting::TlsCert => "tls.cert",
+            Setting::TlsKey => "tls.key",
+        }
+    }
+}
+
+const DEFAULT_FILE: &str = "server.toml";
+
+/// `tls.cert` is set by `SERVER_TLS_CERT`
+fn env_name(key: Setting) -> String {
+    format!("SERVER_{}", key.name().replace('.', "_").to_uppercase())
+}
+
+/// `tls.cert` is set by `--tls-cert`
+fn flag_name(key: Setting) -> String {
+    format!("--{}", key.name().replace(&['.', '_'][..], "-"))
+}
+
+/// Parses `value` into the type of the setting it is assigned to
+fn parse<T: FromStr>(value: &str, expected: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("`{}` is not {}", value, expected))
+}
+
+impl Settings {
+    /// Reads `server.toml`, or the file named by `--config` or
+    /// `SERVER_CONFIG`, then applies `SERVER_*` environment variables and
+    /// finally command line flags, each overriding the one before.
+    pub fn load() -> Result<Self, ConfigError> {
+        Settings::from_sources(std::env::vars(), std::env::args().skip(1))
+    }
+
+    pub fn from_sources<E, A>(env: E, args: A) -> Result<Self, ConfigError>
+    where
+        E: IntoIterator<Item = (String, String)>,
+        A: IntoIterator<Item = String>,
+    {
+        let env: HashMap<_, _> = env.into_iter().collect();
+        let mut flags = parse_flags(args)?;
+
+        let config_file = flags
+            .iter()
+            .position(|(flag, _)| flag == "--config")
+            .map(|idx| flags.remove(idx).1)
+            .or_else(|| env.get("SERVER_CONFIG").cloned());
+
+        // only a config file that was asked for explicitly has to exist
+        let path =
+            PathBuf::from(config_file.as_deref().unwrap_or(DEFAULT_FILE));
+        let (mut settings, origin) = match fs::read_to_string(&path) {
+            Ok(content) => {
+                let settings = toml::from_str(&content).map_err(|err| {
+                    ConfigError::Parse {
+                        path: path.clone(),
+                        message: err.to_string(),
+                    }
+                })?;
+                (settings, Origin::File(path))
+            }
+            Err(err)
+                if config_file.is_none()
+                    && err.kind() == io::ErrorKind::NotFound =>
+            {
+                (Settings::default(), Origin::Default)
+            }
+            Err(source) => return Err(ConfigError::Read { path, source }),
+        };
+
+        let mut origins: HashMap<&str, Origin> = KEYS
+            .iter()
+            .map(|key| (key.name(), origin.clone()))
+            .collect();
+
+        for &key in KEYS {
+            let name = env_name(key);
+            if let Some(value) = env.get(&name) {
+                let origin = Origin::Env(name);
+                settings.set(key, value, &origin)?;
+                origins.insert(key.name(), origin);
+            }
+        }
+
+        for (flag, value) in flags {
+            let key = KEYS
+                .iter()
+                .copied()
+                .find(|&key| flag_name(key) == flag)
+                .ok_or_else(|| {
+                    ConfigError::Args(format!("unknown flag {}", flag))
+                })?;
+            let origin = Origin::Flag(flag);
+            settings.set(key, &value, &origin)?;
+            origins.insert(key.name(), origin);
+        }
+
+        settings.validate(&origins)?;
+        Ok(settings)
+    }
+
+    fn set(
+        &mut self,
+        key: Setting,
+        value: &str,
+        origin: &Origin,
+    ) -> Result<(), ConfigError> {
+        let invalid = |message| ConfigError::Invalid {
+            key: key.name(),
+            origin: origin.clone(),
+            message,
+        };
+        let number = "a non-negative integer";
+
+        match key {
+            Setting::Bind => {
+                self.bind = parse(value, "an address like 127.0.0.1:8080")
+                    .map_err(invalid)?
+            }
+            Setting::Workers => {
+                self.workers = Some(parse(value, number).map_err(invalid)?)
+            }
+            Setting::KeepAlive => {
+                self.keep_alive = parse(value, number).map_err(invalid)?
+            }
+            Setting::ShutdownTimeout => {
+                self.shutdown_timeout =
+                    parse(value, number).map_err(invalid)?
+            }
+            Setting::TlsCert => self.tls.cert = Some(value.into()),
+            Setting::TlsKey => self.tls.key = Some(value.into()),
+        }
+
+        Ok(())
+    }
+
+    fn validate(
+        &self,
+        origins: &HashMap<&str, Origin>,
+    ) -> Result<(), ConfigError> {
+        let invalid =
+            |key: &'static str, message: String| ConfigError::Invalid {
+                key,
+                origin: origins[key].clone(),
+                message,
+            };
+
+        if self.workers == Some(0) {
+            return Err(invalid(
+                "workers",
+                String::from("must be at least 1"),
+            ));
+        }
+
+        match (&self.tls.cert, &self.tls.key) {
+            (Some(_), None) => Err(invalid(
+                "tls.cert",
+                String::from("`tls.key` must be set as well"),
+            )),
+            (None, Some(_)) => Err(invalid(
+                "tls.key",
+                String::from("`tls.cert` must be set as well"),
+            )),
+            (Some(cert), Some(key)) => {
+                for (name, path) in &[("tls.cert", cert), ("tls.key", key)] {
+                    if !path.is_file() {
+                        return Err(invalid(
+                            name,
+                            format!("{} does not exist", path.display()),
+                        ));
+                    }
+                }
+                Ok(())
+            }
+            (None, None) => Ok(()),
+        }
+    }
+
+    /// Applies the settings to `server` and binds it, over TLS if a
+    /// certificate is configured.
+    pub fn apply<F, I, S, B>(
+        &self,
+        server: HttpServer<F, I, S, B>,
+    ) -> io::Result<HttpServer<F, I, S, B>>
+    where
+        F: Fn() -> I + Send + Clone + 'static,
+        I: IntoServiceFactory<S>,
+        S: ServiceFactory<Config = AppConfig, Request = Request>,
+        S::Error: Into<Error> + 'static,
+        S::InitError: fmt::Debug,
+        S::Response: Into<Response<B>> + 'static,
+        <S::Service as Service>::Future: 'static,
+        B: MessageBody + 'static,
+    {
+        let keep_alive = match self.keep_alive {
+            0 => None,
+            secs => Some(secs),
+        };
+
+        let mut server = server
+            .keep_alive(keep_alive)
+            .shutdown_timeout(self.shutdown_timeout);
+        if let Some(workers) = self.workers {
+            server = server.workers(workers);
+        }
+
+        match (&self.tls.cert, &self.tls.key) {
+            (Some(cert), Some(key)) => {
+                server.bind_openssl(self.bind, ssl::watch_certs(cert, key)?)
+            }
+            _ => server.bind(self.bind),
+        }
+    }
+}
+
+/// Splits `--flag value` and `--flag=value` pairs
+fn parse_flags<A>(args: A) -> Result<Vec<(String, String)>, ConfigError>
+where
+    A: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let mut flags = Vec::new();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            return Err(ConfigError::Args(format!(
+                "unexpected argument `{}`",
+                arg
+            )));
+        }
+
+        let (flag, value) = match arg.find('=') {
+            Some(idx) => (arg[..idx].to_owned(), arg[idx + 1..].to_owned()),
+            None => {
+                let value = args.next().ok_or_else(|| {
+                    ConfigError::Args(format!("missing value for {}", arg))
+                })?;
+                (arg, value)
+            }
+        };
+        flags.push((flag, value));
+    }
+
+    Ok(flags)
+}
+
+/// Where a setting was taken from
+#[derive(Clone, Debug, PartialEq)]
+pub enum Origin {
+    Default,
+    File(PathBuf),
+    Env(String),
+    Flag(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "the defaults"),
+            Origin::File(path) => write!(f, "{}", path.display()),
+            Origin::Env(name) => write!(f, "environment variable {}", name),
+            Origin::Flag(flag) => write!(f, "command line flag {}", flag),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Config file could not be read
+    Read { path: PathBuf, source: io::Error },
+    /// Config file is not valid TOML or a value has the wrong type
+    Parse { path: PathBuf, message: String },
+    /// Value is well-formed but not acceptable
+    Invalid {
+        key: &'static str,
+        origin: Origin,
+        message: String,
+    },
+    /// Malformed command line
+    Args(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, source } => {
+                write!(f, "cannot read {}: {}", path.display(), source)
+            }
+            // the TOML error names the key and line
+            ConfigError::Parse { path, message } => {
+                write!(f, "{}: {}", path.display(), message)
+            }
+            ConfigError::Invalid {
+                key,
+                origin,
+                message,
+            } => write!(f, "invalid `{}` (from {}): {}", key, origin, message),
+            ConfigError::Args(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<ConfigError> for io::Error {
+    fn from(err: ConfigError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
+}
+// </config>
+
+// <config-main>
+#[actix_web::main]
+async fn main() -> io::Result<()> {
+    use actix_web::{web, App, HttpResponse};
+
+    let settings = Settings::load()?;
+
+    settings
+        .apply(HttpServer::new(|| {
+            App::new().route("/", web::get().to(HttpResponse::Ok))
+        }))?
+        .run()
+        .await
+}
+// </config-main>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.toml");
+        fs::write(
+            &path,
+            "bind = \"0.0.0.0:80\"\nworkers = 2\nkeep_alive = 75\n",
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+
+        let settings = Settings::from_sources(
+            env(&[("SERVER_WORKERS", "4"), ("SERVER_SHUTDOWN_TIMEOUT", "60")]),
+            args(&["--config", path, "--workers=8"]),
+        )
+        .unwrap();
+
+        assert_eq!(settings.bind, "0.0.0.0:80".parse().unwrap());
+        assert_eq!(settings.keep_alive, 75);
+        assert_eq!(settings.shutdown_timeout, 60);
+        assert_eq!(settings.workers, Some(8));
+        assert_eq!(settings.tls, Tls::default());
+    }
+
+    #[test]
+    fn test_errors_name_the_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.toml");
+        fs::write(&path, "workers = \"four\"\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        let err = Settings::from_sources(env(&[]), args(&["--config", path]))
+            .unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { .. }));
+        assert!(err.to_string().contains("`workers`"));
+
+        let err = Settings::from_sources(
+            env(&[("SERVER_CONFIG", path), ("SERVER_WORKERS", "0")]),
+            args(&["--workers", "2"]),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { .. }));
+
+        let err = Settings::from_sources(
+            env(&[("SERVER_BIND", "localhost")]),
+            args(&[]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid `bind` (from environment variable SERVER_BIND): \
+             `localhost` is not an address like 127.0.0.1:8080"
+        );
+
+        let err = Settings::from_sources(
+            env(&[("SERVER_SHUTDOWN_TIMEOUT", "-1")]),
+            args(&[]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid `shutdown_timeout` (from environment variable \
+             SERVER_SHUTDOWN_TIMEOUT): `-1` is not a non-negative integer"
+        );
+
+        let err = Settings::from_sources(env(&[]), args(&["--workers", "0"]))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid `workers` (from command line flag --workers): \
+             must be at least 1"
+        );
+
+        let err = Settings::from_sources(env(&[]), args(&["--port", "80"]))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "unknown flag --port");
+    }
+
+    #[test]
+    fn test_tls_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert = dir.path().join("cert.pem");
+        fs::write(&cert, "").unwrap();
+        let cert = cert.to_str().unwrap();
+
+        let err = Settings::from_sources(
+            env(&[("SERVER_TLS_CERT", cert)]),
+            args(&[]),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                key: "tls.cert",
+                ..
+            }
+        ));
+
+        let err = Settings::from_sources(
+            env(&[("SERVER_TLS_CERT", cert)]),
+            args(&["--tls-key", "missing.pem"]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid `tls.key` (from command line flag --tls-key): \
+             missing.pem does not exist"
+        );
+    }
+}
@@ -1,23 +1,26 @@
 // <keep-alive>
 use actix_web::{web, App, HttpResponse, HttpServer};
 
+use crate::config::Settings;
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let one = HttpServer::new(|| {
-        App::new().route("/", web::get().to(|| HttpResponse::Ok()))
-    })
-    .keep_alive(75); // <- Set keep-alive to 75 seconds
+    // `keep_alive = 75` in server.toml, `SERVER_KEEP_ALIVE=75` or
+    // `--keep-alive 75`
+    let settings = Settings::load()?;
 
-    // let _two = HttpServer::new(|| {
-    //     App::new().route("/", web::get().to(|| HttpResponse::Ok()))
-    // })
-    // .keep_alive(); // <- Use `SO_KEEPALIVE` socket option.
+    let keep_alive = match settings.keep_alive {
+        0 => None,          // <- Disable keep-alive
+        secs => Some(secs), // <- Set keep-alive to 75 seconds
+    };
 
-    let _three = HttpServer::new(|| {
-        App::new().route("/", web::get().to(|| HttpResponse::Ok()))
-    })
-    .keep_alive(None); // <- Disable keep-alive
+    // `.keep_alive(KeepAlive::Tcp(75))` would use the `SO_KEEPALIVE`
+    // socket option instead
 
-    one.bind("127.0.0.1:8080")?.run().await
+    HttpServer::new(|| App::new().route("/", web::get().to(HttpResponse::Ok)))
+        .keep_alive(keep_alive)
+        .bind(settings.bind)?
+        .run()
+        .await
 }
 // </keep-alive>
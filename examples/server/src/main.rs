@@ -1,3 +1,4 @@
+pub mod config;
 pub mod keep_alive;
 // pub mod keep_alive_tp;
 pub mod signals;
@@ -7,13 +8,19 @@ pub mod workers;
 // <main>
 use actix_web::{web, App, HttpResponse, HttpServer};
 
+use crate::config::Settings;
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| {
-        App::new().route("/", web::get().to(|| HttpResponse::Ok()))
-    })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+    // the bind address and the other deployment parameters come from
+    // server.toml, see the configuration section below
+    let settings = Settings::load()?;
+
+    settings
+        .apply(HttpServer::new(|| {
+            App::new().route("/", web::get().to(HttpResponse::Ok))
+        }))?
+        .run()
+        .await
 }
 // </main>
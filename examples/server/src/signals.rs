@@ -1,33 +1,287 @@
 // <signals>
-use actix_web::{rt::System, web, App, HttpResponse, HttpServer};
-use std::sync::mpsc;
-use std::thread;
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use actix_web::dev::Server;
+use actix_web::{web, App, HttpResponse, HttpServer};
+use futures::stream::{self, LocalBoxStream, Stream, StreamExt};
+
+use crate::config::Settings;
+
+/// State shared by all workers and the signal handler
+pub struct AppState {
+    /// Set once a shutdown signal has been received
+    draining: AtomicBool,
+    /// Re-read from `config_path` on SIGHUP
+    greeting: RwLock<String>,
+    config_path: PathBuf,
+}
+
+impl AppState {
+    pub fn load(config_path: impl Into<PathBuf>) -> io::Result<Self> {
+        let config_path = config_path.into();
+        let greeting = fs::read_to_string(&config_path)?;
+
+        Ok(AppState {
+            draining: AtomicBool::new(false),
+            greeting: RwLock::new(greeting.trim().to_owned()),
+            config_path,
+        })
+    }
+
+    /// Reloads the configuration. On error the old one is kept.
+    pub fn reload(&self) -> io::Result<()> {
+        let greeting = fs::read_to_string(&self.config_path)?;
+        *self.greeting.write().unwrap() = greeting.trim().to_owned();
+        Ok(())
+    }
+}
+
+async fn index(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().body(state.greeting.read().unwrap().clone())
+}
+
+/// Readiness probe for load balancers; fails as soon as shutdown starts.
+async fn ready(state: web::Data<AppState>) -> HttpResponse {
+    if state.draining.load(Ordering::SeqCst) {
+        HttpResponse::ServiceUnavailable().body("draining")
+    } else {
+        HttpResponse::Ok().body("ready")
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/", web::get().to(index))
+        .route("/ready", web::get().to(ready));
+}
+
+#[derive(Clone, Copy)]
+enum Action {
+    Reload,
+    Shutdown,
+}
+
+#[cfg(unix)]
+fn signals() -> io::Result<LocalBoxStream<'static, Action>> {
+    use actix_web::rt::signal::unix::{signal, SignalKind};
+
+    let on = |kind, action| -> io::Result<_> {
+        Ok(signal(kind)?.map(move |_| action).boxed_local())
+    };
+
+    Ok(stream::select_all(vec![
+        on(SignalKind::hangup(), Action::Reload)?,
+        on(SignalKind::terminate(), Action::Shutdown)?,
+        on(SignalKind::interrupt(), Action::Shutdown)?,
+        on(SignalKind::quit(), Action::Shutdown)?,
+    ])
+    .boxed_local())
+}
+
+/// There is no SIGHUP elsewhere; CTRL-C still shuts down gracefully.
+#[cfg(not(unix))]
+fn signals() -> io::Result<LocalBoxStream<'static, Action>> {
+    Ok(stream::once(actix_web::rt::signal::ctrl_c())
+        .map(|_| Action::Shutdown)
+        .boxed_local())
+}
+
+/// Installs the signal handlers and returns a future that acts on them.
+///
+/// SIGHUP reloads the configuration. SIGTERM, SIGINT and SIGQUIT start a
+/// graceful shutdown: the readiness probe fails for `drain_delay` so load
+/// balancers can take the instance out of rotation, then the server stops
+/// accepting connections and waits up to `shutdown_timeout` for in-flight
+/// requests to finish.
+pub fn handle_signals(
+    server: Server,
+    state: web::Data<AppState>,
+    drain_delay: Duration,
+) -> io::Result<impl Future<Output = ()>> {
+    Ok(handle_actions(server, state, drain_delay, signals()?))
+}
+
+async fn handle_actions(
+    server: Server,
+    state: web::Data<AppState>,
+    drain_delay: Duration,
+    actions: impl Stream<Item = Action>,
+) {
+    futures::pin_mut!(actions);
+
+    while let Some(action) = actions.next().await {
+        match action {
+            Action::Reload => match state.reload() {
+                Ok(()) => log::info!("configuration reloaded"),
+                Err(err) => log::error!("failed to reload: {}", err),
+            },
+            Action::Shutdown => {
+                state.draining.store(true, Ordering::SeqCst);
+                actix_web::rt::time::delay_for(drain_delay).await;
+                server.stop(true).await;
+                break;
+            }
+        }
+    }
+}
 
 #[actix_web::main]
-async fn main() {
-    let (tx, rx) = mpsc::channel();
+async fn main() -> io::Result<()> {
+    let state = web::Data::new(AppState::load("greeting.txt")?);
+
+    // bind address and shutdown timeout come from the server settings
+    let settings = Settings::load()?;
+
+    let app_state = state.clone();
+    let server = settings
+        .apply(HttpServer::new(move || {
+            App::new().app_data(app_state.clone()).configure(config)
+        }))?
+        .disable_signals() // <- Signals are handled below instead
+        .run();
+
+    let signals =
+        handle_signals(server.clone(), state, Duration::from_secs(5))?;
+    actix_web::rt::spawn(signals);
+
+    server.await
+}
+// </signals>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::client::Client;
+    use actix_web::rt::time::delay_for;
+    use futures::channel::mpsc;
 
-    thread::spawn(move || {
-        let sys = System::new("http-server");
+    /// Serves `config` and a slow route with one worker, returns the server
+    /// and its base URL
+    fn serve(state: &web::Data<AppState>) -> (Server, String) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
 
-        let srv = HttpServer::new(|| {
-            App::new().route("/", web::get().to(|| HttpResponse::Ok()))
+        let app_state = state.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_state.clone())
+                .configure(config)
+                .route(
+                    "/slow",
+                    web::get().to(|| async {
+                        delay_for(Duration::from_millis(500)).await;
+                        "done"
+                    }),
+                )
         })
-        .bind("127.0.0.1:8080")?
-        .shutdown_timeout(60) // <- Set shutdown timeout to 60 seconds
+        .listen(listener)
+        .unwrap()
+        .workers(1)
+        .shutdown_timeout(2)
+        .disable_signals()
         .run();
 
-        let _ = tx.send(srv);
-        sys.run()
-    });
+        (server, format!("http://{}", addr))
+    }
 
-    let srv = rx.recv().unwrap();
+    /// Waits for the greeting to change to `expected`
+    async fn until_greeting(client: &Client, base: &str, expected: &str) {
+        let mut greeting = String::new();
+        for _ in 0..50 {
+            let mut res = client.get(base).send().await.unwrap();
+            greeting =
+                String::from_utf8(res.body().await.unwrap().to_vec()).unwrap();
+            if greeting == expected {
+                break;
+            }
+            delay_for(Duration::from_millis(10)).await;
+        }
+        assert_eq!(greeting, expected);
+    }
 
-    // pause accepting new connections
-    srv.pause().await;
-    // resume accepting new connections
-    srv.resume().await;
-    // stop server
-    srv.stop(true).await;
+    #[actix_rt::test]
+    async fn test_graceful_shutdown() {
+        let path = std::env::temp_dir()
+            .join(format!("signals-{}.txt", std::process::id()));
+        fs::write(&path, "hello").unwrap();
+
+        let state = web::Data::new(AppState::load(&path).unwrap());
+        let (server, base) = serve(&state);
+
+        // feeds the actions directly, so that the drain delay and the
+        // request in flight can be timed
+        let (signals, actions) = mpsc::unbounded();
+        actix_rt::spawn(handle_actions(
+            server.clone(),
+            state.clone(),
+            Duration::from_millis(200),
+            actions,
+        ));
+
+        let client = Client::default();
+        let url = |path: &str| format!("{}{}", base, path);
+
+        // a reload request re-reads the configuration
+        fs::write(&path, "reloaded").unwrap();
+        signals.unbounded_send(Action::Reload).unwrap();
+        until_greeting(&client, &base, "reloaded").await;
+        fs::remove_file(&path).unwrap();
+
+        // shutdown is requested while a long request is in flight
+        let slow = async {
+            let mut res = client.get(url("/slow")).send().await.unwrap();
+            assert!(res.status().is_success());
+            assert_eq!(res.body().await.unwrap(), "done");
+        };
+        let shutdown = async {
+            delay_for(Duration::from_millis(100)).await;
+            signals.unbounded_send(Action::Shutdown).unwrap();
+            delay_for(Duration::from_millis(50)).await;
+
+            let res = client.get(url("/ready")).send().await.unwrap();
+            assert_eq!(res.status(), 503);
+        };
+        // the server future resolves once all workers have stopped
+        let (_, _, stopped) = futures::join!(slow, shutdown, server);
+        stopped.unwrap();
+    }
+
+    /// Sends real signals to the test binary. Once `handle_signals` has
+    /// installed its handlers they no longer terminate the process.
+    #[cfg(unix)]
+    #[actix_rt::test]
+    async fn test_signals() {
+        let path = std::env::temp_dir()
+            .join(format!("signals-os-{}.txt", std::process::id()));
+        fs::write(&path, "hello").unwrap();
+
+        let state = web::Data::new(AppState::load(&path).unwrap());
+        let (server, base) = serve(&state);
+        let signals = handle_signals(
+            server.clone(),
+            state.clone(),
+            Duration::from_secs(0),
+        )
+        .unwrap();
+        actix_rt::spawn(signals);
+
+        let raise = |signal| unsafe {
+            assert_eq!(libc::kill(libc::getpid(), signal), 0);
+        };
+        let client = Client::default();
+
+        fs::write(&path, "reloaded").unwrap();
+        raise(libc::SIGHUP);
+        until_greeting(&client, &base, "reloaded").await;
+        fs::remove_file(&path).unwrap();
+
+        raise(libc::SIGTERM);
+        server.await.unwrap();
+        assert!(state.draining.load(Ordering::SeqCst));
+    }
 }
-// </signals>
@@ -1,29 +1,121 @@
 #![allow(dead_code)]
 
 // <ssl>
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
 use actix_web::{get, App, HttpRequest, HttpServer, Responder};
-use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+use http2::tls::{openssl::Reloader, CertPaths};
+use openssl::ssl::SslAcceptorBuilder;
+
+use crate::config::Settings;
 
 #[get("/")]
 async fn index(_req: HttpRequest) -> impl Responder {
     "Welcome!"
 }
 
+/// Loads the certificate and key, then reloads them whenever either file
+/// changes. If the new files are broken, the previous certificate stays
+/// in use. Must be called from within the actix runtime.
+pub fn watch_certs(cert: &Path, key: &Path) -> io::Result<SslAcceptorBuilder> {
+    let reloader = Reloader::new(CertPaths::new(cert, key))?;
+    reloader.watch(Duration::from_secs(10));
+    Ok(reloader.acceptor()?)
+}
+
 #[actix_web::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> io::Result<()> {
     // load ssl keys
     // to create a self-signed temporary cert for testing:
     // `openssl req -x509 -newkey rsa:4096 -nodes -keyout key.pem -out cert.pem -days 365 -subj '/CN=localhost'`
-    let mut builder =
-        SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
-    builder
-        .set_private_key_file("key.pem", SslFiletype::PEM)
-        .unwrap();
-    builder.set_certificate_chain_file("cert.pem").unwrap();
-
-    HttpServer::new(|| App::new().service(index))
-        .bind_openssl("127.0.0.1:8080", builder)?
+    let mut settings = Settings::load()?;
+
+    // this example always serves HTTPS; `settings.apply` loads the
+    // certificate with `watch_certs` and binds over TLS
+    settings.tls.cert.get_or_insert_with(|| "cert.pem".into());
+    settings.tls.key.get_or_insert_with(|| "key.pem".into());
+
+    settings
+        .apply(HttpServer::new(|| App::new().service(index)))?
         .run()
         .await
 }
 // </ssl>
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use actix_web::client::{Client, Connector};
+    use openssl::asn1::Asn1Time;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::PKey;
+    use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+    use openssl::x509::{X509NameBuilder, X509};
+
+    use super::*;
+
+    fn self_signed(cert_path: &Path, key_path: &Path) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, "localhost")
+            .unwrap();
+        let name = name.build();
+
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_issuer_name(&name).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+
+        fs::write(key_path, key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        fs::write(cert_path, cert.build().to_pem().unwrap()).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_watch_certs() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert = dir.path().join("cert.pem");
+        let key = dir.path().join("key.pem");
+
+        let err = watch_certs(&cert, &key).err().unwrap();
+        assert!(err.to_string().contains("cert.pem"));
+
+        self_signed(&cert, &key);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = HttpServer::new(|| App::new().service(index))
+            .workers(1)
+            .disable_signals()
+            .listen_openssl(listener, watch_certs(&cert, &key).unwrap())
+            .unwrap()
+            .run();
+
+        let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+        connector.set_verify(SslVerifyMode::NONE);
+        let client = Client::builder()
+            .connector(Connector::new().ssl(connector.build()).finish())
+            .finish();
+
+        let mut res = client
+            .get(format!("https://localhost:{}/", addr.port()))
+            .send()
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+        assert_eq!(res.body().await.unwrap(), "Welcome!");
+
+        server.stop(false).await;
+    }
+}
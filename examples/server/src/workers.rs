@@ -1,11 +1,21 @@
 // <workers>
 use actix_web::{web, App, HttpResponse, HttpServer};
 
+use crate::config::Settings;
+
 #[actix_web::main]
-async fn main() {
-    HttpServer::new(|| {
-        App::new().route("/", web::get().to(|| HttpResponse::Ok()))
-    })
-    .workers(4); // <- Start 4 workers
+async fn main() -> std::io::Result<()> {
+    // `workers = 4` in server.toml, `SERVER_WORKERS=4` or `--workers 4`
+    let settings = Settings::load()?;
+
+    let server = HttpServer::new(|| {
+        App::new().route("/", web::get().to(HttpResponse::Ok))
+    });
+    let server = match settings.workers {
+        Some(workers) => server.workers(workers), // <- Start 4 workers
+        None => server, // <- One worker per logical CPU
+    };
+
+    server.bind(settings.bind)?.run().await
 }
 // </workers>
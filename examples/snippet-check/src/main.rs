@@ -0,0 +1,241 @@
+//! Checks the `include-example` shortcodes used by the website.
+//!
+//! Every shortcode in `content/**/*.md` must point at an existing example
+//! file and section, and every `// <section>` marker in the examples must
+//! be balanced, unique within its file and used by some page, unless it
+//! is listed in `UNREFERENCED_SECTIONS`.
+//!
+//! ```sh
+//! cargo run -p snippet-check                                # check everything
+//! cargo run -p snippet-check -- print server ssl.rs ssl     # show a snippet
+//! ```
+mod markers;
+mod shortcode;
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Examples whose code is shown on the front page (`layouts/index.html`)
+/// instead of through the shortcode, so their sections are never included
+const FRONT_PAGE_EXAMPLES: &[&str] = &[
+    "easy-form-handling",
+    "flexible-responders",
+    "main-example",
+    "request-routing",
+];
+
+/// Sections that no page includes yet but that are kept for pages to come,
+/// as `(file, section)`
+const UNREFERENCED_SECTIONS: &[(&str, &str)] = &[
+    ("examples/application/src/combine.rs", "combine"),
+    ("examples/application/src/main.rs", "multi"),
+    ("examples/extractors/src/lib.rs", "option-two"),
+    ("examples/extractors/src/multiple.rs", "multi"),
+    ("examples/responses/src/chunked.rs", "chunked"),
+];
+
+/// Repository root, one level above the examples workspace
+fn default_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../..")
+}
+
+/// Path relative to the repository root, with `/` separators
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// All files below `dir` with the given extension, in a stable order.
+/// Build output and hidden directories are skipped.
+fn find_files(dir: &Path, extension: &str) -> io::Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    let mut files = Vec::new();
+    for path in entries {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            if name != "target" && !name.starts_with('.') {
+                files.extend(find_files(&path, extension)?);
+            }
+        } else if path.extension() == Some(OsStr::new(extension)) {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Returns one line per problem, formatted as `path:line: message`.
+fn check(root: &Path) -> io::Result<Vec<String>> {
+    let mut problems = Vec::new();
+    let mut sources: HashMap<String, Option<String>> = HashMap::new();
+    let mut referenced: HashSet<(String, String)> = HashSet::new();
+
+    for page in find_files(&root.join("content"), "md")? {
+        let page_path = relative(root, &page);
+        let content = fs::read_to_string(&page)?;
+
+        for include in shortcode::parse(&content) {
+            let mut report = |message: String| {
+                problems.push(format!(
+                    "{}:{}: {}",
+                    page_path, include.line, message
+                ))
+            };
+
+            let path = match include.source_path() {
+                Some(path) => path,
+                None => {
+                    report(String::from("`example` parameter is missing"));
+                    continue;
+                }
+            };
+            let section = match include.section {
+                Some(ref section) => section,
+                None => {
+                    report(String::from("`section` parameter is missing"));
+                    continue;
+                }
+            };
+
+            let source = sources
+                .entry(path.clone())
+                .or_insert_with(|| fs::read_to_string(root.join(&path)).ok());
+            match source {
+                None => report(format!("{} does not exist", path)),
+                Some(source) => {
+                    if markers::extract(source, section).is_none() {
+                        report(format!(
+                            "section `{}` not found in {}",
+                            section, path
+                        ))
+                    }
+                }
+            }
+
+            referenced.insert((path, section.clone()));
+        }
+    }
+
+    for file in find_files(&root.join("examples"), "rs")? {
+        let file_path = relative(root, &file);
+        let front_page = FRONT_PAGE_EXAMPLES.iter().any(|example| {
+            file_path.starts_with(&format!("examples/{}/", example))
+        });
+        let (sections, file_problems) =
+            markers::scan(&fs::read_to_string(&file)?);
+
+        for problem in file_problems {
+            problems.push(format!(
+                "{}:{}: {}",
+                file_path,
+                problem.line(),
+                problem.message()
+            ));
+        }
+
+        for section in sections {
+            let allowed = UNREFERENCED_SECTIONS.iter().any(|&(file, name)| {
+                file == file_path && name == section.name
+            });
+            if !front_page
+                && !allowed
+                && !referenced
+                    .contains(&(file_path.clone(), section.name.clone()))
+            {
+                problems.push(format!(
+                    "{}:{}: section `{}` is not included by any page",
+                    file_path, section.line, section.name
+                ));
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Prints a snippet exactly as the shortcode would pass it to `highlight`
+fn print(
+    root: &Path,
+    example: &str,
+    file: &str,
+    section: &str,
+) -> Result<(), String> {
+    let path = format!("examples/{}/src/{}", example, file);
+    let source = fs::read_to_string(root.join(&path))
+        .map_err(|err| format!("cannot read {}: {}", path, err))?;
+    let snippet = markers::extract(&source, section).ok_or_else(|| {
+        format!("section `{}` not found in {}", section, path)
+    })?;
+
+    println!("{}", snippet);
+    Ok(())
+}
+
+fn usage() -> ! {
+    eprintln!("usage: snippet-check [--root <dir>]");
+    eprintln!(
+        "       snippet-check [--root <dir>] print <example> <file> <section>"
+    );
+    process::exit(2);
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    let root = match args.iter().position(|arg| arg == "--root") {
+        Some(idx) if idx + 1 < args.len() => {
+            args.remove(idx);
+            PathBuf::from(args.remove(idx))
+        }
+        Some(_) => usage(),
+        None => default_root(),
+    };
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        [] => match check(&root) {
+            Ok(problems) if problems.is_empty() => {}
+            Ok(problems) => {
+                for problem in &problems {
+                    println!("{}", problem);
+                }
+                eprintln!("{} problem(s) found", problems.len());
+                process::exit(1);
+            }
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(2);
+            }
+        },
+        ["print", example, file, section] => {
+            if let Err(err) = print(&root, example, file, section) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        _ => usage(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keeps the website and the examples in sync on every `cargo test`
+    #[test]
+    fn test_repository_snippets() {
+        let problems = check(&default_root()).unwrap();
+        assert!(problems.is_empty(), "\n{}", problems.join("\n"));
+    }
+}
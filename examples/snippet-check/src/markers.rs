@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+/// A `// <name>` ... `// </name>` block in an example source file
+#[derive(Debug, PartialEq)]
+pub struct Section {
+    pub name: String,
+    /// Line of the opening marker, starting at 1
+    pub line: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    Unclosed {
+        name: String,
+        line: usize,
+    },
+    UnexpectedClose {
+        name: String,
+        line: usize,
+    },
+    Duplicate {
+        name: String,
+        line: usize,
+        first: usize,
+    },
+    /// Looks like a marker, but the shortcode's regex will not match it
+    Malformed {
+        line: usize,
+        text: String,
+    },
+}
+
+impl Problem {
+    pub fn line(&self) -> usize {
+        match self {
+            Problem::Unclosed { line, .. }
+            | Problem::UnexpectedClose { line, .. }
+            | Problem::Duplicate { line, .. }
+            | Problem::Malformed { line, .. } => *line,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Problem::Unclosed { name, .. } => {
+                format!("section `{}` is never closed", name)
+            }
+            Problem::UnexpectedClose { name, .. } => {
+                format!("section `{}` is closed but was never opened", name)
+            }
+            Problem::Duplicate { name, first, .. } => format!(
+                "section `{}` is already defined on line {}, only the first one is used",
+                name, first
+            ),
+            Problem::Malformed { text, .. } => format!(
+                "`{}` looks like a marker but is not one; markers must be exactly `// <name>` or `// </name>`",
+                text
+            ),
+        }
+    }
+}
+
+/// Finds all sections in `source` along with any problems with their markers.
+pub fn scan(source: &str) -> (Vec<Section>, Vec<Problem>) {
+    let marker = Regex::new(r"^// <(/?)([^>]+)>$").unwrap();
+    let near_miss = Regex::new(r"^\s*//\s*<\s*/?\s*[\w-]+\s*>\s*$").unwrap();
+
+    let mut sections = Vec::new();
+    let mut problems = Vec::new();
+    let mut open: HashMap<String, usize> = HashMap::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+
+    for (idx, text) in source.lines().enumerate() {
+        let line = idx + 1;
+
+        let caps = match marker.captures(text) {
+            Some(caps) => caps,
+            None => {
+                if near_miss.is_match(text) {
+                    problems.push(Problem::Malformed {
+                        line,
+                        text: text.to_owned(),
+                    });
+                }
+                continue;
+            }
+        };
+
+        let name = caps[2].to_owned();
+        if &caps[1] == "/" {
+            if open.remove(&name).is_none() {
+                problems.push(Problem::UnexpectedClose { name, line });
+            }
+            continue;
+        }
+
+        if let Some(&first) = seen.get(&name) {
+            problems.push(Problem::Duplicate {
+                name: name.clone(),
+                line,
+                first,
+            });
+        } else {
+            seen.insert(name.clone(), line);
+            sections.push(Section {
+                name: name.clone(),
+                line,
+            });
+        }
+
+        if let Some(earlier) = open.insert(name.clone(), line) {
+            // a closing marker can only end one of them
+            problems.push(Problem::Unclosed {
+                name,
+                line: earlier,
+            });
+        }
+    }
+
+    for (name, line) in open {
+        problems.push(Problem::Unclosed { name, line });
+    }
+    problems.sort_by_key(Problem::line);
+
+    (sections, problems)
+}
+
+/// Extracts a section the way `layouts/shortcodes/include-example.html`
+/// does: the first match of the section's markers, with every marker line
+/// blanked out and surrounding newlines trimmed.
+pub fn extract(source: &str, section: &str) -> Option<String> {
+    let block = Regex::new(&format!(
+        r"(?ms)^// <{0}>$(.*?)^// </{0}>$",
+        regex::escape(section)
+    ))
+    .unwrap();
+    let marker = Regex::new(r"(?m)^// <[^>]+>").unwrap();
+
+    let found = block.find(source)?;
+    let snippet = marker.replace_all(found.as_str(), "");
+    Some(snippet.trim_matches('\n').to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let source = "use a;\n\
+                      // <outer>\n\
+                      fn one() {}\n\
+                      // <inner>\n\
+                      fn two() {}\n\
+                      // </inner>\n\
+                      // </outer>\n";
+
+        assert_eq!(
+            extract(source, "outer").unwrap(),
+            "fn one() {}\n\nfn two() {}"
+        );
+        assert_eq!(extract(source, "inner").unwrap(), "fn two() {}");
+        assert_eq!(extract(source, "other"), None);
+    }
+
+    #[test]
+    fn test_scan_problems() {
+        let source = "// <a>\n\
+                      // </a>\n\
+                      // <a>\n\
+                      // </a>\n\
+                      // <b>\n\
+                      // </c>\n\
+                      //<d>\n\
+                      // <e> \n\
+                      // <- not a marker\n";
+
+        let (sections, problems) = scan(source);
+        let names: Vec<_> = sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+
+        assert_eq!(
+            problems,
+            vec![
+                Problem::Duplicate {
+                    name: "a".to_owned(),
+                    line: 3,
+                    first: 1
+                },
+                Problem::Unclosed {
+                    name: "b".to_owned(),
+                    line: 5
+                },
+                Problem::UnexpectedClose {
+                    name: "c".to_owned(),
+                    line: 6
+                },
+                Problem::Malformed {
+                    line: 7,
+                    text: "//<d>".to_owned()
+                },
+                Problem::Malformed {
+                    line: 8,
+                    text: "// <e> ".to_owned()
+                },
+            ]
+        );
+    }
+}
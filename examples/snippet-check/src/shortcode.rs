@@ -0,0 +1,84 @@
+use regex::Regex;
+
+/// One `{{< include-example ... >}}` call in a content page
+#[derive(Debug, PartialEq)]
+pub struct Include {
+    /// Line in the page, starting at 1
+    pub line: usize,
+    pub example: Option<String>,
+    pub file: String,
+    pub section: Option<String>,
+}
+
+impl Include {
+    /// Path of the source file relative to the repository root, as the
+    /// shortcode builds it
+    pub fn source_path(&self) -> Option<String> {
+        let example = self.example.as_ref()?;
+        Some(format!("examples/{}/src/{}", example, self.file))
+    }
+}
+
+/// Finds all `include-example` calls in a markdown page.
+pub fn parse(page: &str) -> Vec<Include> {
+    let call = Regex::new(r"\{\{<\s*include-example\s+(.*?)\s*>\}\}").unwrap();
+    let param = Regex::new(r#"(\w+)="([^"]*)""#).unwrap();
+
+    let mut includes = Vec::new();
+
+    for (idx, text) in page.lines().enumerate() {
+        for caps in call.captures_iter(text) {
+            let mut include = Include {
+                line: idx + 1,
+                example: None,
+                // same default as the shortcode
+                file: String::from("main.rs"),
+                section: None,
+            };
+
+            for param in param.captures_iter(&caps[1]) {
+                let value = param[2].to_owned();
+                match &param[1] {
+                    "example" => include.example = Some(value),
+                    "file" => include.file = value,
+                    "section" => include.section = Some(value),
+                    _ => {}
+                }
+            }
+
+            includes.push(include);
+        }
+    }
+
+    includes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let page = "Text\n\
+            {{< include-example example=\"server\" section=\"main\" >}}\n\
+            {{< include-example example=\"server\" file=\"ssl.rs\" section=\"ssl\" >}}\n";
+
+        assert_eq!(
+            parse(page),
+            vec![
+                Include {
+                    line: 2,
+                    example: Some("server".to_owned()),
+                    file: "main.rs".to_owned(),
+                    section: Some("main".to_owned()),
+                },
+                Include {
+                    line: 3,
+                    example: Some("server".to_owned()),
+                    file: "ssl.rs".to_owned(),
+                    section: Some("ssl".to_owned()),
+                },
+            ]
+        );
+    }
+}
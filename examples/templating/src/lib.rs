@@ -0,0 +1,318 @@
+//! HTML templates for actix-web handlers, rendered with Tera.
+//!
+//! [`Templates`] is shared through `web::Data` and the [`Template`]
+//! responder renders one of them. The sources are compiled into the binary;
+//! debug builds can read them from disk again whenever they change.
+
+// <templates>
+use std::collections::HashMap;
+use std::error::Error as _;
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+use std::time::SystemTime;
+use std::{fmt, fs, io};
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use tera::{Context, Tera};
+
+pub struct Templates {
+    tera: RwLock<Tera>,
+    reload: Option<Reload>,
+}
+
+/// Where to look for changed sources, and when they were last read
+struct Reload {
+    dir: PathBuf,
+    modified: Mutex<HashMap<String, SystemTime>>,
+}
+
+impl Templates {
+    /// Parses `(name, source)` pairs, usually `include_str!`-ed. Names
+    /// ending in `.html` are autoescaped.
+    pub fn new(sources: &[(&str, &str)]) -> Result<Templates, TemplateError> {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(sources.iter().copied())?;
+
+        Ok(Templates {
+            tera: RwLock::new(tera),
+            reload: None,
+        })
+    }
+
+    /// In debug builds, reads a template from `dir/<name>` again whenever
+    /// that file changes. Missing files keep their compiled-in source.
+    /// Release builds always use the compiled-in sources.
+    pub fn reload_from(mut self, dir: impl Into<PathBuf>) -> Self {
+        if cfg!(debug_assertions) {
+            self.reload = Some(Reload {
+                dir: dir.into(),
+                modified: Mutex::new(HashMap::new()),
+            });
+        }
+        self
+    }
+
+    pub fn render(
+        &self,
+        name: &str,
+        ctx: &Context,
+    ) -> Result<String, TemplateError> {
+        self.reload()?;
+        Ok(self.tera.read().unwrap().render(name, ctx)?)
+    }
+
+    fn reload(&self) -> Result<(), TemplateError> {
+        let reload = match self.reload {
+            Some(ref reload) => reload,
+            None => return Ok(()),
+        };
+        // one reload at a time, renders go on with the old sources meanwhile
+        let mut modified = reload.modified.lock().unwrap();
+
+        let names: Vec<String> = self
+            .tera
+            .read()
+            .unwrap()
+            .get_template_names()
+            .map(String::from)
+            .collect();
+
+        let mut changed = Vec::new();
+        for name in names {
+            let path = reload.dir.join(&name);
+            if let Ok(mtime) = fs::metadata(&path).and_then(|m| m.modified()) {
+                if modified.get(&name) != Some(&mtime) {
+                    changed.push((name, fs::read_to_string(&path)?, mtime));
+                }
+            }
+        }
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        // all at once, as templates may extend each other
+        self.tera.write().unwrap().add_raw_templates(
+            changed.iter().map(|(name, source, _)| (name, source)),
+        )?;
+        for (name, _, mtime) in changed {
+            log::info!("reloaded template {}", name);
+            modified.insert(name, mtime);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    /// Parsing or rendering failed
+    Tera(tera::Error),
+    /// A changed template could not be read
+    Io(io::Error),
+    /// No `web::Data<Templates>` was registered
+    NotConfigured,
+}
+
+impl From<tera::Error> for TemplateError {
+    fn from(err: tera::Error) -> Self {
+        TemplateError::Tera(err)
+    }
+}
+
+impl From<io::Error> for TemplateError {
+    fn from(err: io::Error) -> Self {
+        TemplateError::Io(err)
+    }
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::Tera(err) => {
+                // Tera explains the actual problem in the sources
+                write!(f, "{}", err)?;
+                let mut source = err.source();
+                while let Some(err) = source {
+                    write!(f, ": {}", err)?;
+                    source = err.source();
+                }
+                Ok(())
+            }
+            TemplateError::Io(err) => {
+                write!(f, "cannot read template: {}", err)
+            }
+            TemplateError::NotConfigured => {
+                f.write_str("no templates registered with the app")
+            }
+        }
+    }
+}
+
+/// The details are only shown in debug builds, they are meant for the
+/// developer editing the templates.
+impl ResponseError for TemplateError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        log::error!("{}", self);
+
+        let mut res = HttpResponse::InternalServerError();
+        if cfg!(debug_assertions) {
+            res.content_type("text/plain; charset=utf-8")
+                .body(self.to_string())
+        } else {
+            res.finish()
+        }
+    }
+}
+// </templates>
+
+// <template-responder>
+use actix_web::{web, HttpRequest, Responder};
+use futures::future::{ready, Ready};
+use serde::Serialize;
+
+/// Renders a template from the app's `web::Data<Templates>` as HTML.
+pub struct Template {
+    name: &'static str,
+    context: Context,
+}
+
+impl Template {
+    pub fn new(name: &'static str) -> Self {
+        Template {
+            name,
+            context: Context::new(),
+        }
+    }
+
+    /// Adds a variable to the context
+    pub fn with<T: Serialize + ?Sized>(
+        mut self,
+        key: &str,
+        value: &T,
+    ) -> Self {
+        self.context.insert(key, value);
+        self
+    }
+
+    pub fn render(&self, req: &HttpRequest) -> Result<String, TemplateError> {
+        let templates = req
+            .app_data::<web::Data<Templates>>()
+            .ok_or(TemplateError::NotConfigured)?;
+        templates.render(self.name, &self.context)
+    }
+}
+
+impl Responder for Template {
+    type Error = TemplateError;
+    type Future = Ready<Result<HttpResponse, TemplateError>>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        ready(self.render(req).map(|body| {
+            HttpResponse::Ok()
+                .content_type("text/html; charset=utf-8")
+                .body(body)
+        }))
+    }
+}
+// </template-responder>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    const LAYOUT: &str = "<title>{% block title %}{% endblock %}</title>";
+    const PAGE: &str = r#"{% extends "layout.html" %}
+{% block title %}Hello {{ name }}{% endblock %}"#;
+
+    fn templates() -> Templates {
+        Templates::new(&[("layout.html", LAYOUT), ("page.html", PAGE)])
+            .unwrap()
+    }
+
+    async fn page(name: web::Path<String>) -> Template {
+        Template::new("page.html").with("name", name.as_str())
+    }
+
+    async fn missing() -> Template {
+        Template::new("missing.html")
+    }
+
+    #[actix_rt::test]
+    async fn test_template_responder() {
+        let mut app = test::init_service(
+            App::new()
+                .data(templates())
+                .route("/page/{name}", web::get().to(page))
+                .route("/missing", web::get().to(missing)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/page/%3Cferris%3E")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(
+            resp.headers().get("content-type").unwrap(),
+            "text/html; charset=utf-8"
+        );
+        let body = test::read_body(resp).await;
+        assert_eq!(body, "<title>Hello &lt;ferris&gt;</title>");
+
+        let req = test::TestRequest::get().uri("/missing").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = test::read_body(resp).await;
+        assert!(std::str::from_utf8(&body).unwrap().contains("missing.html"));
+    }
+
+    #[actix_rt::test]
+    async fn test_not_configured() {
+        let mut app = test::init_service(
+            App::new().route("/page/{name}", web::get().to(page)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/page/ferris").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn test_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let templates = templates().reload_from(dir.path());
+        let ctx =
+            Context::from_serialize(serde_json::json!({ "name": "ferris" }))
+                .unwrap();
+
+        // without a file on disk the compiled-in source is used
+        let html = templates.render("page.html", &ctx).unwrap();
+        assert_eq!(html, "<title>Hello ferris</title>");
+
+        fs::write(
+            dir.path().join("layout.html"),
+            "<h1>{% block title %}{% endblock %}</h1>",
+        )
+        .unwrap();
+        let html = templates.render("page.html", &ctx).unwrap();
+        assert_eq!(html, "<h1>Hello ferris</h1>");
+
+        // a broken edit is reported, and picked up again once it is fixed
+        let page = dir.path().join("page.html");
+        fs::write(&page, "{% if %}").unwrap();
+        let err = templates.render("page.html", &ctx).unwrap_err();
+        assert!(err.to_string().starts_with("Failed to parse 'page.html'"));
+
+        fs::write(&page, "Bye {{ name }}").unwrap();
+        // the fix may land within the timestamp resolution of the filesystem
+        let mtime = SystemTime::now() + std::time::Duration::from_secs(1);
+        let mtime = filetime::FileTime::from_system_time(mtime);
+        filetime::set_file_mtime(&page, mtime).unwrap();
+        let html = templates.render("page.html", &ctx).unwrap();
+        assert_eq!(html, "Bye ferris");
+    }
+}
@@ -1,5 +1,6 @@
 pub mod integration_one;
 pub mod integration_two;
+pub mod sse;
 pub mod stream_response;
 use actix_web::{http, web, App, HttpRequest, HttpResponse};
 
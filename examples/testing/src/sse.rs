@@ -0,0 +1,390 @@
+// <sse-broadcaster>
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use actix_web::http::{header, ContentEncoding};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use futures::channel::mpsc;
+use futures::StreamExt;
+use serde::Deserialize;
+
+/// How long clients wait before reconnecting after the stream ends
+const RETRY_MS: u32 = 3000;
+
+/// Events buffered per client before it is considered too slow and dropped
+const CLIENT_BUFFER: usize = 16;
+
+#[derive(Clone)]
+struct Event {
+    id: u64,
+    event: Option<String>,
+    data: String,
+}
+
+impl Event {
+    fn to_bytes(&self) -> web::Bytes {
+        let mut msg = format!("id: {}\n", self.id);
+        if let Some(ref event) = self.event {
+            msg.push_str(&format!("event: {}\n", event));
+        }
+        // every line of a multi-line payload needs its own `data:` field,
+        // and an event without one is ignored by browsers
+        let data = self.data.replace("\r\n", "\n");
+        for line in data.split(['\r', '\n']) {
+            msg.push_str(&format!("data: {}\n", line));
+        }
+        msg.push('\n');
+
+        web::Bytes::from(msg)
+    }
+}
+
+struct Inner {
+    clients: Vec<mpsc::Sender<web::Bytes>>,
+    history: VecDeque<Event>,
+    next_id: u64,
+}
+
+/// Fans events out to all subscribed clients and keeps the last few of
+/// them around for clients that reconnect with `Last-Event-ID`.
+pub struct Broadcaster {
+    inner: Mutex<Inner>,
+    history_size: usize,
+}
+
+impl Broadcaster {
+    pub fn new(history_size: usize) -> Self {
+        Broadcaster {
+            inner: Mutex::new(Inner {
+                clients: Vec::new(),
+                history: VecDeque::with_capacity(history_size),
+                next_id: 1,
+            }),
+            history_size,
+        }
+    }
+
+    /// Creates a broadcaster that sends a keep-alive comment to all clients
+    /// every `ping_interval`. Must be called from within the actix runtime.
+    pub fn create(
+        history_size: usize,
+        ping_interval: Duration,
+    ) -> web::Data<Self> {
+        let me = web::Data::new(Broadcaster::new(history_size));
+
+        let broadcaster = me.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(ping_interval);
+            loop {
+                interval.tick().await;
+                broadcaster.ping();
+            }
+        });
+
+        me
+    }
+
+    /// Registers a new client. If `last_event_id` is given, all buffered
+    /// events after it are replayed first.
+    pub fn subscribe(
+        &self,
+        last_event_id: Option<u64>,
+    ) -> mpsc::Receiver<web::Bytes> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let replay: Vec<_> = match last_event_id {
+            Some(last_id) => inner
+                .history
+                .iter()
+                .filter(|event| event.id > last_id)
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let (mut tx, rx) = mpsc::channel(CLIENT_BUFFER + replay.len() + 1);
+
+        // the channel has room for everything queued here
+        let _ =
+            tx.try_send(web::Bytes::from(format!("retry: {}\n\n", RETRY_MS)));
+        for event in replay {
+            let _ = tx.try_send(event.to_bytes());
+        }
+
+        inner.clients.push(tx);
+        rx
+    }
+
+    /// Sends an event to every client and returns its id.
+    pub fn send(&self, event: Option<&str>, data: &str) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+
+        let event = Event {
+            id: inner.next_id,
+            event: event.map(str::to_owned),
+            data: data.to_owned(),
+        };
+        inner.next_id += 1;
+
+        if inner.history.len() == self.history_size {
+            inner.history.pop_front();
+        }
+        if self.history_size > 0 {
+            inner.history.push_back(event.clone());
+        }
+
+        Self::send_to_all(&mut inner.clients, event.to_bytes());
+        event.id
+    }
+
+    /// Sends a comment line, which keeps idle connections (and proxies in
+    /// between) from timing out and detects clients that have gone away.
+    pub fn ping(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        Self::send_to_all(
+            &mut inner.clients,
+            web::Bytes::from_static(b": ping\n\n"),
+        );
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.inner.lock().unwrap().clients.len()
+    }
+
+    /// Clients that disconnected, or that fell too far behind, are removed.
+    /// The latter can reconnect and catch up through `Last-Event-ID`.
+    fn send_to_all(
+        clients: &mut Vec<mpsc::Sender<web::Bytes>>,
+        msg: web::Bytes,
+    ) {
+        *clients = clients
+            .drain(..)
+            .filter_map(|mut client| {
+                client.try_send(msg.clone()).ok().map(|_| client)
+            })
+            .collect();
+    }
+}
+
+async fn events(
+    req: HttpRequest,
+    broadcaster: web::Data<Broadcaster>,
+) -> HttpResponse {
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.trim().parse().ok());
+
+    let rx = broadcaster.subscribe(last_event_id);
+
+    HttpResponse::Ok()
+        .set_header(header::CONTENT_TYPE, "text/event-stream")
+        .set_header(header::CACHE_CONTROL, "no-cache")
+        .set_header(
+            header::CONTENT_ENCODING,
+            ContentEncoding::Identity.as_str(),
+        )
+        .streaming(rx.map(Ok::<_, Error>))
+}
+
+#[derive(Deserialize)]
+pub struct NewEvent {
+    event: Option<String>,
+    data: String,
+}
+
+async fn publish(
+    broadcaster: web::Data<Broadcaster>,
+    new_event: web::Json<NewEvent>,
+) -> HttpResponse {
+    // a line break would let the client smuggle in extra fields
+    if let Some(ref event) = new_event.event {
+        if event.contains(['\r', '\n']) {
+            return HttpResponse::BadRequest()
+                .body("event names cannot contain line breaks");
+        }
+    }
+
+    let id = broadcaster.send(new_event.event.as_deref(), &new_event.data);
+    HttpResponse::Ok().json(id)
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/events", web::get().to(events))
+        .route("/events", web::post().to(publish));
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    use actix_web::{App, HttpServer};
+
+    let broadcaster = Broadcaster::create(100, Duration::from_secs(10));
+
+    HttpServer::new(move || {
+        App::new().app_data(broadcaster.clone()).configure(config)
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await
+}
+// </sse-broadcaster>
+
+// <sse-tests>
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures_util::stream::StreamExt;
+
+    use actix_web::{http, test, web, App};
+
+    #[actix_rt::test]
+    async fn test_broadcast() {
+        let broadcaster = web::Data::new(Broadcaster::new(10));
+        let mut app = test::init_service(
+            App::new().app_data(broadcaster.clone()).configure(config),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/events").to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success());
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/event-stream"
+        );
+
+        // the reconnection delay comes first
+        let (bytes, mut resp) = resp.take_body().into_future().await;
+        assert_eq!(
+            bytes.unwrap().unwrap(),
+            web::Bytes::from_static(b"retry: 3000\n\n")
+        );
+
+        // events published through the API reach subscribers
+        let req = test::TestRequest::post()
+            .uri("/events")
+            .set_json(&serde_json::json!({ "event": "greeting", "data": "hello\nworld" }))
+            .to_request();
+        let id: u64 = test::read_response_json(&mut app, req).await;
+        assert_eq!(id, 1);
+
+        let (bytes, mut resp) = resp.take_body().into_future().await;
+        assert_eq!(
+            bytes.unwrap().unwrap(),
+            web::Bytes::from_static(
+                b"id: 1\nevent: greeting\ndata: hello\ndata: world\n\n"
+            )
+        );
+
+        broadcaster.ping();
+        let (bytes, _) = resp.take_body().into_future().await;
+        assert_eq!(
+            bytes.unwrap().unwrap(),
+            web::Bytes::from_static(b": ping\n\n")
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_event_fields() {
+        let broadcaster = web::Data::new(Broadcaster::new(10));
+        let mut app = test::init_service(
+            App::new().app_data(broadcaster.clone()).configure(config),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/events")
+            .set_json(
+                &serde_json::json!({ "event": "a\ndata: b", "data": "c" }),
+            )
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+        let mut rx = broadcaster.subscribe(None);
+        rx.next().await.unwrap();
+
+        // empty payloads still get a `data:` line, CR counts as a line break
+        broadcaster.send(Some("empty"), "");
+        assert_eq!(
+            rx.next().await.unwrap(),
+            web::Bytes::from_static(b"id: 1\nevent: empty\ndata: \n\n")
+        );
+
+        broadcaster.send(None, "one\r\ntwo\rthree");
+        assert_eq!(
+            rx.next().await.unwrap(),
+            web::Bytes::from_static(
+                b"id: 2\ndata: one\ndata: two\ndata: three\n\n"
+            )
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_replay_from_last_event_id() {
+        let broadcaster = web::Data::new(Broadcaster::new(2));
+        let mut app = test::init_service(
+            App::new().app_data(broadcaster.clone()).configure(config),
+        )
+        .await;
+
+        for data in &["one", "two", "three", "four"] {
+            broadcaster.send(None, data);
+        }
+
+        // event 2 has already been pushed out of the buffer
+        let req = test::TestRequest::get()
+            .uri("/events")
+            .header("Last-Event-ID", "1")
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+
+        let (bytes, mut resp) = resp.take_body().into_future().await;
+        assert_eq!(
+            bytes.unwrap().unwrap(),
+            web::Bytes::from_static(b"retry: 3000\n\n")
+        );
+
+        let (bytes, mut resp) = resp.take_body().into_future().await;
+        assert_eq!(
+            bytes.unwrap().unwrap(),
+            web::Bytes::from_static(b"id: 3\ndata: three\n\n")
+        );
+
+        let (bytes, _) = resp.take_body().into_future().await;
+        assert_eq!(
+            bytes.unwrap().unwrap(),
+            web::Bytes::from_static(b"id: 4\ndata: four\n\n")
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_disconnected_clients_are_removed() {
+        let broadcaster = web::Data::new(Broadcaster::new(10));
+        let mut app = test::init_service(
+            App::new().app_data(broadcaster.clone()).configure(config),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/events").to_request();
+        let kept = test::call_service(&mut app, req).await;
+        let req = test::TestRequest::get().uri("/events").to_request();
+        let dropped = test::call_service(&mut app, req).await;
+        assert_eq!(broadcaster.client_count(), 2);
+
+        drop(dropped);
+        broadcaster.ping();
+        assert_eq!(broadcaster.client_count(), 1);
+
+        // a client that never reads is dropped once its buffer is full
+        for _ in 0..=CLIENT_BUFFER {
+            broadcaster.send(None, "spam");
+        }
+        assert_eq!(broadcaster.client_count(), 0);
+
+        drop(kept);
+    }
+}
+// </sse-tests>
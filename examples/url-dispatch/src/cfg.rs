@@ -1,23 +1,55 @@
 use actix_web::{guard, web, App, HttpResponse};
 
-#[rustfmt::skip]
+// <cfg>
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/path").route(
+            web::route()
+                .guard(guard::Get())
+                .guard(guard::Header("content-type", "text/plain"))
+                .to(HttpResponse::Ok),
+        ),
+    );
+}
+// </cfg>
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     use actix_web::HttpServer;
 
-    HttpServer::new(|| {
-// <cfg>
-App::new().service(
-    web::resource("/path").route(
-        web::route()
-            .guard(guard::Get())
-            .guard(guard::Header("content-type", "text/plain"))
-            .to(|| HttpResponse::Ok()),
-    ),
-)
-// </cfg>
-    })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+    HttpServer::new(|| App::new().configure(configure))
+        .bind("127.0.0.1:8080")?
+        .run()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test};
+
+    #[actix_rt::test]
+    async fn test_cfg() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/path")
+            .header("content-type", "text/plain")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // the resource exists, but none of its routes match
+        let req = test::TestRequest::get().uri("/path").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let req = test::TestRequest::post()
+            .uri("/path")
+            .header("content-type", "text/plain")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
 }
@@ -1,24 +1,55 @@
 use actix_web::{guard, web, App, HttpRequest, HttpResponse, HttpServer, Responder};
 
-#[allow(dead_code)]
 async fn index(_req: HttpRequest) -> impl Responder {
     "Welcome!"
 }
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/").route(web::get().to(index)));
+}
+
 // <default>
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     HttpServer::new(|| {
-        App::new()
-            .service(web::resource("/").route(web::get().to(index)))
-            .default_service(
-                web::route()
-                    .guard(guard::Not(guard::Get()))
-                    .to(|| HttpResponse::MethodNotAllowed()),
-            )
+        App::new().configure(configure).default_service(
+            web::route()
+                .guard(guard::Not(guard::Get()))
+                .to(HttpResponse::MethodNotAllowed),
+        )
     })
     .bind("127.0.0.1:8080")?
     .run()
     .await
 }
 // </default>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test};
+
+    #[actix_rt::test]
+    async fn test_default() {
+        let mut app = test::init_service(
+            App::new().configure(configure).default_service(
+                web::route()
+                    .guard(guard::Not(guard::Get()))
+                    .to(HttpResponse::MethodNotAllowed),
+            ),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Welcome!");
+
+        let req = test::TestRequest::post().uri("/").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let req = test::TestRequest::post().uri("/missing").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
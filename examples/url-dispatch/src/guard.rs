@@ -1,5 +1,5 @@
 // <guard>
-use actix_web::{dev::RequestHead, guard::Guard, http, HttpResponse};
+use actix_web::{dev::RequestHead, guard::Guard, http, web, HttpResponse};
 
 struct ContentTypeHeader;
 
@@ -9,20 +9,43 @@ impl Guard for ContentTypeHeader {
     }
 }
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route(
+        "/",
+        web::route().guard(ContentTypeHeader).to(HttpResponse::Ok),
+    );
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    use actix_web::{web, App, HttpServer};
-
-    HttpServer::new(|| {
-        App::new().route(
-            "/",
-            web::route()
-                .guard(ContentTypeHeader)
-                .to(|| HttpResponse::Ok()),
-        )
-    })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+    use actix_web::{App, HttpServer};
+
+    HttpServer::new(|| App::new().configure(configure))
+        .bind("127.0.0.1:8080")?
+        .run()
+        .await
 }
 // </guard>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    #[actix_rt::test]
+    async fn test_guard() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .header(http::header::CONTENT_TYPE, "text/plain")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,50 @@
+pub mod cfg;
+pub mod dhandler;
+pub mod guard;
+pub mod guard2;
+pub mod minfo;
+pub mod norm;
+pub mod norm2;
+pub mod path;
+pub mod path2;
+pub mod resource;
+pub mod scope;
+pub mod url_ext;
+pub mod urls;
+
+// <main>
+use actix_web::{web, HttpResponse};
+
+async fn index() -> HttpResponse {
+    HttpResponse::Ok().body("Hello")
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/", web::get().to(index))
+        .route("/user", web::post().to(index));
+}
+// </main>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    #[actix_rt::test]
+    async fn test_routes() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Hello");
+
+        let req = test::TestRequest::post().uri("/user").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Hello");
+
+        let req = test::TestRequest::get().uri("/user").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,5 +1,5 @@
 // <minfo>
-use actix_web::{get, App, HttpRequest, HttpServer, Result};
+use actix_web::{get, web, App, HttpRequest, HttpServer, Result};
 
 #[get("/a/{v1}/{v2}/")]
 async fn index(req: HttpRequest) -> Result<String> {
@@ -9,11 +9,31 @@ async fn index(req: HttpRequest) -> Result<String> {
     Ok(format!("Values {} {} {} {}", v1, v2, v3, v4))
 }
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| App::new().service(index))
+    HttpServer::new(|| App::new().configure(configure))
         .bind("127.0.0.1:8080")?
         .run()
         .await
 }
 // </minfo>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+
+    #[actix_rt::test]
+    async fn test_match_info() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/a/1/2/").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Values 1 2 1 2");
+    }
+}
@@ -1,23 +1,47 @@
-#![allow(dead_code)]
-
 // <norm>
-use actix_web::{middleware, HttpResponse};
+use actix_web::{middleware, web, HttpResponse};
 
 async fn index() -> HttpResponse {
     HttpResponse::Ok().body("Hello")
 }
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/resource/", web::to(index));
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    use actix_web::{web, App, HttpServer};
+    use actix_web::{App, HttpServer};
 
     HttpServer::new(|| {
         App::new()
             .wrap(middleware::NormalizePath::default())
-            .route("/resource/", web::to(index))
+            .configure(configure)
     })
     .bind("127.0.0.1:8080")?
     .run()
     .await
 }
 // </norm>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn test_normalize() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(middleware::NormalizePath::default())
+                .configure(configure),
+        )
+        .await;
+
+        for uri in &["/resource/", "/resource", "//resource//"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let body = test::read_response(&mut app, req).await;
+            assert_eq!(body, "Hello");
+        }
+    }
+}
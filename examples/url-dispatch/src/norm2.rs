@@ -5,6 +5,10 @@ fn index() -> HttpResponse {
     HttpResponse::Ok().body("Hello")
 }
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index);
+}
+
 // <norm>
 use actix_web::{get, http::Method, middleware, web, App, HttpServer};
 
@@ -13,7 +17,7 @@ async fn main() -> std::io::Result<()> {
     HttpServer::new(|| {
         App::new()
             .wrap(middleware::NormalizePath::default())
-            .service(index)
+            .configure(configure)
             .default_service(web::route().method(Method::GET))
     })
     .bind("127.0.0.1:8080")?
@@ -21,3 +25,28 @@ async fn main() -> std::io::Result<()> {
     .await
 }
 // </norm>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test};
+
+    #[actix_rt::test]
+    async fn test_normalize() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(middleware::NormalizePath::default())
+                .configure(configure)
+                .default_service(web::route().method(Method::GET)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/resource").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Hello");
+
+        let req = test::TestRequest::post().uri("/resource").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
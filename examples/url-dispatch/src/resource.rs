@@ -1,19 +1,52 @@
 // <resource>
-use actix_web::{guard, web, App, HttpResponse};
+use actix_web::{guard, web, App, HttpResponse, HttpServer};
 
-fn index() -> HttpResponse {
+async fn index() -> HttpResponse {
     HttpResponse::Ok().body("Hello")
 }
 
-pub fn main() {
-    App::new()
-        .service(web::resource("/prefix").to(index))
-        .service(
-            web::resource("/user/{name}")
-                .name("user_detail")
-                .guard(guard::Header("content-type", "application/json"))
-                .route(web::get().to(|| HttpResponse::Ok()))
-                .route(web::put().to(|| HttpResponse::Ok())),
-        );
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/prefix").to(index)).service(
+        web::resource("/user/{name}")
+            .name("user_detail")
+            .guard(guard::Header("content-type", "application/json"))
+            .route(web::get().to(HttpResponse::Ok))
+            .route(web::put().to(HttpResponse::Ok)),
+    );
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    HttpServer::new(|| App::new().configure(configure))
+        .bind("127.0.0.1:8080")?
+        .run()
+        .await
 }
 // </resource>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test};
+
+    #[actix_rt::test]
+    async fn test_resource() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/prefix").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Hello");
+
+        let req = test::TestRequest::put()
+            .uri("/user/ferris")
+            .header("content-type", "application/json")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get().uri("/user/ferris").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -11,17 +11,43 @@ async fn user_detail(path: web::Path<(u32,)>) -> HttpResponse {
     HttpResponse::Ok().body(format!("User detail: {}", path.into_inner().0))
 }
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/users")
+            .service(show_users)
+            .service(user_detail),
+    );
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| {
-        App::new().service(
-            web::scope("/users")
-                .service(show_users)
-                .service(user_detail),
-        )
-    })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+    HttpServer::new(|| App::new().configure(configure))
+        .bind("127.0.0.1:8080")?
+        .run()
+        .await
 }
 // </scope>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test};
+
+    #[actix_rt::test]
+    async fn test_scope() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/users/show").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "Show users");
+
+        let req = test::TestRequest::get().uri("/users/show/7").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "User detail: 7");
+
+        let req = test::TestRequest::get().uri("/show").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
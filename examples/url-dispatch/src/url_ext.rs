@@ -1,5 +1,5 @@
 // <ext>
-use actix_web::{get, App, HttpRequest, HttpServer, Responder};
+use actix_web::{get, web, App, HttpRequest, HttpServer, Responder};
 
 #[get("/")]
 async fn index(req: HttpRequest) -> impl Responder {
@@ -9,15 +9,32 @@ async fn index(req: HttpRequest) -> impl Responder {
     url.into_string()
 }
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(index)
+        .external_resource("youtube", "https://youtube.com/watch/{video_id}");
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| {
-        App::new()
-            .service(index)
-            .external_resource("youtube", "https://youtube.com/watch/{video_id}")
-    })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+    HttpServer::new(|| App::new().configure(configure))
+        .bind("127.0.0.1:8080")?
+        .run()
+        .await
 }
 // </ext>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+
+    #[actix_rt::test]
+    async fn test_external_resource() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "https://youtube.com/watch/oHg5SJYRHA0");
+    }
+}
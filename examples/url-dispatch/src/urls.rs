@@ -1,5 +1,7 @@
 // <url>
-use actix_web::{get, guard, http::header, HttpRequest, HttpResponse, Result};
+use actix_web::{
+    get, guard, http::header, web, HttpRequest, HttpResponse, Result,
+};
 
 #[get("/test/")]
 async fn index(req: HttpRequest) -> Result<HttpResponse> {
@@ -10,22 +12,45 @@ async fn index(req: HttpRequest) -> Result<HttpResponse> {
         .finish())
 }
 
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/test/{a}/{b}/{c}")
+            .name("foo") // <- set resource name, then it could be used in `url_for`
+            .guard(guard::Get())
+            .to(HttpResponse::Ok),
+    )
+    .service(index);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    use actix_web::{web, App, HttpServer};
-
-    HttpServer::new(|| {
-        App::new()
-            .service(
-                web::resource("/test/{a}/{b}/{c}")
-                    .name("foo") // <- set resource name, then it could be used in `url_for`
-                    .guard(guard::Get())
-                    .to(|| HttpResponse::Ok()),
-            )
-            .service(index)
-    })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+    use actix_web::{App, HttpServer};
+
+    HttpServer::new(|| App::new().configure(configure))
+        .bind("127.0.0.1:8080")?
+        .run()
+        .await
 }
 // </url>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    #[actix_rt::test]
+    async fn test_url_for() {
+        let mut app =
+            test::init_service(App::new().configure(configure)).await;
+
+        let req = test::TestRequest::get().uri("/test/").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        let location = resp.headers().get(header::LOCATION).unwrap();
+        assert_eq!(location, "http://localhost:8080/test/1/2/3");
+
+        let req = test::TestRequest::get().uri("/test/1/2/3").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
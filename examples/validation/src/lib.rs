@@ -0,0 +1,606 @@
+//! Declarative validation for actix-web extractors.
+//!
+//! Types describe their rules by implementing [`Validate`], and wrapping an
+//! extractor in [`Validated`] runs those rules after deserialization.
+//! Failures are rejected with `422 Unprocessable Entity` and a list of
+//! field errors.
+
+use std::fmt;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+
+/// Describes the rules a value has to satisfy.
+pub trait Validate {
+    fn validate(&self, v: &mut Validator);
+}
+
+/// One failed rule. `field` is a path like `address.city` or `tags[2]`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// Collects the errors of all fields, reporting the first failed rule of
+/// each field.
+#[derive(Debug, Default)]
+pub struct Validator {
+    prefix: String,
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    fn path(&self, name: &str) -> String {
+        if self.prefix.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{}.{}", self.prefix, name)
+        }
+    }
+
+    fn check<T: ?Sized>(
+        &mut self,
+        field: String,
+        value: &T,
+        rules: &[&dyn Rule<T>],
+    ) {
+        for rule in rules {
+            if let Err(message) = rule.check(value) {
+                self.errors.push(FieldError {
+                    field,
+                    code: rule.code(),
+                    message,
+                });
+                return;
+            }
+        }
+    }
+
+    pub fn field<T: ?Sized>(
+        &mut self,
+        name: &str,
+        value: &T,
+        rules: &[&dyn Rule<T>],
+    ) -> &mut Self {
+        self.check(self.path(name), value, rules);
+        self
+    }
+
+    /// Like `field`, but a missing value is valid
+    pub fn optional<T>(
+        &mut self,
+        name: &str,
+        value: &Option<T>,
+        rules: &[&dyn Rule<T>],
+    ) -> &mut Self {
+        if let Some(value) = value {
+            self.check(self.path(name), value, rules);
+        }
+        self
+    }
+
+    /// Applies `rules` to every item, reported as `name[index]`
+    pub fn each<T>(
+        &mut self,
+        name: &str,
+        values: &[T],
+        rules: &[&dyn Rule<T>],
+    ) -> &mut Self {
+        for (idx, value) in values.iter().enumerate() {
+            self.check(format!("{}[{}]", self.path(name), idx), value, rules);
+        }
+        self
+    }
+
+    /// Runs the rules of a nested value, prefixing its fields with `name`
+    pub fn nested<T: Validate>(&mut self, name: &str, value: &T) -> &mut Self {
+        let mut nested = Validator {
+            prefix: self.path(name),
+            errors: Vec::new(),
+        };
+        value.validate(&mut nested);
+        self.errors.append(&mut nested.errors);
+        self
+    }
+}
+
+/// Checks all rules of `value`.
+pub fn validate<T: Validate + ?Sized>(
+    value: &T,
+) -> Result<(), ValidationErrors> {
+    let mut v = Validator::default();
+    value.validate(&mut v);
+
+    if v.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationErrors { errors: v.errors })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidationErrors {
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    /// The error reported for `field`, if any
+    pub fn field(&self, field: &str) -> Option<&FieldError> {
+        self.errors.iter().find(|err| err.field == field)
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("validation failed")?;
+        for (idx, err) in self.errors.iter().enumerate() {
+            let sep = if idx == 0 { ": " } else { ", " };
+            write!(f, "{}{} {}", sep, err.field, err.message)?;
+        }
+        Ok(())
+    }
+}
+
+pub trait Rule<T: ?Sized> {
+    /// Machine readable name of the rule, e.g. `length`
+    fn code(&self) -> &'static str;
+
+    /// Returns a message for the user if `value` breaks the rule
+    fn check(&self, value: &T) -> Result<(), String>;
+}
+
+pub struct Length {
+    min: usize,
+    max: usize,
+}
+
+/// Number of characters of a string or items of a list, both inclusive
+pub fn length(min: usize, max: usize) -> Length {
+    Length { min, max }
+}
+
+impl Length {
+    fn check_len(&self, len: usize, unit: &str) -> Result<(), String> {
+        if len < self.min || len > self.max {
+            Err(format!(
+                "must have between {} and {} {}",
+                self.min, self.max, unit
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Rule<str> for Length {
+    fn code(&self) -> &'static str {
+        "length"
+    }
+
+    fn check(&self, value: &str) -> Result<(), String> {
+        self.check_len(value.chars().count(), "characters")
+    }
+}
+
+impl Rule<String> for Length {
+    fn code(&self) -> &'static str {
+        "length"
+    }
+
+    fn check(&self, value: &String) -> Result<(), String> {
+        Rule::<str>::check(self, value)
+    }
+}
+
+impl<T> Rule<Vec<T>> for Length {
+    fn code(&self) -> &'static str {
+        "length"
+    }
+
+    fn check(&self, value: &Vec<T>) -> Result<(), String> {
+        self.check_len(value.len(), "items")
+    }
+}
+
+pub struct Range<T> {
+    min: T,
+    max: T,
+}
+
+/// A number between `min` and `max`, both inclusive. `NaN` is never in
+/// range.
+pub fn range<T>(min: T, max: T) -> Range<T> {
+    Range { min, max }
+}
+
+impl<T: PartialOrd + fmt::Display> Rule<T> for Range<T> {
+    fn code(&self) -> &'static str {
+        "range"
+    }
+
+    fn check(&self, value: &T) -> Result<(), String> {
+        if *value >= self.min && *value <= self.max {
+            Ok(())
+        } else {
+            Err(format!("must be between {} and {}", self.min, self.max))
+        }
+    }
+}
+
+pub struct Pattern<'a> {
+    regex: &'a Regex,
+    message: &'static str,
+}
+
+/// A string matching `regex`, `message` tells the user what is expected
+pub fn pattern<'a>(regex: &'a Regex, message: &'static str) -> Pattern<'a> {
+    Pattern { regex, message }
+}
+
+impl Rule<str> for Pattern<'_> {
+    fn code(&self) -> &'static str {
+        "pattern"
+    }
+
+    fn check(&self, value: &str) -> Result<(), String> {
+        if self.regex.is_match(value) {
+            Ok(())
+        } else {
+            Err(self.message.to_owned())
+        }
+    }
+}
+
+impl Rule<String> for Pattern<'_> {
+    fn code(&self) -> &'static str {
+        "pattern"
+    }
+
+    fn check(&self, value: &String) -> Result<(), String> {
+        Rule::<str>::check(self, value)
+    }
+}
+
+pub struct Email;
+
+/// A plausible email address; only delivery can prove it exists
+pub fn email() -> Email {
+    Email
+}
+
+static EMAIL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[^@\s]+@[^@\s.]+(\.[^@\s.]+)+$").unwrap());
+
+impl Rule<str> for Email {
+    fn code(&self) -> &'static str {
+        "email"
+    }
+
+    fn check(&self, value: &str) -> Result<(), String> {
+        if value.len() <= 254 && EMAIL.is_match(value) {
+            Ok(())
+        } else {
+            Err(String::from("must be a valid email address"))
+        }
+    }
+}
+
+impl Rule<String> for Email {
+    fn code(&self) -> &'static str {
+        "email"
+    }
+
+    fn check(&self, value: &String) -> Result<(), String> {
+        Rule::<str>::check(self, value)
+    }
+}
+
+pub struct Custom<F> {
+    code: &'static str,
+    check: F,
+}
+
+/// Any other check, `code` names it in the error
+pub fn custom<T, F>(code: &'static str, check: F) -> Custom<F>
+where
+    T: ?Sized,
+    F: Fn(&T) -> Result<(), String>,
+{
+    Custom { code, check }
+}
+
+impl<T: ?Sized, F: Fn(&T) -> Result<(), String>> Rule<T> for Custom<F> {
+    fn code(&self) -> &'static str {
+        self.code
+    }
+
+    fn check(&self, value: &T) -> Result<(), String> {
+        (self.check)(value)
+    }
+}
+
+// <validated>
+use std::ops::Deref;
+
+use actix_web::dev::Payload;
+use actix_web::http::StatusCode;
+use actix_web::{
+    Error, FromRequest, HttpRequest, HttpResponse, ResponseError,
+};
+use futures::future::LocalBoxFuture;
+
+/// Runs the rules of the value extracted by `E`, which is one of
+/// `web::Json`, `web::Form`, `web::Query` or `web::Path`.
+pub struct Validated<E>(pub E);
+
+impl<E> Validated<E> {
+    pub fn into_inner(self) -> E {
+        self.0
+    }
+}
+
+impl<E> Deref for Validated<E> {
+    type Target = E;
+
+    fn deref(&self) -> &E {
+        &self.0
+    }
+}
+
+#[derive(Debug)]
+pub enum Rejection {
+    /// The inner extractor failed, e.g. the body is not valid JSON
+    Extract(Error),
+    Invalid(ValidationErrors),
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Extract(err) => err.fmt(f),
+            Rejection::Invalid(errors) => errors.fmt(f),
+        }
+    }
+}
+
+impl ResponseError for Rejection {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Rejection::Extract(err) => err.as_response_error().status_code(),
+            Rejection::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            Rejection::Extract(err) => {
+                err.as_response_error().error_response()
+            }
+            Rejection::Invalid(errors) => {
+                HttpResponse::UnprocessableEntity().json(errors)
+            }
+        }
+    }
+}
+
+impl<E> FromRequest for Validated<E>
+where
+    E: FromRequest + Deref + 'static,
+    E::Target: Validate,
+    E::Future: 'static,
+{
+    type Error = Rejection;
+    type Future = LocalBoxFuture<'static, Result<Self, Rejection>>;
+    type Config = E::Config;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let extract = E::from_request(req, payload);
+
+        Box::pin(async move {
+            let value = extract
+                .await
+                .map_err(|err| Rejection::Extract(err.into()))?;
+            validate(&*value).map_err(Rejection::Invalid)?;
+            Ok(Validated(value))
+        })
+    }
+}
+// </validated>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App};
+    use serde::Deserialize;
+
+    static SLUG: Lazy<Regex> =
+        Lazy::new(|| Regex::new("^[a-z0-9-]+$").unwrap());
+
+    #[derive(Deserialize)]
+    struct Address {
+        city: String,
+    }
+
+    impl Validate for Address {
+        fn validate(&self, v: &mut Validator) {
+            v.field("city", &self.city, &[&length(1, 10)]);
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct Signup {
+        slug: String,
+        email: String,
+        age: u8,
+        score: f64,
+        nickname: Option<String>,
+        tags: Vec<String>,
+        address: Address,
+    }
+
+    impl Validate for Signup {
+        fn validate(&self, v: &mut Validator) {
+            v.field(
+                "slug",
+                &self.slug,
+                &[
+                    &length(3, 16),
+                    &pattern(&SLUG, "may only contain a-z, 0-9 and -"),
+                ],
+            )
+            .field("email", &self.email, &[&email()])
+            .field("age", &self.age, &[&range(18, 130)])
+            .field("score", &self.score, &[&range(0.0, 1.0)])
+            .optional("nickname", &self.nickname, &[&length(1, 8)])
+            .field("tags", &self.tags, &[&length(0, 2)])
+            .each(
+                "tags",
+                &self.tags,
+                &[&custom("lowercase", |tag: &String| {
+                    if tag.chars().all(|c| !c.is_uppercase()) {
+                        Ok(())
+                    } else {
+                        Err(String::from("must be lowercase"))
+                    }
+                })],
+            )
+            .nested("address", &self.address);
+        }
+    }
+
+    fn signup() -> Signup {
+        Signup {
+            slug: String::from("ferris"),
+            email: String::from("ferris@example.com"),
+            age: 30,
+            score: 0.5,
+            nickname: None,
+            tags: vec![String::from("rust")],
+            address: Address {
+                city: String::from("Oslo"),
+            },
+        }
+    }
+
+    fn codes(value: &Signup) -> Vec<(String, &'static str)> {
+        match validate(value) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .errors
+                .into_iter()
+                .map(|err| (err.field, err.code))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        assert!(codes(&signup()).is_empty());
+
+        let mut value = signup();
+        value.slug = String::from("Ferris");
+        value.email = String::from("ferris@localhost");
+        value.age = 12;
+        value.score = f64::NAN;
+        value.nickname = Some(String::from("far too long"));
+        value.tags = vec![String::from("ok"), String::from("Nope")];
+        value.address.city = String::new();
+
+        assert_eq!(
+            codes(&value),
+            vec![
+                (String::from("slug"), "pattern"),
+                (String::from("email"), "email"),
+                (String::from("age"), "range"),
+                (String::from("score"), "range"),
+                (String::from("nickname"), "length"),
+                (String::from("tags[1]"), "lowercase"),
+                (String::from("address.city"), "length"),
+            ]
+        );
+
+        // only the first failed rule of a field is reported
+        value = signup();
+        value.slug = String::from("X");
+        let errors = validate(&value).unwrap_err();
+        assert_eq!(errors.errors.len(), 1);
+        assert_eq!(
+            errors.field("slug").unwrap().message,
+            "must have between 3 and 16 characters"
+        );
+    }
+
+    #[derive(Deserialize)]
+    struct Info {
+        username: String,
+    }
+
+    impl Validate for Info {
+        fn validate(&self, v: &mut Validator) {
+            v.field("username", &self.username, &[&length(3, 8)]);
+        }
+    }
+
+    async fn json(info: Validated<web::Json<Info>>) -> String {
+        info.username.clone()
+    }
+
+    async fn query(info: Validated<web::Query<Info>>) -> String {
+        info.username.clone()
+    }
+
+    async fn path(info: Validated<web::Path<Info>>) -> String {
+        info.username.clone()
+    }
+
+    #[actix_rt::test]
+    async fn test_extractors() {
+        let mut app = test::init_service(
+            App::new()
+                .route("/json", web::post().to(json))
+                .route("/query", web::get().to(query))
+                .route("/path/{username}", web::get().to(path)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/json")
+            .set_json(&serde_json::json!({ "username": "ferris" }))
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "ferris");
+
+        let req = test::TestRequest::get()
+            .uri("/query?username=ab")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(
+            body,
+            serde_json::json!({ "errors": [{
+                "field": "username",
+                "code": "length",
+                "message": "must have between 3 and 8 characters",
+            }] })
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/path/far-too-long")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // errors of the inner extractor are passed through
+        let req = test::TestRequest::post()
+            .uri("/json")
+            .header("content-type", "application/json")
+            .set_payload("{")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,3 +1,6 @@
+pub mod server;
+pub mod session;
+
 // <websockets>
 use actix::{Actor, StreamHandler};
 use actix_web::{web, App, Error, HttpRequest, HttpResponse, HttpServer};
@@ -0,0 +1,268 @@
+// <chat-server>
+use std::collections::{HashMap, HashSet};
+
+use actix::prelude::*;
+
+/// Chat server sends this message to sessions
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ChatMessage(pub String);
+
+/// New chat session is created, the server replies with the session id
+#[derive(Message)]
+#[rtype(result = "usize")]
+pub struct Connect {
+    pub addr: Recipient<ChatMessage>,
+}
+
+/// Session is disconnected
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Disconnect {
+    pub id: usize,
+}
+
+/// Move a session into a room, creating the room if it does not exist yet
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Join {
+    pub id: usize,
+    pub room: String,
+}
+
+/// Change the nick name of a session; fails if the name is taken
+#[derive(Message)]
+#[rtype(result = "Result<(), String>")]
+pub struct SetNick {
+    pub id: usize,
+    pub nick: String,
+}
+
+/// Send a message to every other session in the sender's room
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Broadcast {
+    pub id: usize,
+    pub msg: String,
+}
+
+/// List of available rooms
+pub struct ListRooms;
+
+impl Message for ListRooms {
+    type Result = Vec<String>;
+}
+
+struct Session {
+    addr: Recipient<ChatMessage>,
+    nick: String,
+    room: String,
+}
+
+/// `ChatServer` keeps track of all connected sessions and the rooms they
+/// are in. Sessions never talk to each other directly, everything goes
+/// through the server.
+pub struct ChatServer {
+    sessions: HashMap<usize, Session>,
+    rooms: HashMap<String, HashSet<usize>>,
+    next_id: usize,
+}
+
+pub const DEFAULT_ROOM: &str = "main";
+
+impl Default for ChatServer {
+    fn default() -> ChatServer {
+        let mut rooms = HashMap::new();
+        rooms.insert(DEFAULT_ROOM.to_owned(), HashSet::new());
+
+        ChatServer {
+            sessions: HashMap::new(),
+            rooms,
+            next_id: 1,
+        }
+    }
+}
+
+impl ChatServer {
+    /// Send message to all sessions in the room except `skip_id`
+    fn send_to_room(&self, room: &str, msg: &str, skip_id: usize) {
+        if let Some(members) = self.rooms.get(room) {
+            for id in members {
+                if *id == skip_id {
+                    continue;
+                }
+                if let Some(session) = self.sessions.get(id) {
+                    let _ = session.addr.do_send(ChatMessage(msg.to_owned()));
+                }
+            }
+        }
+    }
+
+    fn nick_taken(&self, nick: &str) -> bool {
+        self.sessions.values().any(|s| s.nick == nick)
+    }
+
+    fn leave_room(&mut self, id: usize, room: &str) {
+        if let Some(members) = self.rooms.get_mut(room) {
+            members.remove(&id);
+
+            // the default room always exists, other rooms disappear once
+            // the last member has left
+            if members.is_empty() && room != DEFAULT_ROOM {
+                self.rooms.remove(room);
+            }
+        }
+    }
+}
+
+impl Actor for ChatServer {
+    type Context = Context<Self>;
+}
+
+impl Handler<Connect> for ChatServer {
+    type Result = usize;
+
+    fn handle(&mut self, msg: Connect, _: &mut Context<Self>) -> Self::Result {
+        // someone may have picked the guest name for the next id already
+        let (id, nick) = loop {
+            let id = self.next_id;
+            self.next_id += 1;
+
+            let nick = format!("guest{}", id);
+            if !self.nick_taken(&nick) {
+                break (id, nick);
+            }
+        };
+
+        self.send_to_room(DEFAULT_ROOM, &format!("* {} joined", nick), id);
+
+        self.sessions.insert(
+            id,
+            Session {
+                addr: msg.addr,
+                nick,
+                room: DEFAULT_ROOM.to_owned(),
+            },
+        );
+        self.rooms
+            .entry(DEFAULT_ROOM.to_owned())
+            .or_default()
+            .insert(id);
+
+        id
+    }
+}
+
+impl Handler<Disconnect> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: Disconnect, _: &mut Context<Self>) {
+        if let Some(session) = self.sessions.remove(&msg.id) {
+            self.leave_room(msg.id, &session.room);
+            self.send_to_room(
+                &session.room,
+                &format!("* {} left", session.nick),
+                msg.id,
+            );
+        }
+    }
+}
+
+impl Handler<Join> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: Join, _: &mut Context<Self>) {
+        let Join { id, room } = msg;
+
+        let (old_room, nick) = match self.sessions.get_mut(&id) {
+            Some(session) => (
+                std::mem::replace(&mut session.room, room.clone()),
+                session.nick.clone(),
+            ),
+            None => return,
+        };
+
+        self.leave_room(id, &old_room);
+        self.send_to_room(&old_room, &format!("* {} left", nick), id);
+
+        self.rooms.entry(room.clone()).or_default().insert(id);
+        self.send_to_room(&room, &format!("* {} joined", nick), id);
+    }
+}
+
+/// Longest nick name, in characters
+const MAX_NICK_LEN: usize = 32;
+
+/// Nick names may not look like server notices (`* ...`, `! ...`) or contain
+/// the `: ` that separates a sender from their message
+fn valid_nick(nick: &str) -> bool {
+    !nick.is_empty()
+        && nick.chars().count() <= MAX_NICK_LEN
+        && !nick.starts_with(&['*', '!'][..])
+        && !nick
+            .chars()
+            .any(|c| c == ':' || c.is_whitespace() || c.is_control())
+}
+
+impl Handler<SetNick> for ChatServer {
+    type Result = Result<(), String>;
+
+    fn handle(&mut self, msg: SetNick, _: &mut Context<Self>) -> Self::Result {
+        let SetNick { id, nick } = msg;
+
+        if !valid_nick(&nick) {
+            return Err(format!("`{}` is not a valid nick name", nick));
+        }
+        let owner = self
+            .sessions
+            .iter()
+            .find(|(_, session)| session.nick == nick)
+            .map(|(&owner, _)| owner);
+        match owner {
+            // the caller already has it
+            Some(owner) if owner == id => return Ok(()),
+            Some(_) => {
+                return Err(format!("nick name `{}` is already taken", nick))
+            }
+            None => {}
+        }
+
+        let (old_nick, room) = match self.sessions.get_mut(&id) {
+            Some(session) => (
+                std::mem::replace(&mut session.nick, nick.clone()),
+                session.room.clone(),
+            ),
+            None => return Err(String::from("not connected")),
+        };
+
+        self.send_to_room(
+            &room,
+            &format!("* {} is now known as {}", old_nick, nick),
+            id,
+        );
+
+        Ok(())
+    }
+}
+
+impl Handler<Broadcast> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: Broadcast, _: &mut Context<Self>) {
+        if let Some(session) = self.sessions.get(&msg.id) {
+            let text = format!("{}: {}", session.nick, msg.msg);
+            self.send_to_room(&session.room, &text, msg.id);
+        }
+    }
+}
+
+impl Handler<ListRooms> for ChatServer {
+    type Result = MessageResult<ListRooms>;
+
+    fn handle(&mut self, _: ListRooms, _: &mut Context<Self>) -> Self::Result {
+        let mut rooms: Vec<_> = self.rooms.keys().cloned().collect();
+        rooms.sort();
+        MessageResult(rooms)
+    }
+}
+// </chat-server>
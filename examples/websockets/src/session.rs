@@ -0,0 +1,463 @@
+// <chat-session>
+use std::time::{Duration, Instant};
+
+use actix::prelude::*;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+
+use crate::server;
+
+/// How often pings are sent and how long a client may stay silent before
+/// it is dropped.
+#[derive(Clone, Copy)]
+pub struct Heartbeat {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Heartbeat {
+            interval: Duration::from_secs(5),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// One websocket connection, talking to the `ChatServer` on its behalf.
+pub struct ChatSession {
+    id: usize,
+    last_heard: Instant,
+    heartbeat: Heartbeat,
+    server: Addr<server::ChatServer>,
+}
+
+impl ChatSession {
+    /// Ping the client every `heartbeat.interval` and close the connection
+    /// if it has not answered (or sent anything else) for `heartbeat.timeout`.
+    fn start_heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(self.heartbeat.interval, |act, ctx| {
+            if Instant::now().duration_since(act.last_heard)
+                > act.heartbeat.timeout
+            {
+                ctx.close(Some(ws::CloseReason {
+                    code: ws::CloseCode::Away,
+                    description: Some(String::from("heartbeat timed out")),
+                }));
+                ctx.stop();
+                return;
+            }
+
+            ctx.ping(b"");
+        });
+    }
+
+    fn handle_command(
+        &mut self,
+        cmd: &str,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        let mut parts = cmd.splitn(2, ' ');
+        let name = parts.next().unwrap_or("");
+        let arg = parts.next().map(str::trim).unwrap_or("");
+
+        match name {
+            "/join" if !arg.is_empty() => {
+                self.server.do_send(server::Join {
+                    id: self.id,
+                    room: arg.to_owned(),
+                });
+                ctx.text(format!("* joined {}", arg));
+            }
+            "/nick" => {
+                self.server
+                    .send(server::SetNick {
+                        id: self.id,
+                        nick: arg.to_owned(),
+                    })
+                    .into_actor(self)
+                    .then(|res, _, ctx| {
+                        match res {
+                            Ok(Ok(())) => ctx.text("* nick name changed"),
+                            Ok(Err(err)) => ctx.text(format!("! {}", err)),
+                            Err(_) => ctx.stop(),
+                        }
+                        fut::ready(())
+                    })
+                    .wait(ctx);
+            }
+            "/list" => {
+                self.server
+                    .send(server::ListRooms)
+                    .into_actor(self)
+                    .then(|res, _, ctx| {
+                        match res {
+                            Ok(rooms) => ctx.text(format!(
+                                "* rooms: {}",
+                                rooms.join(", ")
+                            )),
+                            Err(_) => ctx.stop(),
+                        }
+                        fut::ready(())
+                    })
+                    .wait(ctx);
+            }
+            _ => ctx.text(format!("! unknown command: {}", cmd)),
+        }
+    }
+}
+
+impl Actor for ChatSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.start_heartbeat(ctx);
+
+        // register with the chat server; nothing else is processed until
+        // the server has assigned us an id
+        let addr = ctx.address();
+        self.server
+            .send(server::Connect {
+                addr: addr.recipient(),
+            })
+            .into_actor(self)
+            .then(|res, act, ctx| {
+                match res {
+                    Ok(id) => act.id = id,
+                    // something is wrong with the chat server
+                    _ => ctx.stop(),
+                }
+                fut::ready(())
+            })
+            .wait(ctx);
+    }
+
+    fn stopping(&mut self, _: &mut Self::Context) -> Running {
+        self.server.do_send(server::Disconnect { id: self.id });
+        Running::Stop
+    }
+}
+
+/// Forward messages from the chat server to the client
+impl Handler<server::ChatMessage> for ChatSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: server::ChatMessage, ctx: &mut Self::Context) {
+        ctx.text(msg.0);
+    }
+}
+
+/// Handle messages from the client
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for ChatSession {
+    fn handle(
+        &mut self,
+        msg: Result<ws::Message, ws::ProtocolError>,
+        ctx: &mut Self::Context,
+    ) {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(err) => {
+                ctx.close(Some(ws::CloseReason {
+                    code: ws::CloseCode::Protocol,
+                    description: Some(err.to_string()),
+                }));
+                ctx.stop();
+                return;
+            }
+        };
+
+        self.last_heard = Instant::now();
+
+        match msg {
+            ws::Message::Ping(msg) => ctx.pong(&msg),
+            ws::Message::Pong(_) => {}
+            ws::Message::Text(text) => {
+                let text = text.trim();
+                if text.starts_with('/') {
+                    self.handle_command(text, ctx);
+                } else {
+                    self.server.do_send(server::Broadcast {
+                        id: self.id,
+                        msg: text.to_owned(),
+                    });
+                }
+            }
+            ws::Message::Binary(_) => {
+                ctx.close(Some(ws::CloseReason {
+                    code: ws::CloseCode::Unsupported,
+                    description: Some(String::from(
+                        "only text messages are supported",
+                    )),
+                }));
+                ctx.stop();
+            }
+            ws::Message::Close(reason) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            ws::Message::Continuation(_) => {
+                ctx.close(Some(ws::CloseReason {
+                    code: ws::CloseCode::Unsupported,
+                    description: Some(String::from(
+                        "fragmented messages are not supported",
+                    )),
+                }));
+                ctx.stop();
+            }
+            ws::Message::Nop => {}
+        }
+    }
+}
+
+pub async fn chat_route(
+    req: HttpRequest,
+    stream: web::Payload,
+    server: web::Data<Addr<server::ChatServer>>,
+    heartbeat: Option<web::Data<Heartbeat>>,
+) -> Result<HttpResponse, Error> {
+    let session = ChatSession {
+        id: 0,
+        last_heard: Instant::now(),
+        heartbeat: heartbeat.map(|hb| *hb.get_ref()).unwrap_or_default(),
+        server: server.get_ref().clone(),
+    };
+
+    ws::start(session, &req, stream)
+}
+// </chat-session>
+
+// <chat-main>
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    use actix_web::{App, HttpServer};
+
+    // a single chat server is shared by all workers
+    let server = server::ChatServer::default().start();
+
+    HttpServer::new(move || {
+        App::new()
+            .data(server.clone())
+            .route("/ws/chat/", web::get().to(chat_route))
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await
+}
+// </chat-main>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use futures::{SinkExt, StreamExt};
+
+    fn chat_app(
+        server: Addr<server::ChatServer>,
+        heartbeat: Heartbeat,
+    ) -> test::TestServer {
+        test::start(move || {
+            App::new()
+                .data(server.clone())
+                .data(heartbeat)
+                .route("/ws/", web::get().to(chat_route))
+        })
+    }
+
+    /// Next text frame, skipping the server's pings
+    async fn next_text<S>(client: &mut S) -> String
+    where
+        S: futures::Stream<Item = Result<ws::Frame, ws::ProtocolError>>
+            + Unpin,
+    {
+        loop {
+            match client.next().await {
+                Some(Ok(ws::Frame::Text(text))) => {
+                    return String::from_utf8(text.to_vec()).unwrap()
+                }
+                Some(Ok(ws::Frame::Ping(_))) => continue,
+                other => panic!("unexpected frame: {:?}", other),
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_broadcast_to_room() {
+        let server = server::ChatServer::default().start();
+        let mut srv = chat_app(server, Heartbeat::default());
+
+        let mut alice = srv.ws_at("/ws/").await.unwrap();
+        alice
+            .send(ws::Message::Text("/nick alice".into()))
+            .await
+            .unwrap();
+        assert_eq!(next_text(&mut alice).await, "* nick name changed");
+
+        let mut bob = srv.ws_at("/ws/").await.unwrap();
+        assert_eq!(next_text(&mut alice).await, "* guest2 joined");
+        bob.send(ws::Message::Text("/nick bob".into()))
+            .await
+            .unwrap();
+        assert_eq!(next_text(&mut bob).await, "* nick name changed");
+        assert_eq!(
+            next_text(&mut alice).await,
+            "* guest2 is now known as bob"
+        );
+
+        let mut carol = srv.ws_at("/ws/").await.unwrap();
+        assert_eq!(next_text(&mut alice).await, "* guest3 joined");
+        assert_eq!(next_text(&mut bob).await, "* guest3 joined");
+
+        // nick names are unique
+        carol
+            .send(ws::Message::Text("/nick alice".into()))
+            .await
+            .unwrap();
+        assert_eq!(
+            next_text(&mut carol).await,
+            "! nick name `alice` is already taken"
+        );
+
+        // carol moves to another room and no longer sees the main room
+        carol
+            .send(ws::Message::Text("/join rust".into()))
+            .await
+            .unwrap();
+        assert_eq!(next_text(&mut carol).await, "* joined rust");
+        assert_eq!(next_text(&mut alice).await, "* guest3 left");
+        assert_eq!(next_text(&mut bob).await, "* guest3 left");
+
+        alice.send(ws::Message::Text("hello".into())).await.unwrap();
+        assert_eq!(next_text(&mut bob).await, "alice: hello");
+
+        carol.send(ws::Message::Text("/list".into())).await.unwrap();
+        assert_eq!(next_text(&mut carol).await, "* rooms: main, rust");
+
+        // the first message carol receives is bob's reply in her room
+        bob.send(ws::Message::Text("/join rust".into()))
+            .await
+            .unwrap();
+        assert_eq!(next_text(&mut bob).await, "* joined rust");
+        assert_eq!(next_text(&mut carol).await, "* bob joined");
+        assert_eq!(next_text(&mut alice).await, "* bob left");
+    }
+
+    #[actix_rt::test]
+    async fn test_close_reasons() {
+        let server = server::ChatServer::default().start();
+        let mut srv = chat_app(server, Heartbeat::default());
+
+        let mut client = srv.ws_at("/ws/").await.unwrap();
+        client
+            .send(ws::Message::Binary("data".into()))
+            .await
+            .unwrap();
+
+        match client.next().await {
+            Some(Ok(ws::Frame::Close(Some(reason)))) => {
+                assert_eq!(reason.code, ws::CloseCode::Unsupported);
+            }
+            other => panic!("expected close frame, got {:?}", other),
+        }
+
+        let mut client = srv.ws_at("/ws/").await.unwrap();
+        client
+            .send(ws::Message::Continuation(actix_http::ws::Item::FirstText(
+                "part".into(),
+            )))
+            .await
+            .unwrap();
+
+        match client.next().await {
+            Some(Ok(ws::Frame::Close(Some(reason)))) => {
+                assert_eq!(reason.code, ws::CloseCode::Unsupported);
+                assert_eq!(
+                    reason.description.as_deref(),
+                    Some("fragmented messages are not supported")
+                );
+            }
+            other => panic!("expected close frame, got {:?}", other),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_nick_rules() {
+        let server = server::ChatServer::default().start();
+        let mut srv = chat_app(server, Heartbeat::default());
+
+        let mut alice = srv.ws_at("/ws/").await.unwrap();
+        for _ in 0..2 {
+            alice
+                .send(ws::Message::Text("/nick alice".into()))
+                .await
+                .unwrap();
+            assert_eq!(next_text(&mut alice).await, "* nick name changed");
+        }
+
+        // names that would let messages look like they came from someone
+        // else, or like server notices
+        let long = "a".repeat(33);
+        for nick in &["bob: hi", "bob\u{7}", "! bob", "*bob", long.as_str()] {
+            alice
+                .send(ws::Message::Text(format!("/nick {}", nick)))
+                .await
+                .unwrap();
+            assert_eq!(
+                next_text(&mut alice).await,
+                format!("! `{}` is not a valid nick name", nick)
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_guest_nicks_stay_unique() {
+        let server = server::ChatServer::default().start();
+        let mut srv = chat_app(server, Heartbeat::default());
+
+        let mut alice = srv.ws_at("/ws/").await.unwrap();
+        alice
+            .send(ws::Message::Text("/nick guest2".into()))
+            .await
+            .unwrap();
+        assert_eq!(next_text(&mut alice).await, "* nick name changed");
+
+        // the next guest skips the name that is already taken
+        let _bob = srv.ws_at("/ws/").await.unwrap();
+        assert_eq!(next_text(&mut alice).await, "* guest3 joined");
+    }
+
+    #[actix_rt::test]
+    async fn test_heartbeat_timeout() {
+        let server = server::ChatServer::default().start();
+        let mut srv = chat_app(
+            server.clone(),
+            Heartbeat {
+                interval: Duration::from_millis(20),
+                timeout: Duration::from_millis(60),
+            },
+        );
+
+        let mut silent = srv.ws_at("/ws/").await.unwrap();
+        let mut pings = 0;
+
+        // never answer the pings
+        let reason = loop {
+            match silent.next().await {
+                Some(Ok(ws::Frame::Ping(_))) => pings += 1,
+                Some(Ok(ws::Frame::Close(reason))) => break reason.unwrap(),
+                other => panic!("unexpected frame: {:?}", other),
+            }
+        };
+
+        assert!(pings > 0);
+        assert_eq!(reason.code, ws::CloseCode::Away);
+        assert_eq!(reason.description.as_deref(), Some("heartbeat timed out"));
+
+        // the dropped session was removed from the server
+        let mut other = srv.ws_at("/ws/").await.unwrap();
+        other
+            .send(ws::Message::Text("/nick guest1".into()))
+            .await
+            .unwrap();
+        assert_eq!(next_text(&mut other).await, "* nick name changed");
+    }
+}